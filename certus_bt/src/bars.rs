@@ -0,0 +1,294 @@
+use certus_core::data::{Bar, MarketData};
+
+/// trait for streaming bar builders
+/// Input can be ticks, quotes or bars, a tick is treated as a single print bar and quotes by their mid price
+pub trait BarBuilder {
+    /// Feed new market data, returns the bars completed by it
+    fn update(&mut self, market_data: MarketData) -> Vec<Bar>;
+
+    /// Emit the partially formed bar, e.g. at the end of the data feed
+    fn flush(&mut self) -> Option<Bar>;
+
+    /// Build bars from historic data
+    fn build(&mut self, data: &[MarketData]) -> Vec<MarketData> {
+        let mut result: Vec<MarketData> = Vec::new();
+        for single_data in data.iter() {
+            result.extend(self.update(*single_data).into_iter().map(MarketData::Bar));
+        }
+        if let Some(bar) = self.flush() {
+            result.push(MarketData::Bar(bar));
+        }
+        result
+    }
+}
+
+fn as_bar(market_data: MarketData) -> Bar {
+    match market_data {
+        MarketData::Bar(bar) => bar,
+        MarketData::Tick(tick) => Bar {
+            timestamp: tick.timestamp,
+            open: tick.price,
+            high: tick.price,
+            low: tick.price,
+            close: tick.price,
+            volume: tick.size,
+        },
+        MarketData::Quote(quote) => Bar {
+            timestamp: quote.timestamp,
+            open: quote.mid(),
+            high: quote.mid(),
+            low: quote.mid(),
+            close: quote.mid(),
+            volume: 0.0,
+        },
+        MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar(),
+    }
+}
+
+fn merge(current: &mut Bar, bar: &Bar) {
+    current.high = current.high.max(bar.high);
+    current.low = current.low.min(bar.low);
+    current.close = bar.close;
+    current.volume += bar.volume;
+}
+
+/// enum defining how activity is measured for `ActivityBarBuilder`
+#[derive(Debug, Clone, Copy)]
+pub enum BarActivity {
+    /// Number of ticks (or input bars)
+    Ticks,
+    /// Traded volume
+    Volume,
+    /// Traded value, close price times volume
+    Dollar,
+}
+
+/// Builds a bar every time the accumulated activity reaches the threshold
+pub struct ActivityBarBuilder {
+    pub activity: BarActivity,
+    pub threshold: f64,
+    accumulated: f64,
+    current: Option<Bar>,
+}
+
+impl ActivityBarBuilder {
+    pub fn new(activity: BarActivity, threshold: f64) -> Self {
+        assert!(threshold > 0.0, "threshold must be larger than 0");
+
+        Self {
+            activity,
+            threshold,
+            accumulated: 0.0,
+            current: None,
+        }
+    }
+
+    /// Bars of `ticks` ticks
+    pub fn ticks(ticks: usize) -> Self {
+        Self::new(BarActivity::Ticks, ticks as f64)
+    }
+
+    /// Bars of at least `volume` traded volume
+    pub fn volume(volume: f64) -> Self {
+        Self::new(BarActivity::Volume, volume)
+    }
+
+    /// Bars of at least `value` traded value
+    pub fn dollar(value: f64) -> Self {
+        Self::new(BarActivity::Dollar, value)
+    }
+
+    fn activity_of(&self, bar: &Bar) -> f64 {
+        match self.activity {
+            BarActivity::Ticks => 1.0,
+            BarActivity::Volume => bar.volume,
+            BarActivity::Dollar => bar.close * bar.volume,
+        }
+    }
+}
+
+impl BarBuilder for ActivityBarBuilder {
+    fn update(&mut self, market_data: MarketData) -> Vec<Bar> {
+        let bar = as_bar(market_data);
+        self.accumulated += self.activity_of(&bar);
+
+        match self.current.as_mut() {
+            Some(current) => merge(current, &bar),
+            None => self.current = Some(bar),
+        }
+
+        if self.accumulated < self.threshold {
+            return Vec::new();
+        }
+
+        self.accumulated = 0.0;
+        self.current.take().into_iter().collect()
+    }
+
+    fn flush(&mut self) -> Option<Bar> {
+        self.accumulated = 0.0;
+        self.current.take()
+    }
+}
+
+/// Builds bars spanning a fixed price range
+/// A new bar is started when the next price would extend the bar beyond the range
+pub struct RangeBarBuilder {
+    pub range: f64,
+    current: Option<Bar>,
+}
+
+impl RangeBarBuilder {
+    pub fn new(range: f64) -> Self {
+        assert!(range > 0.0, "range must be larger than 0");
+
+        Self {
+            range,
+            current: None,
+        }
+    }
+}
+
+impl BarBuilder for RangeBarBuilder {
+    fn update(&mut self, market_data: MarketData) -> Vec<Bar> {
+        let bar = as_bar(market_data);
+        let mut completed = Vec::new();
+
+        if let Some(current) = self.current.as_ref() {
+            let high = current.high.max(bar.high);
+            let low = current.low.min(bar.low);
+            if high - low > self.range {
+                completed.extend(self.current.take());
+            }
+        }
+
+        match self.current.as_mut() {
+            Some(current) => merge(current, &bar),
+            None => self.current = Some(bar),
+        }
+
+        if let Some(current) = self.current.as_ref()
+            && current.high - current.low >= self.range
+        {
+            completed.extend(self.current.take());
+        }
+
+        completed
+    }
+
+    fn flush(&mut self) -> Option<Bar> {
+        self.current.take()
+    }
+}
+
+/// Builds Renko bricks of `brick_size` from close prices
+/// A brick is added when the price moves a full brick beyond the last brick
+pub struct RenkoBuilder {
+    pub brick_size: f64,
+    // Bottom and top of the last brick, equal before the first brick
+    low: Option<f64>,
+    high: f64,
+    volume: f64,
+}
+
+impl RenkoBuilder {
+    pub fn new(brick_size: f64) -> Self {
+        assert!(brick_size > 0.0, "brick_size must be larger than 0");
+
+        Self {
+            brick_size,
+            low: None,
+            high: 0.0,
+            volume: 0.0,
+        }
+    }
+
+    fn brick(&mut self, bar: &Bar, open: f64, close: f64) -> Bar {
+        let volume = self.volume;
+        self.volume = 0.0;
+        Bar {
+            timestamp: bar.timestamp,
+            open,
+            high: open.max(close),
+            low: open.min(close),
+            close,
+            volume,
+        }
+    }
+}
+
+impl BarBuilder for RenkoBuilder {
+    fn update(&mut self, market_data: MarketData) -> Vec<Bar> {
+        let bar = as_bar(market_data);
+        self.volume += bar.volume;
+
+        let Some(mut low) = self.low else {
+            self.low = Some(bar.close);
+            self.high = bar.close;
+            return Vec::new();
+        };
+
+        let mut bricks = Vec::new();
+        while bar.close >= self.high + self.brick_size {
+            let open = self.high;
+            let brick = self.brick(&bar, open, open + self.brick_size);
+            low = brick.low;
+            self.high = brick.high;
+            bricks.push(brick);
+        }
+        while bar.close <= low - self.brick_size {
+            let open = low;
+            let brick = self.brick(&bar, open, open - self.brick_size);
+            low = brick.low;
+            self.high = brick.high;
+            bricks.push(brick);
+        }
+
+        self.low = Some(low);
+        bricks
+    }
+
+    /// Renko bricks are only emitted when completed
+    fn flush(&mut self) -> Option<Bar> {
+        None
+    }
+}
+
+/// Transforms bars into Heikin-Ashi bars
+#[derive(Default)]
+pub struct HeikinAshiBuilder {
+    previous: Option<Bar>,
+}
+
+impl HeikinAshiBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BarBuilder for HeikinAshiBuilder {
+    fn update(&mut self, market_data: MarketData) -> Vec<Bar> {
+        let bar = as_bar(market_data);
+
+        let close = (bar.open + bar.high + bar.low + bar.close) / 4.0;
+        let open = match self.previous {
+            Some(previous) => (previous.open + previous.close) / 2.0,
+            None => (bar.open + bar.close) / 2.0,
+        };
+        let heikin_ashi = Bar {
+            timestamp: bar.timestamp,
+            open,
+            high: bar.high.max(open).max(close),
+            low: bar.low.min(open).min(close),
+            close,
+            volume: bar.volume,
+        };
+
+        self.previous = Some(heikin_ashi);
+        vec![heikin_ashi]
+    }
+
+    fn flush(&mut self) -> Option<Bar> {
+        None
+    }
+}
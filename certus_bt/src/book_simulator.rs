@@ -0,0 +1,250 @@
+//! Standalone level 2 fill simulator
+//! Order book updates are not market data, no data handler or engine feeds the simulator.
+//! Callers drive it with `on_book_update` and `on_trade` and submit orders to it directly.
+
+use std::collections::{HashMap, HashSet};
+
+use certus_core::core::{Fill, Order, OrderSide, OrderType};
+use certus_core::data::{BookSide, OrderBookUpdate, Tick};
+use certus_core::order_book::OrderBook;
+
+/// Order waiting in the simulator, the order size is the size left to fill
+struct SimulatedOrder {
+    order: Order,
+    /// Size resting before the order at its limit price, only traded size reduces it
+    queue_ahead: f64,
+}
+
+/// Fill simulator matching orders against a level 2 order book
+/// Market orders walk the opposite side of the book and take the liquidity they consume.
+/// Limit orders join the back of the queue at their price, they fill once the size ahead of them
+/// has traded or when the market trades through their price. Stops trigger on trades.
+/// The book has no sessions, market-on-open and market-on-close orders take liquidity like market orders.
+#[derive(Default)]
+pub struct OrderBookFillSimulator {
+    book: OrderBook,
+    orders: Vec<SimulatedOrder>,
+    fills: Vec<Fill>,
+    last_order_id: usize,
+    /// Ids of all submitted orders, fills refer to them
+    order_ids: HashSet<usize>,
+}
+
+impl OrderBookFillSimulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    /// Orders that are not completely filled, with the size left to fill
+    pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.iter().map(|simulated| &simulated.order)
+    }
+
+    /// Size ahead of a resting limit order in the queue at its price
+    pub fn queue_ahead(&self, order_id: usize) -> Option<f64> {
+        self.orders
+            .iter()
+            .find(|simulated| simulated.order.id == Some(order_id))
+            .map(|simulated| simulated.queue_ahead)
+    }
+
+    /// Submit an order against the current book, returns the id of the order
+    /// An id supplied with the order is kept unless another order already used it, then a new id is assigned
+    pub fn submit(&mut self, mut order: Order) -> usize {
+        let order_id = match order.id {
+            Some(id) if self.order_ids.insert(id) => {
+                self.last_order_id = self.last_order_id.max(id);
+                id
+            }
+            requested => {
+                let id = self.next_order_id();
+                if let Some(requested) = requested {
+                    log::warn!("Order id {} is already used, order submitted as {}", requested, id);
+                }
+                id
+            }
+        };
+        order.id = Some(order_id);
+        self.execute(SimulatedOrder {
+            order,
+            queue_ahead: 0.0,
+        });
+        order_id
+    }
+
+    fn next_order_id(&mut self) -> usize {
+        loop {
+            self.last_order_id += 1;
+            if self.order_ids.insert(self.last_order_id) {
+                return self.last_order_id;
+            }
+        }
+    }
+
+    pub fn cancel(&mut self, order_id: usize) -> bool {
+        let count = self.orders.len();
+        self.orders.retain(|simulated| simulated.order.id != Some(order_id));
+        self.orders.len() != count
+    }
+
+    /// Apply an update to the book, fills waiting market orders and limit orders the book now crosses
+    /// A crossed limit order fills at its limit up to the size on the crossing levels, which it takes from the book
+    pub fn on_book_update(&mut self, update: &OrderBookUpdate) {
+        self.book.apply(update);
+
+        for mut simulated in std::mem::take(&mut self.orders) {
+            match simulated.order.order_type {
+                OrderType::Market | OrderType::MarketOnOpen | OrderType::MarketOnClose => {
+                    walk_book(&mut self.book, &mut simulated.order, None, false, &mut self.fills)
+                }
+                OrderType::Limit(limit) => {
+                    let side = own_side(&simulated.order.side);
+                    // The resting order has priority, the crossing liquidity trades at its limit
+                    walk_book(&mut self.book, &mut simulated.order, Some(limit), true, &mut self.fills);
+                    simulated.queue_ahead = simulated.queue_ahead.min(self.book.size_at(side, limit));
+                }
+                OrderType::Stop(_) | OrderType::StopLimit(_, _) => {}
+            }
+            if simulated.order.size > 0.0 {
+                self.orders.push(simulated);
+            }
+        }
+    }
+
+    /// Apply a trade, it triggers stops and fills limit orders at or behind its price
+    pub fn on_trade(&mut self, trade: &Tick) {
+        let mut triggered = Vec::new();
+        // Size of the trade left at each of our limit prices, shared by the orders queued there
+        let mut left_at_price: HashMap<u64, f64> = HashMap::new();
+
+        for mut simulated in std::mem::take(&mut self.orders) {
+            let order = &mut simulated.order;
+            match order.order_type {
+                OrderType::Limit(limit) => {
+                    let traded_through = match order.side {
+                        OrderSide::Buy => trade.price < limit,
+                        OrderSide::Sell => trade.price > limit,
+                    };
+                    if traded_through {
+                        let size = order.size;
+                        record_fill(&mut self.fills, order, size, limit);
+                    } else if trade.price == limit {
+                        let left = left_at_price.entry(limit.to_bits()).or_insert(trade.size);
+                        let consumed = simulated.queue_ahead.min(*left);
+                        simulated.queue_ahead -= consumed;
+                        *left -= consumed;
+                        let size = order.size.min(*left);
+                        *left -= size;
+                        record_fill(&mut self.fills, order, size, limit);
+                    }
+                }
+                OrderType::Stop(stop) | OrderType::StopLimit(stop, _) => {
+                    let hit = match order.side {
+                        OrderSide::Buy => trade.price >= stop,
+                        OrderSide::Sell => trade.price <= stop,
+                    };
+                    if hit {
+                        order.order_type = match order.order_type {
+                            OrderType::StopLimit(_, limit) => OrderType::Limit(limit),
+                            _ => OrderType::Market,
+                        };
+                        triggered.push(simulated);
+                        continue;
+                    }
+                }
+                OrderType::Market | OrderType::MarketOnOpen | OrderType::MarketOnClose => {}
+            }
+            if simulated.order.size > 0.0 {
+                self.orders.push(simulated);
+            }
+        }
+
+        for simulated in triggered {
+            log::debug!("Order {:?} triggered by trade at {}", simulated.order.id, trade.price);
+            self.execute(simulated);
+        }
+    }
+
+    /// Take liquidity for marketable orders, the rest of a limit order joins the queue at its price
+    fn execute(&mut self, mut simulated: SimulatedOrder) {
+        match simulated.order.order_type {
+            OrderType::Market | OrderType::MarketOnOpen | OrderType::MarketOnClose => {
+                walk_book(&mut self.book, &mut simulated.order, None, false, &mut self.fills)
+            }
+            OrderType::Limit(limit) => {
+                walk_book(&mut self.book, &mut simulated.order, Some(limit), false, &mut self.fills);
+                simulated.queue_ahead = self.book.size_at(own_side(&simulated.order.side), limit);
+            }
+            OrderType::Stop(_) | OrderType::StopLimit(_, _) => {}
+        }
+        if simulated.order.size > 0.0 {
+            self.orders.push(simulated);
+        }
+    }
+}
+
+fn own_side(side: &OrderSide) -> BookSide {
+    match side {
+        OrderSide::Buy => BookSide::Bid,
+        OrderSide::Sell => BookSide::Ask,
+    }
+}
+
+fn opposite_side(side: &OrderSide) -> BookSide {
+    match side {
+        OrderSide::Buy => BookSide::Ask,
+        OrderSide::Sell => BookSide::Bid,
+    }
+}
+
+/// Fill the order level by level from the best opposite price, up to `limit`
+/// With `at_limit` the fills are priced at the limit instead of the level price
+fn walk_book(book: &mut OrderBook, order: &mut Order, limit: Option<f64>, at_limit: bool, fills: &mut Vec<Fill>) {
+    let side = opposite_side(&order.side);
+    while order.size > 0.0 {
+        let Some(level) = book.levels(side).first().copied() else {
+            return;
+        };
+        let within_limit = limit.is_none_or(|limit| match side {
+            BookSide::Ask => level.price <= limit,
+            BookSide::Bid => level.price >= limit,
+        });
+        if !within_limit {
+            return;
+        }
+
+        let size = order.size.min(level.size);
+        book.set_level(side, level.price, level.size - size);
+        let price = match limit {
+            Some(limit) if at_limit => limit,
+            _ => level.price,
+        };
+        record_fill(fills, order, size, price);
+    }
+}
+
+fn record_fill(fills: &mut Vec<Fill>, order: &mut Order, size: f64, price: f64) {
+    if size <= 0.0 {
+        return;
+    }
+    order.size -= size;
+    let fill = Fill {
+        id: fills.len() + 1,
+        instrument: order.instrument,
+        strategy_id: order.strategy_id,
+        order_id: order.id.unwrap_or_default(),
+        side: order.side.clone(),
+        size,
+        price,
+    };
+    log::info!("Order {} filled: {}", fill.order_id, fill);
+    fills.push(fill);
+}
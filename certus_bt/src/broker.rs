@@ -1,14 +1,25 @@
-use std::{collections::HashMap, mem};
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
 
 use certus_core::core::{Instrument, PositionManager};
+use certus_core::session::SessionType;
 use certus_core::{
     broker::{Account, Broker},
     core::{Fill, Order, OrderSide, OrderType, Trade},
-    data::MarketData,
+    data::{Bar, MarketData, Timestamp},
 };
+use chrono::Duration;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::continuous::RollEvent;
+use crate::corporate_actions::{CorporateActionKind, CorporateActions, DividendPayment};
+use crate::impact::MarketImpact;
 
 pub struct BacktestingBroker {
-    _account: Account,
+    account: Account,
     orders: HashMap<usize, Order>,
     unfilled_orders: Vec<usize>,
     last_order_id: usize,
@@ -21,6 +32,235 @@ pub struct BacktestingBroker {
     last_instrument_id: u32,
     instruments: HashMap<u32, Instrument>,
     position_manager: PositionManager,
+    roll_schedules: HashMap<u32, RollSchedule>,
+    corporate_actions: HashMap<u32, CorporateActionSchedule>,
+    dividends: Vec<DividendPayment>,
+    /// Limit orders that did not fill on the first quote after they were placed
+    resting_orders: HashSet<usize>,
+    limit_fill_model: LimitFillModel,
+    rng: StdRng,
+    slippage_model: SlippageModel,
+    /// Last traded price, the close of the last bar or the last tick
+    last_price: Option<f64>,
+    volume_limits: VolumeLimits,
+    market_impact: Option<MarketImpact>,
+    /// Accumulated permanent impact of earlier fills of each instrument as a fraction of the price, positive after buying
+    permanent_impact: HashMap<u32, f64>,
+    /// Time of the market data being simulated
+    timestamp: Timestamp,
+    /// Fills of each order with the time of the market data that filled them
+    order_fills: HashMap<usize, Vec<(usize, Timestamp)>>,
+    /// Estimated size still ahead of limit orders in the queue at their price
+    queue_ahead: HashMap<usize, f64>,
+    intrabar_path: IntrabarPath,
+    /// Lower timeframe data replayed inside the bars it covers, sorted by timestamp
+    intrabar_data: Vec<MarketData>,
+    bar_duration: Duration,
+    latency: Latency,
+    latency_rng: StdRng,
+    order_times: HashMap<usize, OrderTimes>,
+    cancelled_orders: HashSet<usize>,
+    /// Session open or close at which market-on-open and market-on-close orders fill
+    auction_times: HashMap<usize, Timestamp>,
+    /// Last market data simulated, the signal bar of the orders placed after it
+    last_market_data: Option<MarketData>,
+    fill_on_signal_close: bool,
+    /// Whether the FIFO queue model warned about market data without traded volume at a price
+    warned_fifo_without_ticks: bool,
+}
+
+/// Limits on the liquidity orders may take from market data, orders larger than that fill over several bars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeLimits {
+    /// Fraction of the volume, or quoted size, all orders together may take per market data, e.g. 0.1 for 10%
+    pub max_participation: f64,
+    /// Market data with less volume, or quoted size, fills no orders
+    pub min_volume: f64,
+}
+
+impl Default for VolumeLimits {
+    fn default() -> Self {
+        Self {
+            max_participation: 1.0,
+            min_volume: 0.0,
+        }
+    }
+}
+
+impl VolumeLimits {
+    /// Size orders may take from market data with `volume`
+    pub fn available(&self, volume: f64) -> f64 {
+        if volume < self.min_volume {
+            0.0
+        } else {
+            volume * self.max_participation
+        }
+    }
+}
+
+/// Progress of an order that may fill over several market data
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderFillReport {
+    pub order_id: usize,
+    pub size: f64,
+    pub filled: f64,
+    pub remaining: f64,
+    /// Size weighted average price of the fills, None before the first fill
+    pub average_price: Option<f64>,
+    pub fills: Vec<usize>,
+    /// Time of the market data of the first and last fill
+    pub first_fill: Option<Timestamp>,
+    pub last_fill: Option<Timestamp>,
+}
+
+/// enum defining the slippage of orders taking liquidity on trade data, market orders and triggered stops
+/// Quotes already fill at the opposite side of the spread and have no slippage
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SlippageModel {
+    #[default]
+    None,
+    /// Fixed price difference
+    Fixed(f64),
+    /// Fraction of the price, e.g. 0.001 for 10 basis points
+    Percentage(f64),
+}
+
+impl SlippageModel {
+    /// Move the price against the side of the order
+    pub fn apply(&self, side: &OrderSide, price: f64) -> f64 {
+        let slippage = match *self {
+            SlippageModel::None => 0.0,
+            SlippageModel::Fixed(amount) => amount,
+            SlippageModel::Percentage(fraction) => price * fraction,
+        };
+        match side {
+            OrderSide::Buy => price + slippage,
+            OrderSide::Sell => price - slippage,
+        }
+    }
+}
+
+/// enum defining the order in which prices are visited inside a bar
+/// Orders are filled segment by segment along the path, so an order hit earlier in the bar fills first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntrabarPath {
+    #[default]
+    OpenHighLowClose,
+    OpenLowHighClose,
+    /// Visit the extreme closest to the open first
+    NearestExtreme,
+    /// Visit the extreme against the open position first, e.g. the low for a long position,
+    /// so stops fill before targets. Only the positions of the instruments and strategies with unfilled orders count,
+    /// without a position the nearest extreme is visited first
+    Pessimistic,
+}
+
+/// enum defining whether a limit order fills on trades that touch its price without crossing it
+/// Trades through the limit always fill, on quotes resting limit orders only fill on a trade-through
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LimitFillModel {
+    /// Fill on touch, the most optimistic assumption
+    #[default]
+    Touch,
+    /// Fill only when the price trades through the limit
+    TradeThrough,
+    /// Each touch fills with a probability, the seed makes backtests reproducible
+    TouchProbability { probability: f64, seed: u64 },
+    /// The order joins the back of a FIFO queue with `queue_ahead` in front of it and fills from the
+    /// volume traded at the limit once the queue has traded. Only ticks tell the volume traded at a price,
+    /// on bars touched orders keep resting until a trade through the limit fills them and a warning is logged
+    FifoQueue { queue_ahead: f64 },
+}
+
+/// enum defining the distribution of a delay between the strategy and the exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatencyModel {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// Uniformly distributed between `min` and `max`
+    Uniform { min: Duration, max: Duration },
+    /// `min` plus an exponentially distributed delay with mean `mean`, for the long tail of real latencies
+    Exponential { min: Duration, mean: Duration },
+}
+
+impl LatencyModel {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match *self {
+            LatencyModel::None => Duration::zero(),
+            LatencyModel::Fixed(delay) => delay,
+            LatencyModel::Uniform { min, max } => {
+                let (min, max) = (nanos(min), nanos(max));
+                Duration::nanoseconds(if max > min { rng.random_range(min..=max) } else { min })
+            }
+            LatencyModel::Exponential { min, mean } => {
+                let extra = -(nanos(mean) as f64) * (1.0 - rng.random::<f64>()).ln();
+                min + Duration::nanoseconds(extra as i64)
+            }
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> i64 {
+    duration.num_nanoseconds().unwrap_or(i64::MAX)
+}
+
+/// Latencies of order messages measured against market data timestamps
+/// An order reaches the exchange and can fill `submission` after it was placed, it is acknowledged
+/// `acknowledgement` after that. A cancel takes effect `cancel` after it was sent, the order can fill until then
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Latency {
+    pub submission: LatencyModel,
+    pub acknowledgement: LatencyModel,
+    pub cancel: LatencyModel,
+    /// Seed of the sampled latencies, makes backtests reproducible
+    pub seed: u64,
+}
+
+/// Status of an order as seen from the strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    /// Sent but not acknowledged by the exchange yet
+    Submitted,
+    /// Acknowledged and not completely filled
+    Working,
+    /// Cancel sent but not effective yet, the order can still fill
+    PendingCancel,
+    Cancelled,
+    Filled,
+}
+
+/// Times at which an order reaches the exchange, is acknowledged and is cancelled
+struct OrderTimes {
+    arrival: Timestamp,
+    acknowledged: Timestamp,
+    cancel: Option<Timestamp>,
+}
+
+/// Rolls of the continuous series of an instrument, `next` is the first roll not simulated yet
+struct RollSchedule {
+    rolls: Vec<RollEvent>,
+    next: usize,
+}
+
+/// Corporate actions of an instrument, `next` is the first action not applied yet
+struct CorporateActionSchedule {
+    actions: CorporateActions,
+    next: usize,
+}
+
+/// Prices of one side of the book over a quote or quote bar, and the size quoted there
+struct QuoteSide {
+    open: f64,
+    low: f64,
+    high: f64,
+    size: f64,
+}
+
+impl QuoteSide {
+    fn new(open: f64, low: f64, high: f64, size: f64) -> Self {
+        Self { open, low, high, size }
+    }
 }
 
 struct PendingFill {
@@ -33,7 +273,6 @@ struct PendingFill {
     instrument: u32,
     strategy_id: usize,
     side: OrderSide,
-    order_type: OrderType,
 }
 
 #[derive(Default)]
@@ -45,7 +284,7 @@ struct TradeMetrics {
 impl BacktestingBroker {
     pub fn new(starting_balance: f64) -> Self {
         Self {
-            _account: Account {
+            account: Account {
                 id: String::from("BACKTEST"),
                 balance: starting_balance,
             },
@@ -61,45 +300,419 @@ impl BacktestingBroker {
             last_instrument_id: 0,
             instruments: HashMap::new(),
             position_manager: PositionManager::new(),
+            roll_schedules: HashMap::new(),
+            corporate_actions: HashMap::new(),
+            dividends: Vec::new(),
+            resting_orders: HashSet::new(),
+            limit_fill_model: LimitFillModel::default(),
+            rng: StdRng::seed_from_u64(0),
+            slippage_model: SlippageModel::default(),
+            last_price: None,
+            volume_limits: VolumeLimits::default(),
+            market_impact: None,
+            permanent_impact: HashMap::new(),
+            timestamp: Timestamp::default(),
+            order_fills: HashMap::new(),
+            queue_ahead: HashMap::new(),
+            intrabar_path: IntrabarPath::default(),
+            intrabar_data: Vec::new(),
+            bar_duration: Duration::zero(),
+            latency: Latency::default(),
+            latency_rng: StdRng::seed_from_u64(0),
+            order_times: HashMap::new(),
+            cancelled_orders: HashSet::new(),
+            auction_times: HashMap::new(),
+            last_market_data: None,
+            fill_on_signal_close: false,
+            warned_fifo_without_ticks: false,
         }
     }
 
+    /// Fill market and market-on-close orders at the close of the market data they were placed after,
+    /// e.g. daily strategies trading at the close of the bar they decided on. These fills skip the latency
+    pub fn with_fill_on_signal_close(mut self, fill_on_signal_close: bool) -> Self {
+        self.fill_on_signal_close = fill_on_signal_close;
+        self
+    }
+
+    /// Delay orders and cancels, orders can't fill on market data before they reach the exchange
+    pub fn with_latency(mut self, latency: Latency) -> Self {
+        self.latency_rng = StdRng::seed_from_u64(latency.seed);
+        self.latency = latency;
+        self
+    }
+
+    /// Cap the share of the volume, or quoted size, the orders may take from each market data
+    pub fn with_volume_limits(mut self, volume_limits: VolumeLimits) -> Self {
+        self.volume_limits = volume_limits;
+        self
+    }
+
+    /// Price impact of market orders and triggered stops
+    pub fn with_market_impact(mut self, market_impact: MarketImpact) -> Self {
+        self.market_impact = Some(market_impact);
+        self
+    }
+
+    /// Slippage of market orders and triggered stops on ticks and bars
+    pub fn with_slippage_model(mut self, slippage_model: SlippageModel) -> Self {
+        self.slippage_model = slippage_model;
+        self
+    }
+
+    /// Order of the prices inside bars without lower timeframe data
+    pub fn with_intrabar_path(mut self, intrabar_path: IntrabarPath) -> Self {
+        self.intrabar_path = intrabar_path;
+        self
+    }
+
+    /// Replay lower timeframe data, e.g. minute bars or ticks, instead of the path of the bars it covers
+    /// `bar_duration` is the duration of the simulated bars, bars without lower timeframe data use the path
+    pub fn set_intrabar_data(&mut self, mut data: Vec<MarketData>, bar_duration: Duration) {
+        data.sort_by_key(|market_data| market_data.timestamp());
+        self.intrabar_data = data;
+        self.bar_duration = bar_duration;
+    }
+
+    /// Assumption for limit orders touched by trades on ticks and bars
+    pub fn with_limit_fill_model(mut self, limit_fill_model: LimitFillModel) -> Self {
+        if let LimitFillModel::TouchProbability { seed, .. } = limit_fill_model {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self.limit_fill_model = limit_fill_model;
+        self
+    }
+
+    /// Cash of the account, changed by dividends
+    pub fn balance(&self) -> f64 {
+        self.account.balance
+    }
+
+    /// Apply splits to the open trades and orders of `instrument` and pay dividends on its ex-dates
+    /// Use unadjusted prices for the instrument
+    pub fn set_corporate_actions(&mut self, instrument: u32, actions: CorporateActions) {
+        self.corporate_actions
+            .insert(instrument, CorporateActionSchedule { actions, next: 0 });
+    }
+
+    pub fn dividends(&self) -> &[DividendPayment] {
+        &self.dividends
+    }
+
+    /// Close and reopen the open trades of `instrument` at the rolls of its continuous series
+    /// Unfilled orders related to a rolled trade are moved to the new trade
+    pub fn set_roll_schedule(&mut self, instrument: u32, rolls: Vec<RollEvent>) {
+        self.roll_schedules.insert(instrument, RollSchedule { rolls, next: 0 });
+    }
+
     pub fn simulate_fills(&mut self, market_data: MarketData) {
-        let mut available_size = Self::extract_liquidity(&market_data);
+        self.timestamp = market_data.timestamp();
+        self.apply_corporate_actions(market_data.timestamp());
+        self.simulate_rolls(market_data.timestamp());
 
-        if available_size <= 0.0 {
-            return;
+        if let MarketData::Bar(bar) = market_data {
+            let end = bar.timestamp + self.bar_duration;
+            let start_index = self.intrabar_data.partition_point(|data| data.timestamp() < bar.timestamp);
+            let end_index = self.intrabar_data.partition_point(|data| data.timestamp() < end);
+            if start_index < end_index {
+                for index in start_index..end_index {
+                    self.fill_market_data(self.intrabar_data[index]);
+                }
+                // Orders placed after the bar see the bar, not the last intrabar data inside it
+                self.timestamp = market_data.timestamp();
+                self.last_market_data = Some(market_data);
+                return;
+            }
         }
+        self.fill_market_data(market_data);
+    }
+
+    fn fill_market_data(&mut self, market_data: MarketData) {
+        self.timestamp = market_data.timestamp();
+        self.apply_cancels();
 
+        // Orders still on their way to the exchange sit out this market data
+        let (arrived, in_flight): (Vec<usize>, Vec<usize>) = mem::take(&mut self.unfilled_orders)
+            .into_iter()
+            .partition(|order_id| self.has_arrived(*order_id));
+        self.unfilled_orders = arrived;
+
+        // Auctions cross whole orders at one price, the market impact applies to them but the volume limits don't
+        self.fill_auction_orders(&market_data);
+        match market_data {
+            MarketData::Tick(_) | MarketData::Bar(_) => self.simulate_trade_fills(&market_data),
+            MarketData::Quote(_) | MarketData::QuoteBar(_) => self.simulate_quote_fills(&market_data),
+        }
+
+        // Limit orders left after market data rest in the book from now on
+        let resting: HashSet<usize> = self
+            .unfilled_orders
+            .iter()
+            .copied()
+            .filter(|order_id| matches!(self.orders[order_id].order_type, OrderType::Limit(_)))
+            .collect();
+        self.resting_orders = resting;
+
+        self.unfilled_orders.extend(in_flight);
+        self.unfilled_orders.sort_unstable();
+        self.last_market_data = Some(market_data);
+    }
+
+    /// Session open or close at which an order fills, from the calendar of its instrument
+    /// None for other orders and for instruments without a calendar
+    fn auction_time(&self, order: &Order) -> Option<Timestamp> {
+        let calendar = self.instruments.get(&order.instrument)?.calendar.as_ref()?;
+        let now = self.timestamp.to_local(&calendar.time_zone);
+        let auction = match order.order_type {
+            // The open of the session the order was placed at has passed
+            OrderType::MarketOnOpen => calendar
+                .next_session(now + Duration::nanoseconds(1), SessionType::Regular)?
+                .open,
+            OrderType::MarketOnClose => calendar
+                .session_at(now, SessionType::Regular)
+                .or_else(|| calendar.next_session(now, SessionType::Regular))?
+                .close,
+            _ => return None,
+        };
+        Some(Timestamp::from_local(auction, &calendar.time_zone))
+    }
+
+    /// Fill market-on-open orders at the opening price of the first market data of their session and
+    /// market-on-close orders at the closing price of the last market data before their session closed,
+    /// once market data after the close arrives. Without a calendar they fill at the open or close of the
+    /// next market data. The auctions are not limited by the volume limits.
+    fn fill_auction_orders(&mut self, market_data: &MarketData) {
         let order_queue = mem::take(&mut self.unfilled_orders);
         let mut remaining_orders = Vec::new();
 
-        let prices: Vec<f64> = match market_data {
-            MarketData::Tick(tick) => vec![tick.price],
-            MarketData::Bar(bar) => vec![bar.open, bar.high, bar.low, bar.close],
+        for order_id in order_queue {
+            let order = &self.orders[&order_id];
+            let auction = self.auction_times.get(&order_id).copied();
+            let price = match order.order_type {
+                OrderType::MarketOnOpen => auction
+                    .is_none_or(|open| self.timestamp >= open)
+                    .then(|| opening_price(market_data, &order.side)),
+                OrderType::MarketOnClose => match auction {
+                    None => Some(closing_price(market_data, &order.side)),
+                    Some(close) if self.timestamp >= close => Some(
+                        self.last_market_data
+                            .map_or(opening_price(market_data, &order.side), |data| closing_price(&data, &order.side)),
+                    ),
+                    Some(_) => None,
+                },
+                _ => None,
+            };
+            let Some(price) = price else {
+                remaining_orders.push(order_id);
+                continue;
+            };
+
+            let mut available_size = f64::INFINITY;
+            let Some(pending_fill) = self.prepare_order_fill(order_id, &mut available_size) else {
+                remaining_orders.push(order_id);
+                continue;
+            };
+            let price = self.impacted_price(&pending_fill, price);
+            self.record_fill(&pending_fill, price);
+            self.auction_times.remove(&order_id);
+        }
+
+        self.unfilled_orders = remaining_orders;
+    }
+
+    fn has_arrived(&self, order_id: usize) -> bool {
+        self.order_times
+            .get(&order_id)
+            .is_none_or(|times| times.arrival <= self.timestamp)
+    }
+
+    /// Remove the orders whose cancel reached the exchange
+    fn apply_cancels(&mut self) {
+        let timestamp = self.timestamp;
+        let cancelled: Vec<usize> = self
+            .unfilled_orders
+            .iter()
+            .copied()
+            .filter(|order_id| {
+                self.order_times
+                    .get(order_id)
+                    .and_then(|times| times.cancel)
+                    .is_some_and(|cancel| cancel <= timestamp)
+            })
+            .collect();
+        for order_id in cancelled {
+            self.remove_order(order_id);
+        }
+    }
+
+    fn remove_order(&mut self, order_id: usize) {
+        self.unfilled_orders.retain(|id| *id != order_id);
+        self.resting_orders.remove(&order_id);
+        self.queue_ahead.remove(&order_id);
+        self.cancelled_orders.insert(order_id);
+        log::debug!("Order {} cancelled", order_id);
+    }
+
+    /// Status of an order at the time of the last market data, None for unknown orders
+    pub fn order_status(&self, order_id: usize) -> Option<OrderStatus> {
+        self.orders.get(&order_id)?;
+        if self.cancelled_orders.contains(&order_id) {
+            return Some(OrderStatus::Cancelled);
+        }
+        if !self.unfilled_orders.contains(&order_id) {
+            return Some(OrderStatus::Filled);
+        }
+        let times = self.order_times.get(&order_id);
+        if times.is_some_and(|times| times.cancel.is_some()) {
+            Some(OrderStatus::PendingCancel)
+        } else if times.is_some_and(|times| times.acknowledged > self.timestamp) {
+            Some(OrderStatus::Submitted)
+        } else {
+            Some(OrderStatus::Working)
+        }
+    }
+
+    fn simulate_trade_fills(&mut self, market_data: &MarketData) {
+        let (path, volume) = match market_data {
+            MarketData::Tick(tick) => (vec![tick.price], tick.size),
+            MarketData::Bar(bar) => (self.intrabar_prices(bar), bar.volume),
+            _ => return,
         };
+        let mut available_size = self.volume_limits.available(volume);
+
+        // The first segment is the open, every next segment moves to the next price of the path
+        if available_size > 0.0 {
+            for (index, price) in path.iter().enumerate() {
+                let start = path[index.saturating_sub(1)];
+                let previous = if index == 0 { self.last_price } else { Some(start) };
+                let range = (start.min(*price), start.max(*price));
+                self.fill_segment(market_data, previous, start, range, &mut available_size);
+            }
+        }
+        self.last_price = path.last().copied();
+    }
+
+    /// Prices of a bar in the order of the intrabar path
+    fn intrabar_prices(&self, bar: &Bar) -> Vec<f64> {
+        let high_first = match self.intrabar_path {
+            IntrabarPath::OpenHighLowClose => true,
+            IntrabarPath::OpenLowHighClose => false,
+            IntrabarPath::NearestExtreme => bar.high - bar.open <= bar.open - bar.low,
+            IntrabarPath::Pessimistic => {
+                let position: f64 = self
+                    .unfilled_orders
+                    .iter()
+                    .filter_map(|order_id| self.orders.get(order_id))
+                    .map(|order| (order.strategy_id, order.instrument))
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|(strategy_id, instrument)| self.open_position(strategy_id, instrument))
+                    .sum();
+                if position == 0.0 {
+                    bar.high - bar.open <= bar.open - bar.low
+                } else {
+                    position < 0.0
+                }
+            }
+        };
+        if high_first {
+            vec![bar.open, bar.high, bar.low, bar.close]
+        } else {
+            vec![bar.open, bar.low, bar.high, bar.close]
+        }
+    }
+
+    fn open_position(&self, strategy_id: usize, instrument: u32) -> f64 {
+        self.position_manager
+            .get_open_trades(strategy_id)
+            .iter()
+            .filter_map(|trade_id| self.trades.get(trade_id))
+            .filter(|trade| trade.instrument == instrument)
+            .map(|trade| trade.size)
+            .sum()
+    }
 
-        let lowest_price = prices.iter().cloned().reduce(f64::min).unwrap();
-        let highest_price = prices.iter().cloned().reduce(f64::min).unwrap();
+    /// Fill the unfilled orders hit by prices moving from `open_price` within the range
+    /// `previous_price` is the last price before the segment, None before the first market data
+    fn fill_segment(
+        &mut self,
+        market_data: &MarketData,
+        previous_price: Option<f64>,
+        open_price: f64,
+        (lowest_price, highest_price): (f64, f64),
+        available_size: &mut f64,
+    ) {
+        let order_queue = mem::take(&mut self.unfilled_orders);
+        let mut remaining_orders = Vec::new();
 
         for order_id in order_queue {
-            if available_size <= 0.0 {
+            if *available_size <= 0.0 {
                 remaining_orders.push(order_id);
                 continue;
             }
 
-            if !self.check_order_hit(order_id, lowest_price, highest_price) {
+            let Some(price) = self.trade_fill_price(order_id, previous_price, open_price, lowest_price, highest_price)
+            else {
                 remaining_orders.push(order_id);
                 continue;
+            };
+
+            let mut fill_size = available_size.min(self.limit_fill_size(order_id, market_data, lowest_price, highest_price));
+            let Some(pending_fill) = self.prepare_order_fill(order_id, &mut fill_size) else {
+                remaining_orders.push(order_id);
+                continue;
+            };
+            *available_size -= pending_fill.fill_size;
+            let price = self.impacted_price(&pending_fill, price);
+            self.record_fill(&pending_fill, price);
+
+            if pending_fill.order_remaining > 0.0 {
+                remaining_orders.push(order_id);
+            } else {
+                self.queue_ahead.remove(&order_id);
             }
+        }
 
-            let Some(pending_fill) = self.prepare_order_fill(order_id, &mut available_size) else {
+        self.unfilled_orders = remaining_orders;
+    }
+
+    /// Buy orders fill against the ask and sell orders against the bid, limited by the size quoted there
+    /// Marketable limit orders fill at the quote, resting limit orders only when the opposite side trades through
+    fn simulate_quote_fills(&mut self, market_data: &MarketData) {
+        let (bid, ask) = match market_data {
+            MarketData::Quote(quote) => (
+                QuoteSide::new(quote.bid, quote.bid, quote.bid, quote.bid_size),
+                QuoteSide::new(quote.ask, quote.ask, quote.ask, quote.ask_size),
+            ),
+            MarketData::QuoteBar(quote_bar) => (
+                QuoteSide::new(quote_bar.bid_open, quote_bar.bid_low, quote_bar.bid_high, quote_bar.bid_size),
+                QuoteSide::new(quote_bar.ask_open, quote_bar.ask_low, quote_bar.ask_high, quote_bar.ask_size),
+            ),
+            _ => return,
+        };
+        let mut bid_size = self.volume_limits.available(bid.size);
+        let mut ask_size = self.volume_limits.available(ask.size);
+
+        let order_queue = mem::take(&mut self.unfilled_orders);
+        let mut remaining_orders = Vec::new();
+
+        for order_id in order_queue {
+            let resting = self.resting_orders.contains(&order_id);
+            let order = self.orders.get_mut(&order_id).unwrap();
+            let (quote, available_size) = match order.side {
+                OrderSide::Buy => (&ask, &mut ask_size),
+                OrderSide::Sell => (&bid, &mut bid_size),
+            };
+            let Some(price) = Self::quote_fill_price(order_id, order, quote, resting) else {
                 remaining_orders.push(order_id);
                 continue;
             };
 
-            let price =
-                Self::price_for_fill(&pending_fill.order_type, &pending_fill.side, &market_data, lowest_price, highest_price);
+            let Some(pending_fill) = self.prepare_order_fill(order_id, available_size) else {
+                remaining_orders.push(order_id);
+                continue;
+            };
+            let price = self.impacted_price(&pending_fill, price);
             self.record_fill(&pending_fill, price);
 
             if pending_fill.order_remaining > 0.0 {
@@ -110,6 +723,150 @@ impl BacktestingBroker {
         self.unfilled_orders = remaining_orders;
     }
 
+    fn apply_corporate_actions(&mut self, timestamp: Timestamp) {
+        let mut due = Vec::new();
+        for (instrument, schedule) in self.corporate_actions.iter_mut() {
+            while let Some(action) = schedule.actions.actions.get(schedule.next)
+                && schedule.actions.ex_timestamp(action) <= timestamp
+            {
+                due.push((*instrument, *action));
+                schedule.next += 1;
+            }
+        }
+        due.sort_by_key(|(instrument, action)| (action.ex_date, *instrument));
+
+        for (instrument, action) in due {
+            let mut trade_ids: Vec<usize> = self
+                .trades
+                .values()
+                .filter(|trade| trade.instrument == instrument && trade.size != 0.0)
+                .map(|trade| trade.id)
+                .collect();
+            trade_ids.sort();
+
+            match action.kind {
+                CorporateActionKind::Split(ratio) => {
+                    log::info!("Applying {} split of instrument {}", ratio, instrument);
+                    for trade_id in trade_ids {
+                        let trade = self.trades.get_mut(&trade_id).unwrap();
+                        trade.size *= ratio;
+                        trade.entry_price /= ratio;
+                        if let Some(metrics) = self.trade_metrics.get_mut(&trade_id) {
+                            metrics.net_quantity *= ratio;
+                        }
+                    }
+                    for order_id in self.unfilled_orders.iter() {
+                        let order = self.orders.get_mut(order_id).unwrap();
+                        if order.instrument != instrument {
+                            continue;
+                        }
+                        order.size *= ratio;
+                        order.order_type = match order.order_type {
+                            OrderType::Market => OrderType::Market,
+                            OrderType::MarketOnOpen => OrderType::MarketOnOpen,
+                            OrderType::MarketOnClose => OrderType::MarketOnClose,
+                            OrderType::Limit(limit) => OrderType::Limit(limit / ratio),
+                            OrderType::Stop(stop) => OrderType::Stop(stop / ratio),
+                            OrderType::StopLimit(stop, limit) => OrderType::StopLimit(stop / ratio, limit / ratio),
+                        };
+                    }
+                }
+                CorporateActionKind::Dividend(dividend) => {
+                    for trade_id in trade_ids {
+                        let trade = &self.trades[&trade_id];
+                        let payment = DividendPayment {
+                            instrument,
+                            strategy_id: trade.strategy_id,
+                            trade_id,
+                            timestamp,
+                            amount: trade.size * dividend,
+                        };
+                        log::info!("Dividend of {} for trade {}", payment.amount, trade_id);
+                        self.account.balance += payment.amount;
+                        self.dividends.push(payment);
+                    }
+                }
+            }
+        }
+    }
+
+    fn simulate_rolls(&mut self, timestamp: Timestamp) {
+        let mut due = Vec::new();
+        for (instrument, schedule) in self.roll_schedules.iter_mut() {
+            while let Some(roll) = schedule.rolls.get(schedule.next)
+                && roll.timestamp <= timestamp
+            {
+                due.push((*instrument, roll.clone()));
+                schedule.next += 1;
+            }
+        }
+        due.sort_by_key(|(instrument, roll)| (roll.timestamp, *instrument));
+
+        for (instrument, roll) in due {
+            let mut open_trades: Vec<(usize, usize, f64)> = self
+                .position_manager
+                .open_trades
+                .iter()
+                .flat_map(|(strategy_id, trade_ids)| trade_ids.iter().map(move |trade_id| (*strategy_id, *trade_id)))
+                .filter_map(|(strategy_id, trade_id)| {
+                    let trade = self.trades.get(&trade_id)?;
+                    (trade.instrument == instrument && trade.size != 0.0).then_some((strategy_id, trade_id, trade.size))
+                })
+                .collect();
+            open_trades.sort_by_key(|(_, trade_id, _)| *trade_id);
+
+            for (strategy_id, trade_id, size) in open_trades {
+                log::info!(
+                    "Rolling trade {} from {} @ {} to {} @ {}",
+                    trade_id,
+                    roll.from_symbol,
+                    roll.exit_price,
+                    roll.to_symbol,
+                    roll.entry_price
+                );
+                let (exit_side, entry_side) = if size > 0.0 {
+                    (OrderSide::Sell, OrderSide::Buy)
+                } else {
+                    (OrderSide::Buy, OrderSide::Sell)
+                };
+                let order = |related_id: Option<usize>, side: OrderSide| Order {
+                    id: None,
+                    related_id,
+                    instrument,
+                    strategy_id,
+                    side,
+                    order_type: OrderType::Market,
+                    size: size.abs(),
+                };
+                self.fill_roll_order(order(Some(trade_id), exit_side), roll.exit_price);
+                let entry_order_id = self.fill_roll_order(order(None, entry_side), roll.entry_price);
+
+                // Exit orders of the old trade, e.g. stops and targets, now exit the new trade
+                let Some(new_trade_id) = self.order_trades.get(&entry_order_id).copied() else {
+                    continue;
+                };
+                for order_id in self.unfilled_orders.iter() {
+                    let order = self.orders.get_mut(order_id).unwrap();
+                    if order.related_id == Some(trade_id) {
+                        order.related_id = Some(new_trade_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fill an order of a roll at the roll price, returns the id of the order
+    fn fill_roll_order(&mut self, mut order: Order, price: f64) -> usize {
+        let order_id = self.next_order_id();
+        order.id = Some(order_id);
+        self.orders.insert(order_id, order);
+        let mut available_size = f64::INFINITY;
+        if let Some(pending_fill) = self.prepare_order_fill(order_id, &mut available_size) {
+            self.record_fill(&pending_fill, price);
+        }
+        order_id
+    }
+
     pub fn get_trade_for_order(&self, order_id: usize) -> Option<&Trade> {
         self.order_trades
             .get(&order_id)
@@ -120,17 +877,29 @@ impl BacktestingBroker {
         self.fills.get(&fill_id)
     }
 
-    pub fn unfilled_orders_len(&self) -> usize {
-        self.unfilled_orders.len()
-    }
+    /// Filled and remaining size of an order, None for unknown orders
+    pub fn fill_report(&self, order_id: usize) -> Option<OrderFillReport> {
+        let order = self.orders.get(&order_id)?;
+        let order_fills = self.order_fills.get(&order_id).map(Vec::as_slice).unwrap_or_default();
+        let fills: Vec<&Fill> = order_fills.iter().map(|(fill_id, _)| &self.fills[fill_id]).collect();
+        let filled: f64 = fills.iter().map(|fill| fill.size).sum();
+        let average_price = (filled > 0.0)
+            .then(|| fills.iter().map(|fill| fill.price * fill.size).sum::<f64>() / filled);
 
-    fn extract_liquidity(market_data: &MarketData) -> f64 {
-        let available_size = match market_data {
-            MarketData::Bar(bar) => bar.volume,
-            MarketData::Tick(tick) => tick.size,
-        };
+        Some(OrderFillReport {
+            order_id,
+            size: filled + order.size,
+            filled,
+            remaining: order.size,
+            average_price,
+            fills: order_fills.iter().map(|(fill_id, _)| *fill_id).collect(),
+            first_fill: order_fills.first().map(|(_, timestamp)| *timestamp),
+            last_fill: order_fills.last().map(|(_, timestamp)| *timestamp),
+        })
+    }
 
-        available_size
+    pub fn unfilled_orders_len(&self) -> usize {
+        self.unfilled_orders.len()
     }
 
     fn signed_quantity(side: &OrderSide, size: f64) -> f64 {
@@ -145,8 +914,8 @@ impl BacktestingBroker {
             .position_manager
             .open_trades
             .entry(strategy_id)
-            .or_insert_with(Vec::new);
-        if !entry.iter().any(|id| *id == trade_id) {
+            .or_default();
+        if !entry.contains(&trade_id) {
             entry.push(trade_id);
         }
     }
@@ -179,25 +948,48 @@ impl BacktestingBroker {
             instrument: order.instrument,
             strategy_id: order.strategy_id,
             side: order.side.clone(),
-            order_type: order.order_type.clone(),
         })
     }
 
-    fn check_order_hit(&self, order_id: usize, lowest_price: f64, highest_price: f64) -> bool {
-        let order = self.orders.get(&order_id).unwrap();
-        match order.order_type {
-            OrderType::Market => true,
-            OrderType::Limit(limit) => match order.side {
-                OrderSide::Buy => lowest_price <= limit,
-                OrderSide::Sell => highest_price >= limit
-            },
-            OrderType::Stop(stop) => match order.side {
-                OrderSide::Buy => highest_price >= stop,
-                OrderSide::Sell => lowest_price <= stop,
+    /// Size a hit order may fill, limited by the `LimitFillModel` when a limit order is only touched
+    fn limit_fill_size(&mut self, order_id: usize, market_data: &MarketData, lowest_price: f64, highest_price: f64) -> f64 {
+        let order = &self.orders[&order_id];
+        let OrderType::Limit(limit) = order.order_type else {
+            return f64::INFINITY;
+        };
+        let touched = match order.side {
+            OrderSide::Buy => lowest_price >= limit,
+            OrderSide::Sell => highest_price <= limit,
+        };
+        if !touched {
+            return f64::INFINITY;
+        }
+
+        match self.limit_fill_model {
+            LimitFillModel::Touch => f64::INFINITY,
+            LimitFillModel::TradeThrough => 0.0,
+            LimitFillModel::TouchProbability { probability, .. } => {
+                if self.rng.random::<f64>() < probability {
+                    f64::INFINITY
+                } else {
+                    0.0
+                }
             }
-            OrderType::StopLimit(stop, limit) => match order.side {
-                OrderSide::Buy => highest_price >= stop && lowest_price <= limit,
-                OrderSide::Sell => lowest_price <= stop && highest_price >= limit,
+            LimitFillModel::FifoQueue { queue_ahead } => {
+                let traded = match market_data {
+                    MarketData::Tick(tick) => tick.size,
+                    _ => {
+                        if !self.warned_fifo_without_ticks {
+                            log::warn!("FIFO queue limit fills need ticks, touched limit orders wait for a trade through");
+                            self.warned_fifo_without_ticks = true;
+                        }
+                        0.0
+                    }
+                };
+                let ahead = self.queue_ahead.entry(order_id).or_insert(queue_ahead);
+                let consumed = ahead.min(traded);
+                *ahead -= consumed;
+                traded - consumed
             }
         }
     }
@@ -222,26 +1014,147 @@ impl BacktestingBroker {
         }
     }
 
-    // Calculate the price for the fill
-    // Ensure this also works with bars that gap the limit and stops
-    fn price_for_fill(order_type: &OrderType, side: &OrderSide, market_data: &MarketData, lowest_price: f64, highest_price: f64) -> f64 {
-        match *order_type {
-            OrderType::Market => match market_data {
-                MarketData::Tick(tick) => tick.price,
-                MarketData::Bar(bar) => bar.open,
-            },
-            OrderType::Limit(limit) => match side {
-                OrderSide::Buy => if highest_price >= limit && lowest_price <= limit { limit } else { limit.max(highest_price) },
-                OrderSide::Sell => if highest_price >= limit && lowest_price <= limit { limit } else { limit.min(lowest_price) },
-            },
-            OrderType::Stop(stop) => match side {
-                OrderSide::Buy => if highest_price >= stop && lowest_price <= stop { stop } else { stop.min(lowest_price) },
-                OrderSide::Sell => if highest_price >= stop && lowest_price <= stop { stop } else { stop.max(highest_price) },
-            },
-            OrderType::StopLimit(stop, limit) => match side {
-                OrderSide::Buy => if highest_price >= stop && lowest_price <= limit { stop } else { limit.min(lowest_price) },
-                OrderSide::Sell => if highest_price >= stop && lowest_price <= limit { stop } else { limit.max(highest_price) },
-            },
+    /// Price at which an order fills on trades moving from `open_price` within the range, None when it isn't hit
+    /// Orders that are already through their price at the open of the segment fill at the open: stops worse and
+    /// limits better than their price. A limit order resting at or through its price before the segment fills at its limit.
+    /// A stop-limit whose trigger price is beyond the limit becomes a limit order, which may fill in the same segment.
+    fn trade_fill_price(
+        &mut self,
+        order_id: usize,
+        previous_price: Option<f64>,
+        open_price: f64,
+        lowest_price: f64,
+        highest_price: f64,
+    ) -> Option<f64> {
+        let resting = self.resting_orders.contains(&order_id);
+        let slippage = self.slippage_model;
+        let order = self.orders.get_mut(&order_id)?;
+        let side = order.side.clone();
+        // Whether a price is acceptable for a limit, and the worse of two prices for the order
+        let within = |price: f64, limit: f64| match side {
+            OrderSide::Buy => price <= limit,
+            OrderSide::Sell => price >= limit,
+        };
+        let worse = |a: f64, b: f64| match side {
+            OrderSide::Buy => a.max(b),
+            OrderSide::Sell => a.min(b),
+        };
+        let triggered = |stop: f64| match side {
+            OrderSide::Buy => highest_price >= stop,
+            OrderSide::Sell => lowest_price <= stop,
+        };
+
+        match order.order_type {
+            OrderType::Market => Some(slippage.apply(&side, open_price)),
+            OrderType::MarketOnOpen | OrderType::MarketOnClose => None,
+            OrderType::Limit(limit) => {
+                let hit = match side {
+                    OrderSide::Buy => lowest_price <= limit,
+                    OrderSide::Sell => highest_price >= limit,
+                };
+                if !hit {
+                    None
+                } else if !within(open_price, limit) || (resting && previous_price.is_some_and(|p| within(p, limit))) {
+                    Some(limit)
+                } else {
+                    Some(open_price)
+                }
+            }
+            OrderType::Stop(stop) => triggered(stop).then(|| slippage.apply(&side, worse(stop, open_price))),
+            OrderType::StopLimit(stop, limit) => {
+                if !triggered(stop) {
+                    return None;
+                }
+                let trigger_price = worse(stop, open_price);
+                if within(trigger_price, limit) {
+                    let price = slippage.apply(&side, trigger_price);
+                    return Some(if within(price, limit) { price } else { limit });
+                }
+                log::debug!("Stop of order {} triggered beyond its limit, resting at {}", order_id, limit);
+                order.order_type = OrderType::Limit(limit);
+                // The rest of the segment may trade back to the limit
+                self.trade_fill_price(order_id, previous_price, open_price, lowest_price, highest_price)
+            }
+        }
+    }
+
+    /// Move the price of a fill taking liquidity by the market impact, limit orders fill at their price
+    /// The impact is that of the whole order, each fill adds its share of the permanent impact
+    /// A stop-limit never fills beyond its limit
+    fn impacted_price(&mut self, pending_fill: &PendingFill, price: f64) -> f64 {
+        let Some(market_impact) = self.market_impact else {
+            return price;
+        };
+        let limit = match self.orders[&pending_fill.order_id].order_type {
+            OrderType::Limit(_) => return price,
+            OrderType::StopLimit(_, limit) => Some(limit),
+            OrderType::Market | OrderType::MarketOnOpen | OrderType::MarketOnClose | OrderType::Stop(_) => None,
+        };
+
+        let filled: f64 = self
+            .order_fills
+            .get(&pending_fill.order_id)
+            .map(|order_fills| order_fills.iter().map(|(fill_id, _)| self.fills[fill_id].size).sum())
+            .unwrap_or_default();
+        let order_size = filled + pending_fill.fill_size + pending_fill.order_remaining;
+        let (temporary, permanent) = market_impact.impact(order_size);
+        let direction = pending_fill.signed_quantity.signum();
+        let permanent_impact = self.permanent_impact.entry(pending_fill.instrument).or_default();
+        let impacted = price * (1.0 + *permanent_impact + direction * temporary);
+        *permanent_impact += direction * permanent * pending_fill.fill_size / order_size;
+        match (limit, &pending_fill.side) {
+            (Some(limit), OrderSide::Buy) => impacted.min(limit),
+            (Some(limit), OrderSide::Sell) => impacted.max(limit),
+            (None, _) => impacted,
+        }
+    }
+
+    /// Price at which an order fills against the opposite side of the book, None when it doesn't fill
+    /// A stop-limit whose trigger price is beyond the limit becomes a limit order, which may fill on the same quotes.
+    fn quote_fill_price(order_id: usize, order: &mut Order, quote: &QuoteSide, resting: bool) -> Option<f64> {
+        let side = order.side.clone();
+        // Whether a price is acceptable for the order, and the worse of two prices for it
+        let within = |price: f64, limit: f64| match side {
+            OrderSide::Buy => price <= limit,
+            OrderSide::Sell => price >= limit,
+        };
+        let worse = |a: f64, b: f64| match side {
+            OrderSide::Buy => a.max(b),
+            OrderSide::Sell => a.min(b),
+        };
+        let triggered = |stop: f64| match side {
+            OrderSide::Buy => quote.high >= stop,
+            OrderSide::Sell => quote.low <= stop,
+        };
+
+        match order.order_type {
+            OrderType::Market => Some(quote.open),
+            OrderType::MarketOnOpen | OrderType::MarketOnClose => None,
+            OrderType::Limit(limit) => {
+                let traded_through = match side {
+                    OrderSide::Buy => quote.low < limit,
+                    OrderSide::Sell => quote.high > limit,
+                };
+                if !resting && within(quote.open, limit) {
+                    Some(quote.open)
+                } else {
+                    traded_through.then_some(limit)
+                }
+            }
+            OrderType::Stop(stop) => triggered(stop).then(|| worse(stop, quote.open)),
+            OrderType::StopLimit(stop, limit) => {
+                if !triggered(stop) {
+                    return None;
+                }
+                let trigger_price = worse(stop, quote.open);
+                if within(trigger_price, limit) {
+                    return Some(trigger_price);
+                }
+                log::debug!("Stop of order {} triggered beyond its limit, resting at {}", order_id, limit);
+                order.order_type = OrderType::Limit(limit);
+                // The quotes may trade back to the limit after the trigger
+                Self::quote_fill_price(order_id, order, quote, true)
+            }
         }
     }
 
@@ -258,6 +1171,10 @@ impl BacktestingBroker {
         };
         log::info!("Order {} filled: {}", pending_fill.stored_order_id, fill);
         self.fills.insert(fill_id, fill);
+        self.order_fills
+            .entry(pending_fill.stored_order_id)
+            .or_default()
+            .push((fill_id, self.timestamp));
         fill_id
     }
 
@@ -276,7 +1193,7 @@ impl BacktestingBroker {
             let metrics = self
                 .trade_metrics
                 .entry(trade_id)
-                .or_insert_with(TradeMetrics::default);
+                .or_default();
             let signed_quantity = pending_fill.signed_quantity;
             let fill_size = pending_fill.fill_size;
 
@@ -355,10 +1272,10 @@ impl BacktestingBroker {
                 }
             }
         }
-        if let Some(strategy_id) = remove_from_open_strategy {
-            if let Some(open) = self.position_manager.open_trades.get_mut(&strategy_id) {
-                open.retain(|id| *id != trade_id);
-            }
+        if let Some(strategy_id) = remove_from_open_strategy
+            && let Some(open) = self.position_manager.open_trades.get_mut(&strategy_id)
+        {
+            open.retain(|id| *id != trade_id);
         }
         if let Some(strategy_id) = ensure_open_strategy {
             self.ensure_trade_is_open(strategy_id, trade_id);
@@ -385,7 +1302,7 @@ impl BacktestingBroker {
         self.position_manager
             .trades
             .entry(trade.strategy_id)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(trade_id);
         self.ensure_trade_is_open(trade.strategy_id, trade_id);
 
@@ -421,6 +1338,30 @@ impl BacktestingBroker {
     }
 }
 
+/// First price of market data for an order, the ask for buys and the bid for sells on quotes
+fn opening_price(market_data: &MarketData, side: &OrderSide) -> f64 {
+    match (market_data, side) {
+        (MarketData::Tick(tick), _) => tick.price,
+        (MarketData::Bar(bar), _) => bar.open,
+        (MarketData::Quote(quote), OrderSide::Buy) => quote.ask,
+        (MarketData::Quote(quote), OrderSide::Sell) => quote.bid,
+        (MarketData::QuoteBar(quote_bar), OrderSide::Buy) => quote_bar.ask_open,
+        (MarketData::QuoteBar(quote_bar), OrderSide::Sell) => quote_bar.bid_open,
+    }
+}
+
+/// Last price of market data for an order, the ask for buys and the bid for sells on quotes
+fn closing_price(market_data: &MarketData, side: &OrderSide) -> f64 {
+    match (market_data, side) {
+        (MarketData::Tick(tick), _) => tick.price,
+        (MarketData::Bar(bar), _) => bar.close,
+        (MarketData::Quote(quote), OrderSide::Buy) => quote.ask,
+        (MarketData::Quote(quote), OrderSide::Sell) => quote.bid,
+        (MarketData::QuoteBar(quote_bar), OrderSide::Buy) => quote_bar.ask_close,
+        (MarketData::QuoteBar(quote_bar), OrderSide::Sell) => quote_bar.bid_close,
+    }
+}
+
 impl Broker for BacktestingBroker {
     fn place_order(&mut self, mut order: Order) -> &Order {
         let order_id = self.next_order_id();
@@ -448,9 +1389,62 @@ impl Broker for BacktestingBroker {
         self.orders.insert(order_id, order);
         self.unfilled_orders.push(order_id);
 
+        let submission = self.latency.submission.sample(&mut self.latency_rng);
+        let acknowledgement = self.latency.acknowledgement.sample(&mut self.latency_rng);
+        if !submission.is_zero() || !acknowledgement.is_zero() {
+            let arrival = self.timestamp + submission;
+            self.order_times.insert(
+                order_id,
+                OrderTimes {
+                    arrival,
+                    acknowledged: arrival + acknowledgement,
+                    cancel: None,
+                },
+            );
+        }
+
+        if let Some(auction) = self.auction_time(&self.orders[&order_id]) {
+            self.auction_times.insert(order_id, auction);
+        }
+
+        let signal_close_fill = self.fill_on_signal_close
+            && matches!(self.orders[&order_id].order_type, OrderType::Market | OrderType::MarketOnClose);
+        if signal_close_fill && let Some(signal) = self.last_market_data {
+            let price = closing_price(&signal, &self.orders[&order_id].side);
+            let mut available_size = f64::INFINITY;
+            if let Some(pending_fill) = self.prepare_order_fill(order_id, &mut available_size) {
+                let price = self.impacted_price(&pending_fill, price);
+                self.record_fill(&pending_fill, price);
+                self.unfilled_orders.retain(|id| *id != order_id);
+                self.auction_times.remove(&order_id);
+            }
+        }
+
         self.orders.get(&order_id).unwrap()
     }
 
+    fn cancel_order(&mut self, order_id: usize) -> bool {
+        if !self.unfilled_orders.contains(&order_id) {
+            return false;
+        }
+        let delay = self.latency.cancel.sample(&mut self.latency_rng);
+        if delay.is_zero() {
+            self.remove_order(order_id);
+            return true;
+        }
+
+        let cancel = self.timestamp + delay;
+        let times = self.order_times.entry(order_id).or_insert(OrderTimes {
+            arrival: Timestamp::default(),
+            acknowledged: Timestamp::default(),
+            cancel: None,
+        });
+        if times.cancel.is_none() {
+            times.cancel = Some(cancel);
+        }
+        true
+    }
+
     fn add_instrument(&mut self, mut instrument: Instrument) -> &Instrument {
         let instrument_id = self.next_instrument_id();
 
@@ -459,15 +1453,12 @@ impl Broker for BacktestingBroker {
         self.instruments.get(&instrument_id).unwrap()
     }
 
+    fn get_instrument(&mut self, instrument_id: u32) -> Option<&Instrument> {
+        self.instruments.get(&instrument_id)
+    }
+
     fn get_current_position(&mut self, strategy_id: usize, instrument_id: u32) -> f64 {
-        self
-            .position_manager
-            .get_open_trades(strategy_id)
-            .iter()
-            .filter_map(|k| self.trades.get(k))
-            .filter(|t| t.instrument == instrument_id)
-            .map(|t| t.size)
-            .sum()
+        self.open_position(strategy_id, instrument_id)
     }
 
     fn get_open_trades(&mut self, strategy_id: usize, instrument_id: u32) -> Vec<&Trade> {
@@ -0,0 +1,490 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use certus_core::data::{
+    Bar, DataFeed, DataHandler, DataHandlerError, ErrorPolicy, MarketData, Quote, QuoteBar, Tick, Timestamp,
+};
+use certus_core::timeframe::Timeframe;
+use memmap2::Mmap;
+
+use crate::csv_data_handler::{CSVRowParser, read_csv};
+use crate::data::HistoricBarConsolidationModel;
+
+// File layout, all values little endian:
+//   0  magic "CERTUSMD"
+//   8  version (u32)
+//  12  data kind (u8), timeframe unit (u8), 2 reserved bytes
+//  16  timeframe count (u32), instrument length (u32)
+//  24  number of rows (u64)
+//  32  first and last timestamp (i64 nanoseconds)
+//  48  source length (u64) and modification time (i64 nanoseconds)
+//  64  hash of the parser and consolidation settings (u64)
+//  72  instrument (utf-8), padded to 8 bytes
+// followed by one column of 8 byte values per field, timestamps first
+const MAGIC: &[u8; 8] = b"CERTUSMD";
+const VERSION: u32 = 3;
+const HEADER_LEN: usize = 72;
+
+/// enum defining which market data a cache holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheDataKind {
+    /// Columns timestamp, open, high, low, close, volume
+    Bars,
+    /// Columns timestamp, price, size
+    Ticks,
+    /// Columns timestamp, bid, bid size, ask, ask size
+    Quotes,
+    /// Columns timestamp, bid open, high, low, close, bid size, ask open, high, low, close, ask size
+    QuoteBars,
+}
+
+impl CacheDataKind {
+    fn columns(&self) -> usize {
+        match self {
+            CacheDataKind::Bars => 6,
+            CacheDataKind::Ticks => 3,
+            CacheDataKind::Quotes => 5,
+            CacheDataKind::QuoteBars => 11,
+        }
+    }
+}
+
+/// Size and modification time of the file a cache was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceFingerprint {
+    pub len: u64,
+    pub modified: i64,
+}
+
+impl SourceFingerprint {
+    pub fn of(path: impl AsRef<Path>) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as i64)
+            .unwrap_or(0);
+
+        Ok(Self {
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheHeader {
+    pub instrument: String,
+    pub kind: CacheDataKind,
+    /// Timeframe of the bars, None for ticks or unknown timeframes
+    pub timeframe: Option<Timeframe>,
+    /// Timestamps of the first and last row
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub len: usize,
+    pub source: SourceFingerprint,
+    /// Hash of the settings the data was parsed and consolidated with, see `settings_hash`
+    pub settings: u64,
+}
+
+/// Stable FNV-1a hash of a settings description
+pub fn settings_hash(settings: &str) -> u64 {
+    settings
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn encode_timeframe(timeframe: Option<Timeframe>) -> (u8, u32) {
+    match timeframe {
+        None => (0, 0),
+        Some(Timeframe::Seconds(n)) => (1, n),
+        Some(Timeframe::Minutes(n)) => (2, n),
+        Some(Timeframe::Hours(n)) => (3, n),
+        Some(Timeframe::Days(n)) => (4, n),
+        Some(Timeframe::Weeks(n)) => (5, n),
+        Some(Timeframe::Months(n)) => (6, n),
+    }
+}
+
+fn decode_timeframe(unit: u8, count: u32) -> io::Result<Option<Timeframe>> {
+    Ok(match unit {
+        0 => None,
+        1 => Some(Timeframe::Seconds(count)),
+        2 => Some(Timeframe::Minutes(count)),
+        3 => Some(Timeframe::Hours(count)),
+        4 => Some(Timeframe::Days(count)),
+        5 => Some(Timeframe::Weeks(count)),
+        6 => Some(Timeframe::Months(count)),
+        _ => return Err(invalid_data("unknown timeframe unit")),
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn padded(len: usize) -> usize {
+    len.div_ceil(8) * 8
+}
+
+/// Write `data` to a columnar cache file
+/// All data has to be of the same kind, the file is replaced atomically
+pub fn write_cache(
+    path: impl AsRef<Path>,
+    instrument: &str,
+    timeframe: Option<Timeframe>,
+    source: SourceFingerprint,
+    settings: u64,
+    data: &[MarketData],
+) -> io::Result<CacheHeader> {
+    let kind = match data.first() {
+        Some(MarketData::Tick(_)) => CacheDataKind::Ticks,
+        Some(MarketData::Quote(_)) => CacheDataKind::Quotes,
+        Some(MarketData::QuoteBar(_)) => CacheDataKind::QuoteBars,
+        _ => CacheDataKind::Bars,
+    };
+
+    let mut timestamps: Vec<i64> = Vec::with_capacity(data.len());
+    let mut values: Vec<Vec<f64>> = vec![Vec::with_capacity(data.len()); kind.columns() - 1];
+    for single_data in data.iter() {
+        let row = match (kind, single_data) {
+            (CacheDataKind::Bars, MarketData::Bar(bar)) => {
+                vec![bar.open, bar.high, bar.low, bar.close, bar.volume]
+            }
+            (CacheDataKind::Ticks, MarketData::Tick(tick)) => vec![tick.price, tick.size],
+            (CacheDataKind::Quotes, MarketData::Quote(quote)) => {
+                vec![quote.bid, quote.bid_size, quote.ask, quote.ask_size]
+            }
+            (CacheDataKind::QuoteBars, MarketData::QuoteBar(quote_bar)) => vec![
+                quote_bar.bid_open,
+                quote_bar.bid_high,
+                quote_bar.bid_low,
+                quote_bar.bid_close,
+                quote_bar.bid_size,
+                quote_bar.ask_open,
+                quote_bar.ask_high,
+                quote_bar.ask_low,
+                quote_bar.ask_close,
+                quote_bar.ask_size,
+            ],
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot cache a mix of market data kinds",
+                ));
+            }
+        };
+        timestamps.push(single_data.timestamp().nanos());
+        for (column, value) in values.iter_mut().zip(row) {
+            column.push(value);
+        }
+    }
+
+    let header = CacheHeader {
+        instrument: instrument.to_string(),
+        kind,
+        timeframe,
+        start: data.first().map(|d| d.timestamp()).unwrap_or_default(),
+        end: data.last().map(|d| d.timestamp()).unwrap_or_default(),
+        len: data.len(),
+        source,
+        settings,
+    };
+
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    let (unit, count) = encode_timeframe(timeframe);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&[kind as u8, unit, 0, 0])?;
+    writer.write_all(&count.to_le_bytes())?;
+    writer.write_all(&(instrument.len() as u32).to_le_bytes())?;
+    writer.write_all(&(header.len as u64).to_le_bytes())?;
+    writer.write_all(&header.start.nanos().to_le_bytes())?;
+    writer.write_all(&header.end.nanos().to_le_bytes())?;
+    writer.write_all(&source.len.to_le_bytes())?;
+    writer.write_all(&source.modified.to_le_bytes())?;
+    writer.write_all(&settings.to_le_bytes())?;
+    writer.write_all(instrument.as_bytes())?;
+    writer.write_all(&vec![0; padded(instrument.len()) - instrument.len()])?;
+
+    for timestamp in timestamps {
+        writer.write_all(&timestamp.to_le_bytes())?;
+    }
+    for column in values {
+        for value in column {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    writer.into_inner()?.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    Ok(header)
+}
+
+/// Memory mapped, read only view of a cache file written by `write_cache`
+pub struct MarketDataCache {
+    mmap: Mmap,
+    header: CacheHeader,
+    data_offset: usize,
+}
+
+impl MarketDataCache {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: cache files are never modified in place, `write_cache` replaces them
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < HEADER_LEN || &mmap[0..8] != MAGIC {
+            return Err(invalid_data("not a market data cache"));
+        }
+        let u32_at = |offset: usize| u32::from_le_bytes(mmap[offset..offset + 4].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(mmap[offset..offset + 8].try_into().unwrap());
+        if u32_at(8) != VERSION {
+            return Err(invalid_data("unsupported cache version"));
+        }
+
+        let kind = match mmap[12] {
+            0 => CacheDataKind::Bars,
+            1 => CacheDataKind::Ticks,
+            2 => CacheDataKind::Quotes,
+            3 => CacheDataKind::QuoteBars,
+            _ => return Err(invalid_data("unknown data kind")),
+        };
+        let timeframe = decode_timeframe(mmap[13], u32_at(16))?;
+        let instrument_len = u32_at(20) as usize;
+        let len = u64_at(24) as usize;
+        let data_offset = HEADER_LEN + padded(instrument_len);
+        // The length comes from the file, a corrupt header must not overflow
+        let file_len = (kind.columns() * 8)
+            .checked_mul(len)
+            .and_then(|data_len| data_len.checked_add(data_offset));
+        if file_len != Some(mmap.len()) {
+            return Err(invalid_data("cache file is truncated"));
+        }
+        let instrument = std::str::from_utf8(&mmap[HEADER_LEN..HEADER_LEN + instrument_len])
+            .map_err(|_| invalid_data("instrument is not valid utf-8"))?
+            .to_string();
+
+        let header = CacheHeader {
+            instrument,
+            kind,
+            timeframe,
+            start: Timestamp::from_nanos(u64_at(32) as i64),
+            end: Timestamp::from_nanos(u64_at(40) as i64),
+            len,
+            source: SourceFingerprint {
+                len: u64_at(48),
+                modified: u64_at(56) as i64,
+            },
+            settings: u64_at(64),
+        };
+
+        Ok(Self {
+            mmap,
+            header,
+            data_offset,
+        })
+    }
+
+    pub fn header(&self) -> &CacheHeader {
+        &self.header
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    fn value_bytes(&self, column: usize, index: usize) -> [u8; 8] {
+        let offset = self.data_offset + (column * self.header.len + index) * 8;
+        self.mmap[offset..offset + 8].try_into().unwrap()
+    }
+
+    fn value(&self, column: usize, index: usize) -> f64 {
+        f64::from_le_bytes(self.value_bytes(column, index))
+    }
+
+    pub fn get(&self, index: usize) -> Option<MarketData> {
+        if index >= self.header.len {
+            return None;
+        }
+
+        let timestamp = Timestamp::from_nanos(i64::from_le_bytes(self.value_bytes(0, index)));
+        Some(match self.header.kind {
+            CacheDataKind::Bars => MarketData::Bar(Bar {
+                timestamp,
+                open: self.value(1, index),
+                high: self.value(2, index),
+                low: self.value(3, index),
+                close: self.value(4, index),
+                volume: self.value(5, index),
+            }),
+            CacheDataKind::Ticks => MarketData::Tick(Tick {
+                timestamp,
+                price: self.value(1, index),
+                size: self.value(2, index),
+            }),
+            CacheDataKind::Quotes => MarketData::Quote(Quote {
+                timestamp,
+                bid: self.value(1, index),
+                bid_size: self.value(2, index),
+                ask: self.value(3, index),
+                ask_size: self.value(4, index),
+            }),
+            CacheDataKind::QuoteBars => MarketData::QuoteBar(QuoteBar {
+                timestamp,
+                bid_open: self.value(1, index),
+                bid_high: self.value(2, index),
+                bid_low: self.value(3, index),
+                bid_close: self.value(4, index),
+                bid_size: self.value(5, index),
+                ask_open: self.value(6, index),
+                ask_high: self.value(7, index),
+                ask_low: self.value(8, index),
+                ask_close: self.value(9, index),
+                ask_size: self.value(10, index),
+            }),
+        })
+    }
+}
+
+pub struct CacheDataFeed<'a> {
+    index: usize,
+    cache: Option<&'a MarketDataCache>,
+}
+
+impl<'a> DataFeed for CacheDataFeed<'a> {
+    fn poll(&mut self) -> Result<Option<MarketData>, DataHandlerError> {
+        let market_data = self.cache.and_then(|cache| cache.get(self.index));
+        if market_data.is_some() {
+            self.index += 1;
+        }
+        Ok(market_data)
+    }
+}
+
+/// Data handler replaying consolidated CSV data from a cache file
+/// The cache is rebuilt from the CSV file when the file, the instrument, the output timeframe or
+/// the parser and consolidation settings changed since the cache was written
+pub struct CachedCSVDataHandler {
+    pub file_path: String,
+    pub cache_path: String,
+    pub instrument: String,
+    csv_row_parser: Box<dyn CSVRowParser>,
+    bar_consolidation_model: HistoricBarConsolidationModel,
+    pub error_policy: ErrorPolicy,
+    skipped: Vec<DataHandlerError>,
+    cache: Option<MarketDataCache>,
+}
+
+impl CachedCSVDataHandler {
+    pub fn new(
+        file_path: String,
+        cache_path: String,
+        instrument: String,
+        csv_row_parser: Box<dyn CSVRowParser>,
+        bar_consolidation_model: HistoricBarConsolidationModel,
+    ) -> Self {
+        Self {
+            file_path,
+            cache_path,
+            instrument,
+            csv_row_parser,
+            bar_consolidation_model,
+            error_policy: ErrorPolicy::Abort,
+            skipped: Vec::new(),
+            cache: None,
+        }
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Rows skipped with `ErrorPolicy::SkipAndReport` when the cache was rebuilt
+    pub fn skipped_rows(&self) -> &[DataHandlerError] {
+        &self.skipped
+    }
+
+    /// The cache replayed by the data feed, available after start
+    pub fn cache(&self) -> Option<&MarketDataCache> {
+        self.cache.as_ref()
+    }
+
+    /// Hash of the parser and consolidation settings the cache has to be built with
+    fn settings(&self) -> u64 {
+        settings_hash(&format!(
+            "{} {} {} {}",
+            self.bar_consolidation_model.settings(),
+            self.csv_row_parser.settings(),
+            self.csv_row_parser.delimiter(),
+            self.csv_row_parser.has_headers()
+        ))
+    }
+
+    fn is_up_to_date(&self, header: &CacheHeader, source: SourceFingerprint) -> bool {
+        header.source == source
+            && header.instrument == self.instrument
+            && header.timeframe == Some(self.bar_consolidation_model.output)
+            && header.settings == self.settings()
+    }
+
+    fn load_cache(&mut self) -> Result<MarketDataCache, DataHandlerError> {
+        let source =
+            SourceFingerprint::of(&self.file_path).map_err(|e| DataHandlerError::io(&self.file_path, &e))?;
+        if let Ok(cache) = MarketDataCache::open(&self.cache_path)
+            && self.is_up_to_date(cache.header(), source)
+        {
+            return Ok(cache);
+        }
+
+        log::info!("Building cache {} from {}", self.cache_path, self.file_path);
+        let data = read_csv(
+            &self.file_path,
+            self.csv_row_parser.as_mut(),
+            self.error_policy,
+            &mut self.skipped,
+            None,
+        )?;
+        let data = self.bar_consolidation_model.consolidate_bars(&data)?;
+        write_cache(
+            &self.cache_path,
+            &self.instrument,
+            Some(self.bar_consolidation_model.output),
+            source,
+            self.settings(),
+            &data,
+        )
+        .map_err(|e| DataHandlerError::io(&self.cache_path, &e))?;
+        MarketDataCache::open(&self.cache_path).map_err(|e| DataHandlerError::io(&self.cache_path, &e))
+    }
+}
+
+impl DataHandler for CachedCSVDataHandler {
+    fn start(&mut self) -> Result<(), DataHandlerError> {
+        self.skipped.clear();
+        self.cache = Some(self.load_cache()?);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.cache = None;
+    }
+
+    fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
+        Box::new(CacheDataFeed {
+            index: 0,
+            cache: self.cache.as_ref(),
+        })
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use certus_core::data::{Bar, MarketData, Timestamp};
+use certus_core::session::{SessionType, TradingCalendar};
+use chrono::Duration;
+
+/// enum defining what the cleaner does with a row that has an issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleaningAction {
+    /// Keep the row and report the issue
+    Report,
+    /// Drop the row and report the issue
+    Drop,
+    /// Fix the row where possible, e.g. OHLC relationships, otherwise drop it
+    Repair,
+}
+
+/// enum defining the data quality issues found by the cleaner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataIssueKind {
+    /// Bar with the same timestamp as the previous bar, or a tick identical to the previous tick
+    Duplicate,
+    /// Row older than the previous row
+    OutOfOrder,
+    /// Non finite values, high below low, open or close outside the range or negative volume
+    InvalidPrice,
+    /// Bar without volume or tick without size
+    ZeroVolume,
+    /// Price moving more than `spike_sigma` standard deviations of the recent returns
+    PriceSpike,
+    /// No data for longer than `max_gap` while the session is open, always reported only
+    SessionGap,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataIssue {
+    pub kind: DataIssueKind,
+    /// Line of the row in the source file, if known
+    pub line: Option<u64>,
+    pub timestamp: Timestamp,
+    pub message: String,
+    /// What happened to the row, `Repair` only when the row was actually repaired
+    pub action: CleaningAction,
+}
+
+impl fmt::Display for DataIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{:?} at {} ({:?}): {}", self.kind, self.timestamp, self.action, self.message)
+    }
+}
+
+/// Summary of the rows seen by a `MarketDataCleaner`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DataQualityReport {
+    pub rows: usize,
+    pub dropped: usize,
+    pub repaired: usize,
+    pub issues: Vec<DataIssue>,
+}
+
+impl DataQualityReport {
+    /// Number of issues of `kind`
+    pub fn count(&self, kind: DataIssueKind) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == kind).count()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for DataQualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rows, {} dropped, {} repaired",
+            self.rows, self.dropped, self.repaired
+        )?;
+        for kind in [
+            DataIssueKind::Duplicate,
+            DataIssueKind::OutOfOrder,
+            DataIssueKind::InvalidPrice,
+            DataIssueKind::ZeroVolume,
+            DataIssueKind::PriceSpike,
+            DataIssueKind::SessionGap,
+        ] {
+            let count = self.count(kind);
+            if count > 0 {
+                write!(f, ", {} {:?}", count, kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CleaningConfig {
+    pub duplicates: CleaningAction,
+    pub out_of_order: CleaningAction,
+    pub invalid_prices: CleaningAction,
+    pub zero_volume: CleaningAction,
+    pub price_spikes: CleaningAction,
+    /// Number of standard deviations a return has to be away from the mean to be a spike
+    pub spike_sigma: f64,
+    /// Number of returns the mean and standard deviation are computed over
+    pub spike_window: usize,
+    /// Longest time without data, None disables the gap check
+    pub max_gap: Option<Duration>,
+    /// Only report gaps while the session of the calendar is open
+    pub calendar: Option<TradingCalendar>,
+    pub session_type: SessionType,
+}
+
+impl Default for CleaningConfig {
+    fn default() -> Self {
+        Self {
+            duplicates: CleaningAction::Drop,
+            out_of_order: CleaningAction::Drop,
+            invalid_prices: CleaningAction::Drop,
+            zero_volume: CleaningAction::Report,
+            price_spikes: CleaningAction::Report,
+            spike_sigma: 6.0,
+            spike_window: 100,
+            max_gap: None,
+            calendar: None,
+            session_type: SessionType::Regular,
+        }
+    }
+}
+
+/// Validation and cleaning stage between the row parser and the bar consolidation
+pub struct MarketDataCleaner {
+    pub config: CleaningConfig,
+    report: DataQualityReport,
+    previous: Option<MarketData>,
+    returns: VecDeque<f64>,
+    /// Dropped spike waiting for the next row, with the index of its issue in the report
+    held_spike: Option<(MarketData, usize)>,
+}
+
+impl MarketDataCleaner {
+    pub fn new(config: CleaningConfig) -> Self {
+        Self {
+            config,
+            report: DataQualityReport::default(),
+            previous: None,
+            returns: VecDeque::new(),
+            held_spike: None,
+        }
+    }
+
+    pub fn report(&self) -> &DataQualityReport {
+        &self.report
+    }
+
+    /// Forget the previous rows and clear the report, e.g. when the data is read again
+    pub fn reset(&mut self) {
+        self.report = DataQualityReport::default();
+        self.previous = None;
+        self.returns.clear();
+        self.held_spike = None;
+    }
+
+    /// Check a row, returns the rows released by it: the (repaired) row unless it is dropped
+    /// A price spike that is dropped is held until the next row. When the price stays at the level of
+    /// the spike it was a level shift, e.g. a gap or a roll, and the held row is released before this row.
+    /// Otherwise it was a one row outlier and stays dropped
+    pub fn clean(&mut self, market_data: MarketData, line: Option<u64>) -> Vec<MarketData> {
+        let mut released = Vec::new();
+        let row = self.clean_row(market_data, line, &mut released);
+        released.extend(row);
+        released
+    }
+
+    fn clean_row(&mut self, market_data: MarketData, line: Option<u64>, released: &mut Vec<MarketData>) -> Option<MarketData> {
+        self.report.rows += 1;
+        let timestamp = market_data.timestamp();
+
+        let mut market_data = market_data;
+        if let Some(message) = invalid_price(&market_data) {
+            let repaired = match (self.config.invalid_prices, market_data) {
+                (CleaningAction::Repair, MarketData::Bar(bar)) => repair_bar(bar).map(MarketData::Bar),
+                _ => None,
+            };
+            match repaired {
+                Some(repaired) => {
+                    self.issue(DataIssueKind::InvalidPrice, line, timestamp, message, CleaningAction::Repair);
+                    self.report.repaired += 1;
+                    market_data = repaired;
+                }
+                None => {
+                    if !self.check(self.config.invalid_prices, DataIssueKind::InvalidPrice, line, timestamp, message) {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        if let Some(previous) = self.previous {
+            let previous_timestamp = previous.timestamp();
+            if timestamp < previous_timestamp {
+                let message = format!("previous row at {}", previous_timestamp);
+                if !self.check(self.config.out_of_order, DataIssueKind::OutOfOrder, line, timestamp, message) {
+                    return None;
+                }
+            } else if is_duplicate(&previous, &market_data) {
+                let message = String::from("same timestamp as the previous row");
+                if !self.check(self.config.duplicates, DataIssueKind::Duplicate, line, timestamp, message) {
+                    return None;
+                }
+            } else if let Some(max_gap) = self.config.max_gap
+                && timestamp > self.gap_limit(previous_timestamp, max_gap)
+            {
+                let message = format!("no data since {}", previous_timestamp);
+                self.issue(DataIssueKind::SessionGap, line, timestamp, message, CleaningAction::Report);
+            }
+        }
+
+        if volume(&market_data) == Some(0.0) {
+            let message = String::from("zero volume");
+            if !self.check(self.config.zero_volume, DataIssueKind::ZeroVolume, line, timestamp, message) {
+                return None;
+            }
+        }
+
+        let mut change = self.change(&market_data, self.previous);
+        if let Some((spike, issue)) = self.held_spike.take()
+            && self.spike_sigma(change).is_some()
+            && self.spike_sigma(self.change(&market_data, Some(spike))).is_none()
+        {
+            log::debug!("Price spike at {} is a level shift, keeping it", spike.timestamp());
+            self.report.issues[issue].action = CleaningAction::Report;
+            self.report.dropped -= 1;
+            self.previous = Some(spike);
+            released.push(spike);
+            change = self.change(&market_data, self.previous);
+        }
+
+        if let Some(sigma) = self.spike_sigma(change) {
+            let message = format!("return of {:.4} is {:.1} standard deviations", change.unwrap_or_default(), sigma);
+            if !self.check(self.config.price_spikes, DataIssueKind::PriceSpike, line, timestamp, message) {
+                self.held_spike = Some((market_data, self.report.issues.len() - 1));
+                return None;
+            }
+        }
+
+        if let Some(change) = change {
+            self.returns.push_back(change);
+            if self.returns.len() > self.config.spike_window {
+                self.returns.pop_front();
+            }
+        }
+        self.previous = Some(market_data);
+        Some(market_data)
+    }
+
+    /// Report an issue, returns whether the row is kept
+    fn check(
+        &mut self,
+        action: CleaningAction,
+        kind: DataIssueKind,
+        line: Option<u64>,
+        timestamp: Timestamp,
+        message: String,
+    ) -> bool {
+        let kept = action == CleaningAction::Report;
+        let action = if kept { CleaningAction::Report } else { CleaningAction::Drop };
+        self.issue(kind, line, timestamp, message, action);
+        if !kept {
+            self.report.dropped += 1;
+        }
+        kept
+    }
+
+    fn issue(&mut self, kind: DataIssueKind, line: Option<u64>, timestamp: Timestamp, message: String, action: CleaningAction) {
+        let issue = DataIssue {
+            kind,
+            line,
+            timestamp,
+            message,
+            action,
+        };
+        log::debug!("{}", issue);
+        self.report.issues.push(issue);
+    }
+
+    /// Latest time the row after `previous` is expected
+    fn gap_limit(&self, previous: Timestamp, max_gap: Duration) -> Timestamp {
+        let expected = previous + max_gap;
+        let Some(calendar) = &self.config.calendar else {
+            return expected;
+        };
+        match calendar.next_trading_time_at(expected, self.config.session_type) {
+            Some(next) if next != expected => next + max_gap,
+            Some(next) => next,
+            None => Timestamp::from_nanos(i64::MAX),
+        }
+    }
+
+    fn change(&self, market_data: &MarketData, previous: Option<MarketData>) -> Option<f64> {
+        previous
+            .map(|previous| price(market_data) / price(&previous) - 1.0)
+            .filter(|change| change.is_finite())
+    }
+
+    /// Distance of the price `change` to the recent returns in standard deviations when it is a spike
+    fn spike_sigma(&self, change: Option<f64>) -> Option<f64> {
+        change
+            .and_then(|change| self.sigma(change))
+            .filter(|sigma| *sigma > self.config.spike_sigma)
+    }
+
+    /// Distance of the price `change` to the mean of the recent returns in standard deviations
+    fn sigma(&self, change: f64) -> Option<f64> {
+        if self.returns.len() < self.config.spike_window {
+            return None;
+        }
+        let count = self.returns.len() as f64;
+        let mean = self.returns.iter().sum::<f64>() / count;
+        let variance = self.returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count;
+        // Flat prices, e.g. a quiet tick feed, don't tell what a spike is
+        if variance == 0.0 {
+            return None;
+        }
+        Some((change - mean).abs() / variance.sqrt())
+    }
+}
+
+fn invalid_price(market_data: &MarketData) -> Option<String> {
+    match market_data {
+        MarketData::Bar(bar) => bar.ohlc_error(),
+        MarketData::Tick(tick) => {
+            if !tick.price.is_finite() || !tick.size.is_finite() {
+                Some(String::from("price and size must be finite"))
+            } else if tick.size < 0.0 {
+                Some(format!("size {} is negative", tick.size))
+            } else {
+                None
+            }
+        }
+        MarketData::Quote(quote) => {
+            let values = [quote.bid, quote.bid_size, quote.ask, quote.ask_size];
+            if values.iter().any(|value| !value.is_finite()) {
+                Some(String::from("prices and sizes must be finite"))
+            } else if quote.bid_size < 0.0 || quote.ask_size < 0.0 {
+                Some(String::from("sizes must not be negative"))
+            } else if quote.bid > quote.ask {
+                Some(format!("bid {} is above ask {}", quote.bid, quote.ask))
+            } else {
+                None
+            }
+        }
+        MarketData::QuoteBar(quote_bar) => quote_bar
+            .bid_bar()
+            .ohlc_error()
+            .map(|message| format!("bid {}", message))
+            .or_else(|| quote_bar.ask_bar().ohlc_error().map(|message| format!("ask {}", message))),
+    }
+}
+
+/// Widen high and low to contain open and close and clamp the volume at zero
+fn repair_bar(bar: Bar) -> Option<Bar> {
+    let values = [bar.open, bar.high, bar.low, bar.close, bar.volume];
+    if values.iter().any(|value| !value.is_finite()) {
+        return None;
+    }
+    let high = bar.open.max(bar.high).max(bar.low).max(bar.close);
+    let low = bar.open.min(bar.high).min(bar.low).min(bar.close);
+    Some(Bar {
+        high,
+        low,
+        volume: bar.volume.max(0.0),
+        ..bar
+    })
+}
+
+fn is_duplicate(previous: &MarketData, market_data: &MarketData) -> bool {
+    match (previous, market_data) {
+        (MarketData::Bar(previous), MarketData::Bar(bar)) => previous.timestamp == bar.timestamp,
+        (MarketData::Tick(previous), MarketData::Tick(tick)) => {
+            previous.timestamp == tick.timestamp && previous.price == tick.price && previous.size == tick.size
+        }
+        (MarketData::Quote(previous), MarketData::Quote(quote)) => {
+            previous.timestamp == quote.timestamp
+                && previous.bid == quote.bid
+                && previous.ask == quote.ask
+                && previous.bid_size == quote.bid_size
+                && previous.ask_size == quote.ask_size
+        }
+        (MarketData::QuoteBar(previous), MarketData::QuoteBar(quote_bar)) => {
+            previous.timestamp == quote_bar.timestamp
+        }
+        _ => false,
+    }
+}
+
+fn price(market_data: &MarketData) -> f64 {
+    match market_data {
+        MarketData::Bar(bar) => bar.close,
+        MarketData::Tick(tick) => tick.price,
+        MarketData::Quote(quote) => quote.mid(),
+        MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar().close,
+    }
+}
+
+/// Traded volume, None for quotes which don't trade
+fn volume(market_data: &MarketData) -> Option<f64> {
+    match market_data {
+        MarketData::Bar(bar) => Some(bar.volume),
+        MarketData::Tick(tick) => Some(tick.size),
+        MarketData::Quote(_) | MarketData::QuoteBar(_) => None,
+    }
+}
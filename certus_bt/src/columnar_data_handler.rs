@@ -0,0 +1,429 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int64Type, TimestampNanosecondType};
+use arrow_array::{Array, BooleanArray, Float64Array, RecordBatch};
+use arrow_cast::cast;
+use arrow_ipc::reader::FileReader;
+use arrow_schema::{DataType, TimeUnit};
+use certus_core::data::{Bar, DataFeed, DataHandler, DataHandlerError, MarketData, Tick, Timestamp};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use parquet::arrow::ProjectionMask;
+use parquet::arrow::arrow_reader::statistics::StatisticsConverter;
+use parquet::arrow::arrow_reader::{ArrowPredicateFn, ParquetRecordBatchReaderBuilder, RowFilter};
+
+use crate::csv_data_handler::BacktestingDataFeed;
+use crate::data::HistoricBarConsolidationModel;
+
+/// enum defining the market data stored in the files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnarDataKind {
+    /// OHLCV bars, read as `MarketData::Bar`
+    Bars,
+    /// Trades, read as `MarketData::Tick`
+    Trades,
+}
+
+/// enum defining the file format, detected from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnarFormat {
+    /// .parquet files
+    Parquet,
+    /// .arrow, .ipc and .feather files in the Arrow IPC file format
+    ArrowIpc,
+}
+
+impl ColumnarFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "parquet" => Some(ColumnarFormat::Parquet),
+            "arrow" | "ipc" | "feather" => Some(ColumnarFormat::ArrowIpc),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the columns holding the market data fields
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    /// Timestamp, date or integer epoch column
+    pub timestamp: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: String,
+    pub price: String,
+    pub size: String,
+    /// Unit of integer epoch timestamps
+    pub epoch_unit: TimeUnit,
+    /// Time zone of timestamps and dates stored without time zone
+    pub time_zone: Tz,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            timestamp: String::from("timestamp"),
+            open: String::from("open"),
+            high: String::from("high"),
+            low: String::from("low"),
+            close: String::from("close"),
+            volume: String::from("volume"),
+            price: String::from("price"),
+            size: String::from("size"),
+            epoch_unit: TimeUnit::Nanosecond,
+            time_zone: Tz::UTC,
+        }
+    }
+}
+
+impl ColumnMapping {
+    fn columns(&self, kind: ColumnarDataKind) -> Vec<&str> {
+        match kind {
+            ColumnarDataKind::Bars => vec![
+                &self.timestamp,
+                &self.open,
+                &self.high,
+                &self.low,
+                &self.close,
+                &self.volume,
+            ],
+            ColumnarDataKind::Trades => vec![&self.timestamp, &self.price, &self.size],
+        }
+    }
+
+    /// Convert a timestamp column, nulls are kept as None
+    /// Fails on epoch timestamps outside the range of nanosecond timestamps
+    fn timestamps(&self, array: &dyn Array) -> Result<Vec<Option<Timestamp>>, Box<dyn Error>> {
+        if let DataType::Int64 = array.data_type() {
+            let convert = match self.epoch_unit {
+                TimeUnit::Second => Timestamp::checked_from_seconds,
+                TimeUnit::Millisecond => Timestamp::checked_from_millis,
+                TimeUnit::Microsecond => Timestamp::checked_from_micros,
+                TimeUnit::Nanosecond => |nanos| Some(Timestamp::from_nanos(nanos)),
+            };
+            let timestamps = array
+                .as_primitive::<Int64Type>()
+                .iter()
+                .map(|value| {
+                    value
+                        .map(|value| {
+                            convert(value).ok_or_else(|| {
+                                column_error(&self.timestamp, format!("timestamp {} is out of range", value))
+                            })
+                        })
+                        .transpose()
+                })
+                .collect::<Result<_, _>>()?;
+            return Ok(timestamps);
+        }
+
+        // Timestamps with a time zone are stored as UTC, others as local time
+        let (nanos, is_local) = match array.data_type() {
+            DataType::Timestamp(_, Some(tz)) => (
+                cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, Some(tz.clone())))?,
+                false,
+            ),
+            _ => (cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?, true),
+        };
+        Ok(nanos
+            .as_primitive::<TimestampNanosecondType>()
+            .iter()
+            .map(|value| {
+                value.map(|value| {
+                    let timestamp = Timestamp::from_nanos(value);
+                    if is_local && self.time_zone != Tz::UTC {
+                        Timestamp::from_local(timestamp.naive_utc(), &self.time_zone)
+                    } else {
+                        timestamp
+                    }
+                })
+            })
+            .collect())
+    }
+}
+
+/// Date range with an exclusive end, None means unbounded
+#[derive(Debug, Clone, Copy, Default)]
+struct DateRange {
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+}
+
+impl DateRange {
+    fn contains(&self, timestamp: Timestamp) -> bool {
+        self.start.is_none_or(|start| timestamp >= start) && self.end.is_none_or(|end| timestamp < end)
+    }
+
+    fn overlaps(&self, min: Option<Timestamp>, max: Option<Timestamp>) -> bool {
+        let after_start = match (self.start, max) {
+            (Some(start), Some(max)) => max >= start,
+            _ => true,
+        };
+        let before_end = match (self.end, min) {
+            (Some(end), Some(min)) => min < end,
+            _ => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// Date of a partition, taken from the last path component that is a date,
+/// e.g. "date=2024-01-02/part-0.parquet" or "2024-01-02.parquet"
+pub fn partition_date(path: &Path) -> Option<NaiveDate> {
+    path.iter()
+        .rev()
+        .find_map(|component| {
+            let component = Path::new(component).file_stem()?.to_str()?;
+            let value = component.rsplit('=').next()?;
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d"))
+                .ok()
+        })
+}
+
+/// Data handler reading bars or trades from a Parquet or Arrow IPC file, or from a
+/// directory of such files partitioned by date
+pub struct ColumnarDataHandler {
+    pub path: String,
+    pub kind: ColumnarDataKind,
+    pub columns: ColumnMapping,
+    range: DateRange,
+    bar_consolidation_model: HistoricBarConsolidationModel,
+    pub data: Vec<MarketData>,
+}
+
+impl ColumnarDataHandler {
+    pub fn new(
+        path: String,
+        kind: ColumnarDataKind,
+        bar_consolidation_model: HistoricBarConsolidationModel,
+    ) -> Self {
+        Self {
+            path,
+            kind,
+            columns: ColumnMapping::default(),
+            range: DateRange::default(),
+            bar_consolidation_model,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn with_columns(mut self, columns: ColumnMapping) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Only read data from `start` until `end` (exclusive)
+    /// Partitions and Parquet row groups outside the range are skipped without reading them
+    pub fn with_date_range(mut self, start: Option<Timestamp>, end: Option<Timestamp>) -> Self {
+        self.range = DateRange { start, end };
+        self
+    }
+
+    /// Data files of the dataset in path order, partitions outside the date range are skipped
+    pub fn dataset_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        collect_files(Path::new(&self.path), &mut files)?;
+        files.retain(|file| self.is_partition_in_range(file));
+        files.sort();
+        Ok(files)
+    }
+
+    // Partition dates are taken as UTC dates with a day of margin for the exchange time zone
+    fn is_partition_in_range(&self, file: &Path) -> bool {
+        let Some(date) = partition_date(file) else {
+            return true;
+        };
+        let day_start = Timestamp::from_naive_utc(date.and_time(NaiveTime::MIN));
+        self.range.overlaps(
+            Some(day_start - Duration::days(1)),
+            Some(day_start + Duration::days(2)),
+        )
+    }
+
+    fn load_data(&mut self) -> Result<Vec<MarketData>, DataHandlerError> {
+        if !Path::new(&self.path).exists() {
+            return Err(DataHandlerError::FileNotFound { path: self.path.clone() });
+        }
+
+        let mut data: Vec<MarketData> = Vec::new();
+        let files = self.dataset_files().map_err(|e| DataHandlerError::io(&self.path, &e))?;
+        for file in files {
+            let result = match ColumnarFormat::from_path(&file) {
+                Some(ColumnarFormat::Parquet) => self.read_parquet(&file, &mut data),
+                Some(ColumnarFormat::ArrowIpc) => self.read_ipc(&file, &mut data),
+                None => continue,
+            };
+            result.map_err(|e| file_error(&file, e))?;
+        }
+
+        data.sort_by_key(|d| d.timestamp());
+        self.bar_consolidation_model.consolidate_bars(&data)
+    }
+
+    fn read_parquet(&self, file: &Path, data: &mut Vec<MarketData>) -> Result<(), Box<dyn Error>> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(file)?)?;
+        let columns = self.columns.columns(self.kind);
+        let projection = ProjectionMask::columns(builder.parquet_schema(), columns.iter().copied());
+
+        // Skip row groups by their timestamp statistics
+        let statistics = StatisticsConverter::try_new(
+            &self.columns.timestamp,
+            builder.schema(),
+            builder.parquet_schema(),
+        )?;
+        let row_groups = builder.metadata().row_groups();
+        let mins = self.columns.timestamps(&statistics.row_group_mins(row_groups.iter())?)?;
+        let maxes = self.columns.timestamps(&statistics.row_group_maxes(row_groups.iter())?)?;
+        let selected: Vec<usize> = (0..row_groups.len())
+            .filter(|&index| self.range.overlaps(mins[index], maxes[index]))
+            .collect();
+
+        // Filter rows before the other columns are decoded
+        let timestamp_projection =
+            ProjectionMask::columns(builder.parquet_schema(), [self.columns.timestamp.as_str()]);
+        let mapping = self.columns.clone();
+        let range = self.range;
+        // Rows with timestamps that can't be converted are kept, reading the batch reports them
+        let predicate = ArrowPredicateFn::new(timestamp_projection, move |batch: RecordBatch| {
+            let Ok(timestamps) = mapping.timestamps(batch.column(0)) else {
+                return Ok(BooleanArray::from(vec![true; batch.num_rows()]));
+            };
+            Ok(BooleanArray::from_iter(
+                timestamps
+                    .into_iter()
+                    .map(|timestamp| Some(timestamp.is_some_and(|timestamp| range.contains(timestamp)))),
+            ))
+        });
+
+        let reader = builder
+            .with_projection(projection)
+            .with_row_groups(selected)
+            .with_row_filter(RowFilter::new(vec![Box::new(predicate)]))
+            .build()?;
+        for batch in reader {
+            self.read_batch(&batch?, data)?;
+        }
+        Ok(())
+    }
+
+    fn read_ipc(&self, file: &Path, data: &mut Vec<MarketData>) -> Result<(), Box<dyn Error>> {
+        let reader = FileReader::try_new(File::open(file)?, None)?;
+        for batch in reader {
+            self.read_batch(&batch?, data)?;
+        }
+        Ok(())
+    }
+
+    fn read_batch(&self, batch: &RecordBatch, data: &mut Vec<MarketData>) -> Result<(), Box<dyn Error>> {
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| column_error(name, String::from("column not found")))
+        };
+        let float_column = |name: &str| -> Result<Float64Array, Box<dyn Error>> {
+            Ok(cast(column(name)?, &DataType::Float64)?
+                .as_primitive::<Float64Type>()
+                .clone())
+        };
+        let value = |values: &Float64Array, name: &str, row: usize| {
+            if values.is_null(row) {
+                return Err(column_error(name, format!("null value at row {}", row)));
+            }
+            Ok(values.value(row))
+        };
+
+        let timestamps = self.columns.timestamps(column(&self.columns.timestamp)?)?;
+        let names = self.columns.columns(self.kind);
+        let values = names[1..]
+            .iter()
+            .map(|name| float_column(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (row, timestamp) in timestamps.into_iter().enumerate() {
+            let Some(timestamp) = timestamp.filter(|timestamp| self.range.contains(*timestamp)) else {
+                continue;
+            };
+            let mut fields = values
+                .iter()
+                .zip(&names[1..])
+                .map(|(values, name)| value(values, name, row));
+
+            data.push(match self.kind {
+                ColumnarDataKind::Bars => MarketData::Bar(Bar {
+                    timestamp,
+                    open: fields.next().unwrap()?,
+                    high: fields.next().unwrap()?,
+                    low: fields.next().unwrap()?,
+                    close: fields.next().unwrap()?,
+                    volume: fields.next().unwrap()?,
+                }),
+                ColumnarDataKind::Trades => MarketData::Tick(Tick {
+                    timestamp,
+                    price: fields.next().unwrap()?,
+                    size: fields.next().unwrap()?,
+                }),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn column_error(name: &str, message: String) -> DataHandlerError {
+    DataHandlerError::Parse {
+        line: None,
+        column: Some(name.to_string()),
+        message,
+    }
+}
+
+/// Map an error reading `file` to a `DataHandlerError`
+fn file_error(file: &Path, error: Box<dyn Error>) -> DataHandlerError {
+    let path = file.display().to_string();
+    let error = match error.downcast::<DataHandlerError>() {
+        Ok(error) => return *error,
+        Err(error) => error,
+    };
+    match error.downcast::<std::io::Error>() {
+        Ok(error) => DataHandlerError::io(path, &error),
+        Err(error) => DataHandlerError::Parse {
+            line: None,
+            column: None,
+            message: format!("{}: {}", path, error),
+        },
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        if ColumnarFormat::from_path(path).is_some() {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(path)? {
+        collect_files(&entry?.path(), files)?;
+    }
+    Ok(())
+}
+
+impl DataHandler for ColumnarDataHandler {
+    fn start(&mut self) -> Result<(), DataHandlerError> {
+        self.data = self.load_data()?;
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.data = Vec::new();
+    }
+
+    fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
+        Box::new(BacktestingDataFeed::new(&self.data))
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use certus_core::data::{Bar, DataFeed, DataHandler, DataHandlerError, MarketData, Timestamp};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+
+use crate::csv_data_handler::BacktestingDataFeed;
+use crate::data::HistoricBarConsolidationModel;
+
+/// Bars of a single futures contract
+#[derive(Debug, Clone)]
+pub struct ContractData {
+    pub symbol: String,
+    pub expiry: NaiveDate,
+    pub bars: Vec<Bar>,
+    /// Open interest per bar, only needed for `RollRule::OpenInterestCrossover`
+    pub open_interest: Option<Vec<f64>>,
+}
+
+impl ContractData {
+    pub fn new(symbol: String, expiry: NaiveDate, bars: Vec<Bar>) -> Self {
+        Self {
+            symbol,
+            expiry,
+            bars,
+            open_interest: None,
+        }
+    }
+
+    pub fn with_open_interest(mut self, open_interest: Vec<f64>) -> Self {
+        assert_eq!(
+            open_interest.len(),
+            self.bars.len(),
+            "open interest of {} must have a value per bar",
+            self.symbol
+        );
+        self.open_interest = Some(open_interest);
+        self
+    }
+
+    /// Volume summed and open interest of the last bar per local date
+    fn daily_values(&self, rule: RollRule, time_zone: &Tz) -> BTreeMap<NaiveDate, f64> {
+        let mut values = BTreeMap::new();
+        for (index, bar) in self.bars.iter().enumerate() {
+            let date = bar.timestamp.to_local(time_zone).date();
+            match rule {
+                RollRule::OpenInterestCrossover => {
+                    if let Some(open_interest) = &self.open_interest {
+                        values.insert(date, open_interest[index]);
+                    }
+                }
+                _ => *values.entry(date).or_insert(0.0) += bar.volume,
+            }
+        }
+        values
+    }
+}
+
+/// enum defining when the continuous series switches to the next contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollRule {
+    /// Roll at midnight a fixed number of calendar days before the expiry of the front contract
+    DaysBeforeExpiry(u32),
+    /// Roll the day after the volume of the next contract exceeds the front contract
+    VolumeCrossover,
+    /// Roll the day after the open interest of the next contract exceeds the front contract
+    OpenInterestCrossover,
+}
+
+/// enum defining how prices before a roll are adjusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjustmentMethod {
+    /// Add the price difference at each roll to all earlier prices
+    BackAdjusted,
+    /// Multiply all earlier prices with the price ratio at each roll
+    RatioAdjusted,
+    /// Keep the prices of each contract, the series gaps at the rolls
+    Unadjusted,
+}
+
+/// Switch from one contract to the next in a continuous series
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollEvent {
+    /// Time of the first bar of the new contract
+    pub timestamp: Timestamp,
+    pub from_symbol: String,
+    pub to_symbol: String,
+    /// Close of both contracts on the last bar before the roll, unadjusted
+    pub from_price: f64,
+    pub to_price: f64,
+    /// Prices of the old and the new contract in the adjusted series, used for roll trades
+    pub exit_price: f64,
+    pub entry_price: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContinuousSeries {
+    pub bars: Vec<Bar>,
+    pub rolls: Vec<RollEvent>,
+}
+
+/// Model stitching per expiry contracts into a continuous series
+pub struct ContinuousFuturesModel {
+    pub roll_rule: RollRule,
+    pub adjustment: AdjustmentMethod,
+    /// Time zone of the roll dates
+    pub time_zone: Tz,
+}
+
+struct Segment<'a> {
+    contract: &'a ContractData,
+    bars: Vec<Bar>,
+}
+
+impl ContinuousFuturesModel {
+    pub fn new(roll_rule: RollRule) -> Self {
+        Self {
+            roll_rule,
+            adjustment: AdjustmentMethod::BackAdjusted,
+            time_zone: Tz::UTC,
+        }
+    }
+
+    pub fn with_adjustment(mut self, adjustment: AdjustmentMethod) -> Self {
+        self.adjustment = adjustment;
+        self
+    }
+
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    pub fn build(&self, contracts: &[ContractData]) -> ContinuousSeries {
+        let mut contracts: Vec<&ContractData> = contracts.iter().collect();
+        contracts.sort_by_key(|contract| contract.expiry);
+
+        // Roll times between consecutive contracts, never before the previous roll
+        let mut roll_times: Vec<Timestamp> = Vec::new();
+        for pair in contracts.windows(2) {
+            let previous = roll_times.last().copied();
+            let roll_time = self.roll_time(pair[0], pair[1], previous);
+            roll_times.push(previous.map_or(roll_time, |previous| previous.max(roll_time)));
+        }
+
+        let segments: Vec<Segment> = contracts
+            .iter()
+            .enumerate()
+            .map(|(index, contract)| {
+                let start = index.checked_sub(1).map(|previous| roll_times[previous]);
+                let end = roll_times.get(index).copied();
+                let bars = contract
+                    .bars
+                    .iter()
+                    .filter(|bar| start.is_none_or(|start| bar.timestamp >= start))
+                    .filter(|bar| end.is_none_or(|end| bar.timestamp < end))
+                    .copied()
+                    .collect();
+                Segment { contract, bars }
+            })
+            .filter(|segment: &Segment| !segment.bars.is_empty())
+            .collect();
+
+        let mut rolls: Vec<RollEvent> = segments
+            .windows(2)
+            .map(|pair| {
+                let last = pair[0].bars.last().unwrap();
+                let first = pair[1].bars.first().unwrap();
+                let to_price = pair[1]
+                    .contract
+                    .bars
+                    .iter()
+                    .rev()
+                    .find(|bar| bar.timestamp < first.timestamp && bar.timestamp <= last.timestamp)
+                    .map_or(first.open, |bar| bar.close);
+                RollEvent {
+                    timestamp: first.timestamp,
+                    from_symbol: pair[0].contract.symbol.clone(),
+                    to_symbol: pair[1].contract.symbol.clone(),
+                    from_price: last.close,
+                    to_price,
+                    exit_price: last.close,
+                    entry_price: to_price,
+                }
+            })
+            .collect();
+
+        // Adjust from the last contract backwards, the last contract keeps its prices
+        let mut bars: Vec<Vec<Bar>> = Vec::with_capacity(segments.len());
+        let mut offset = 0.0;
+        let mut factor = 1.0;
+        for (index, segment) in segments.iter().enumerate().rev() {
+            if let Some(roll) = rolls.get_mut(index) {
+                roll.entry_price = self.adjust(roll.to_price, offset, factor);
+                match self.adjustment {
+                    AdjustmentMethod::BackAdjusted => offset += roll.to_price - roll.from_price,
+                    AdjustmentMethod::RatioAdjusted => factor *= roll.to_price / roll.from_price,
+                    AdjustmentMethod::Unadjusted => {}
+                }
+                roll.exit_price = self.adjust(roll.from_price, offset, factor);
+            }
+            bars.push(
+                segment
+                    .bars
+                    .iter()
+                    .map(|bar| Bar {
+                        open: self.adjust(bar.open, offset, factor),
+                        high: self.adjust(bar.high, offset, factor),
+                        low: self.adjust(bar.low, offset, factor),
+                        close: self.adjust(bar.close, offset, factor),
+                        ..*bar
+                    })
+                    .collect(),
+            );
+        }
+
+        for roll in rolls.iter() {
+            log::debug!(
+                "Rolling from {} to {} at {} ({} -> {})",
+                roll.from_symbol,
+                roll.to_symbol,
+                roll.timestamp,
+                roll.from_price,
+                roll.to_price
+            );
+        }
+
+        ContinuousSeries {
+            bars: bars.into_iter().rev().flatten().collect(),
+            rolls,
+        }
+    }
+
+    fn adjust(&self, price: f64, offset: f64, factor: f64) -> f64 {
+        match self.adjustment {
+            AdjustmentMethod::BackAdjusted => price + offset,
+            AdjustmentMethod::RatioAdjusted => price * factor,
+            AdjustmentMethod::Unadjusted => price,
+        }
+    }
+
+    fn start_of_day(&self, date: NaiveDate) -> Timestamp {
+        Timestamp::from_local(date.and_time(NaiveTime::MIN), &self.time_zone)
+    }
+
+    /// First time the series holds `next` instead of `front`
+    fn roll_time(&self, front: &ContractData, next: &ContractData, previous: Option<Timestamp>) -> Timestamp {
+        let expiry = self.start_of_day(front.expiry);
+        match self.roll_rule {
+            RollRule::DaysBeforeExpiry(days) => self.start_of_day(front.expiry - Duration::days(days as i64)),
+            rule => {
+                let front_values = front.daily_values(rule, &self.time_zone);
+                let next_values = next.daily_values(rule, &self.time_zone);
+                let first_date = previous.map(|previous| previous.to_local(&self.time_zone).date());
+                front_values
+                    .iter()
+                    .filter(|(date, _)| first_date.is_none_or(|first_date| **date >= first_date))
+                    .find(|(date, value)| next_values.get(date).is_some_and(|next| next > value))
+                    .map(|(date, _)| self.start_of_day(*date + Duration::days(1)))
+                    .map_or(expiry, |roll_time| roll_time.min(expiry))
+            }
+        }
+    }
+}
+
+/// Data handler replaying a continuous series
+pub struct ContinuousFuturesDataHandler {
+    pub series: ContinuousSeries,
+    bar_consolidation_model: HistoricBarConsolidationModel,
+    data: Vec<MarketData>,
+}
+
+impl ContinuousFuturesDataHandler {
+    pub fn new(series: ContinuousSeries, bar_consolidation_model: HistoricBarConsolidationModel) -> Self {
+        Self {
+            series,
+            bar_consolidation_model,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl DataHandler for ContinuousFuturesDataHandler {
+    fn start(&mut self) -> Result<(), DataHandlerError> {
+        let data: Vec<MarketData> = self.series.bars.iter().copied().map(MarketData::Bar).collect();
+        self.data = self.bar_consolidation_model.consolidate_bars(&data)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.data = Vec::new();
+    }
+
+    fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
+        Box::new(BacktestingDataFeed::new(&self.data))
+    }
+}
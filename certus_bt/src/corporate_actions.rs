@@ -0,0 +1,163 @@
+use certus_core::data::{DataHandlerError, MarketData, Timestamp};
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use csv::ReaderBuilder;
+
+/// enum defining the supported corporate actions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CorporateActionKind {
+    /// Number of new shares per old share, e.g. 4 for a 4:1 split or 0.1 for a 1:10 reverse split
+    Split(f64),
+    /// Cash paid per share
+    Dividend(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CorporateAction {
+    /// First day the stock trades without the split or dividend
+    pub ex_date: NaiveDate,
+    pub kind: CorporateActionKind,
+}
+
+/// Corporate actions of a single stock, sorted by ex-date
+/// Either adjust the historical prices or let the broker apply the actions to positions, not both
+#[derive(Debug, Clone)]
+pub struct CorporateActions {
+    pub actions: Vec<CorporateAction>,
+    /// Time zone of the exchange, an action applies from midnight of its ex-date
+    pub time_zone: Tz,
+}
+
+impl CorporateActions {
+    pub fn new(mut actions: Vec<CorporateAction>) -> Self {
+        actions.sort_by_key(|action| action.ex_date);
+        Self {
+            actions,
+            time_zone: Tz::UTC,
+        }
+    }
+
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Read rows of "ex_date,type,value" with a header, e.g. "2020-08-31,split,4" or "2024-02-09,dividend,0.24"
+    pub fn from_csv(file_path: &str) -> Result<Self, DataHandlerError> {
+        let mut reader = ReaderBuilder::new().from_path(file_path).map_err(|e| match e.kind() {
+            csv::ErrorKind::Io(io) => DataHandlerError::io(file_path, io),
+            _ => DataHandlerError::Parse {
+                line: None,
+                column: None,
+                message: e.to_string(),
+            },
+        })?;
+
+        let mut actions = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| DataHandlerError::Parse {
+                line: e.position().map(|position| position.line()),
+                column: None,
+                message: e.to_string(),
+            })?;
+            let line = record.position().map(|position| position.line());
+            let parse_error = |column: &str, message: String| DataHandlerError::Parse {
+                line,
+                column: Some(column.to_string()),
+                message,
+            };
+
+            let ex_date = NaiveDate::parse_from_str(record.get(0).unwrap_or_default(), "%Y-%m-%d")
+                .map_err(|e| parse_error("ex_date", e.to_string()))?;
+            let value = record
+                .get(2)
+                .unwrap_or_default()
+                .parse::<f64>()
+                .map_err(|e| parse_error("value", e.to_string()))?;
+            let kind = match record.get(1).unwrap_or_default().to_lowercase().as_str() {
+                "split" => CorporateActionKind::Split(value),
+                "dividend" => CorporateActionKind::Dividend(value),
+                other => return Err(parse_error("type", format!("unknown corporate action {}", other))),
+            };
+            actions.push(CorporateAction { ex_date, kind });
+        }
+        Ok(Self::new(actions))
+    }
+
+    /// Time from which `action` applies
+    pub fn ex_timestamp(&self, action: &CorporateAction) -> Timestamp {
+        Timestamp::from_local(action.ex_date.and_time(NaiveTime::MIN), &self.time_zone)
+    }
+
+    /// Adjust the prices and volumes before each split, and with `dividends` the prices before
+    /// each dividend by 1 - dividend / close before the ex-date, so returns include the dividends
+    /// `data` has to be sorted by timestamp
+    pub fn adjust(&self, data: &mut [MarketData], dividends: bool) {
+        for action in self.actions.iter() {
+            let end = data.partition_point(|market_data| market_data.timestamp() < self.ex_timestamp(action));
+            let (price_factor, volume_factor) = match action.kind {
+                CorporateActionKind::Split(ratio) => (1.0 / ratio, ratio),
+                CorporateActionKind::Dividend(amount) if dividends => {
+                    let Some(previous) = end.checked_sub(1).map(|index| price(&data[index])) else {
+                        continue;
+                    };
+                    (1.0 - amount / previous, 1.0)
+                }
+                CorporateActionKind::Dividend(_) => continue,
+            };
+
+            for market_data in data[..end].iter_mut() {
+                match market_data {
+                    MarketData::Bar(bar) => {
+                        bar.open *= price_factor;
+                        bar.high *= price_factor;
+                        bar.low *= price_factor;
+                        bar.close *= price_factor;
+                        bar.volume *= volume_factor;
+                    }
+                    MarketData::Tick(tick) => {
+                        tick.price *= price_factor;
+                        tick.size *= volume_factor;
+                    }
+                    MarketData::Quote(quote) => {
+                        quote.bid *= price_factor;
+                        quote.ask *= price_factor;
+                        quote.bid_size *= volume_factor;
+                        quote.ask_size *= volume_factor;
+                    }
+                    MarketData::QuoteBar(quote_bar) => {
+                        quote_bar.bid_open *= price_factor;
+                        quote_bar.bid_high *= price_factor;
+                        quote_bar.bid_low *= price_factor;
+                        quote_bar.bid_close *= price_factor;
+                        quote_bar.bid_size *= volume_factor;
+                        quote_bar.ask_open *= price_factor;
+                        quote_bar.ask_high *= price_factor;
+                        quote_bar.ask_low *= price_factor;
+                        quote_bar.ask_close *= price_factor;
+                        quote_bar.ask_size *= volume_factor;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn price(market_data: &MarketData) -> f64 {
+    match market_data {
+        MarketData::Bar(bar) => bar.close,
+        MarketData::Tick(tick) => tick.price,
+        MarketData::Quote(quote) => quote.mid(),
+        MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar().close,
+    }
+}
+
+/// Dividend credited to, or for short positions debited from, the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct DividendPayment {
+    pub instrument: u32,
+    pub strategy_id: usize,
+    pub trade_id: usize,
+    pub timestamp: Timestamp,
+    pub amount: f64,
+}
@@ -1,37 +1,217 @@
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::fmt;
+use std::fs::File;
 
-use certus_core::data::{DataFeed, DataHandler, DataHandlerError, MarketData};
-use csv::{ReaderBuilder, StringRecord};
+use certus_core::data::{
+    Bar, DataFeed, DataHandler, DataHandlerError, ErrorPolicy, MarketData, MarketDataValidator, Quote, Tick,
+    Timestamp,
+};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, ParseResult};
+use chrono_tz::Tz;
+use csv::{Reader, ReaderBuilder, StringRecord, StringRecordsIter};
 
-use crate::data::HistoricBarConsolidationModel;
+use crate::cleaning::{CleaningAction, CleaningConfig, DataQualityReport, MarketDataCleaner};
+use crate::corporate_actions::CorporateActions;
+use crate::data::{HistoricBarConsolidationModel, StreamingBarConsolidation};
 
 pub struct BacktestingDataFeed<'a> {
     index: usize,
     data: &'a [MarketData],
 }
 
+impl<'a> BacktestingDataFeed<'a> {
+    pub fn new(data: &'a [MarketData]) -> Self {
+        Self { index: 0, data }
+    }
+}
+
 impl<'a> DataFeed for BacktestingDataFeed<'a> {
-    fn poll(&mut self) -> Option<MarketData> {
-        if self.data.len() == 0 || self.index >= self.data.len() {
-            return None;
+    fn poll(&mut self) -> Result<Option<MarketData>, DataHandlerError> {
+        if self.data.is_empty() || self.index >= self.data.len() {
+            return Ok(None);
         }
 
         let cur_data = self.data[self.index];
 
         self.index += 1;
 
-        Some(cur_data)
+        Ok(Some(cur_data))
     }
 }
 
 pub trait CSVRowParser {
+    /// Parse a row, return a `CSVFieldError` to report the column that failed
     fn parse_row(&mut self, row: StringRecord) -> Result<MarketData, Box<dyn Error>>;
+
+    /// Called with the header row before the first row is parsed
+    fn set_headers(&mut self, _headers: &StringRecord) {}
+
+    fn delimiter(&self) -> u8 {
+        b','
+    }
+
+    fn has_headers(&self) -> bool {
+        true
+    }
+
+    /// Description of the settings that change the parsed data, caches built with other settings are rebuilt
+    fn settings(&self) -> String {
+        String::new()
+    }
+}
+
+/// Error of a single field of a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CSVFieldError {
+    pub column: String,
+    pub message: String,
+}
+
+impl CSVFieldError {
+    pub fn new(column: impl fmt::Display, message: impl fmt::Display) -> Self {
+        Self {
+            column: column.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CSVFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl Error for CSVFieldError {}
+
+/// Parse a date without offset, e.g. "01/02/2015 09:01", as local time in `time_zone`
+/// Formats without a time, e.g. "%Y-%m-%d", are parsed as midnight
+pub fn parse_local_timestamp(value: &str, format: &str, time_zone: &Tz) -> ParseResult<Timestamp> {
+    let dt = match NaiveDateTime::parse_from_str(value, format) {
+        Ok(dt) => dt,
+        Err(e) => match NaiveDate::parse_from_str(value, format) {
+            Ok(date) => date.and_time(NaiveTime::MIN),
+            Err(_) => return Err(e),
+        },
+    };
+    Ok(Timestamp::from_local(dt, time_zone))
+}
+
+fn csv_error(file_path: &str, error: csv::Error) -> DataHandlerError {
+    let line = error.position().map(|position| position.line());
+    match error.kind() {
+        csv::ErrorKind::Io(e) => DataHandlerError::io(file_path, e),
+        _ => DataHandlerError::Parse {
+            line,
+            column: None,
+            message: error.to_string(),
+        },
+    }
+}
+
+fn open_reader(file_path: &str, csv_row_parser: &mut dyn CSVRowParser) -> Result<Reader<File>, DataHandlerError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(csv_row_parser.has_headers())
+        .delimiter(csv_row_parser.delimiter())
+        .from_path(file_path)
+        .map_err(|e| csv_error(file_path, e))?;
+    if csv_row_parser.has_headers() {
+        let headers = reader.headers().map_err(|e| csv_error(file_path, e))?;
+        csv_row_parser.set_headers(headers);
+    }
+    Ok(reader)
+}
+
+/// Cleaner for rows that are consolidated afterwards, the consolidation assumes chronological rows
+fn consolidation_cleaner(config: CleaningConfig) -> MarketDataCleaner {
+    assert!(
+        config.out_of_order != CleaningAction::Report,
+        "out of order rows can't be consolidated, drop them instead of reporting them"
+    );
+    MarketDataCleaner::new(config)
+}
+
+/// Parse and validate a single record, returns the rows released by the cleaner when it is set
+/// The cleaner replaces the validator
+fn parse_record(
+    file_path: &str,
+    record: csv::Result<StringRecord>,
+    csv_row_parser: &mut dyn CSVRowParser,
+    validator: &mut MarketDataValidator,
+    cleaner: Option<&mut MarketDataCleaner>,
+) -> Result<Vec<MarketData>, DataHandlerError> {
+    let record = record.map_err(|e| csv_error(file_path, e))?;
+    let line = record.position().map(|position| position.line());
+    let market_data = csv_row_parser.parse_row(record).map_err(|e| {
+        let (column, message) = match e.downcast_ref::<CSVFieldError>() {
+            Some(field_error) => (Some(field_error.column.clone()), field_error.message.clone()),
+            None => (None, e.to_string()),
+        };
+        DataHandlerError::Parse { line, column, message }
+    })?;
+    if let Some(cleaner) = cleaner {
+        return Ok(cleaner.clean(market_data, line));
+    }
+    validator.validate(&market_data, line)?;
+    Ok(vec![market_data])
+}
+
+fn log_quality_report(file_path: &str, cleaner: Option<&MarketDataCleaner>) {
+    if let Some(cleaner) = cleaner {
+        log::info!("Data quality of {}: {}", file_path, cleaner.report());
+    }
+}
+
+/// Skip row errors with `ErrorPolicy::SkipAndReport`, other errors are returned
+fn handle_error(
+    error: DataHandlerError,
+    error_policy: ErrorPolicy,
+    skipped: &mut Vec<DataHandlerError>,
+) -> Result<(), DataHandlerError> {
+    if error_policy == ErrorPolicy::Abort || !error.is_row_error() {
+        return Err(error);
+    }
+    log::warn!("Skipping row, {}", error);
+    skipped.push(error);
+    Ok(())
+}
+
+/// Parse all rows of the file
+pub(crate) fn read_csv(
+    file_path: &str,
+    csv_row_parser: &mut dyn CSVRowParser,
+    error_policy: ErrorPolicy,
+    skipped: &mut Vec<DataHandlerError>,
+    mut cleaner: Option<&mut MarketDataCleaner>,
+) -> Result<Vec<MarketData>, DataHandlerError> {
+    let mut reader = open_reader(file_path, csv_row_parser)?;
+    let mut validator = MarketDataValidator::new();
+
+    let mut data: Vec<MarketData> = Vec::new();
+
+    for result in reader.records() {
+        match parse_record(file_path, result, csv_row_parser, &mut validator, cleaner.as_deref_mut()) {
+            Ok(market_data) => data.extend(market_data),
+            Err(e) => handle_error(e, error_policy, skipped)?,
+        }
+    }
+    log_quality_report(file_path, cleaner.as_deref());
+
+    Ok(data)
 }
 
+/// Data handler loading the whole file into memory on start
+/// Use it when the data is replayed many times, e.g. for optimization runs
 pub struct CSVDataHandler {
     pub file_path: String,
     csv_row_parser: Box<dyn CSVRowParser>,
     bar_consolidation_model: HistoricBarConsolidationModel,
+    pub error_policy: ErrorPolicy,
+    skipped: Vec<DataHandlerError>,
+    cleaner: Option<MarketDataCleaner>,
+    corporate_actions: Option<CorporateActions>,
+    adjust_dividends: bool,
     pub data: Vec<MarketData>,
 }
 
@@ -45,36 +225,61 @@ impl CSVDataHandler {
             file_path,
             csv_row_parser,
             bar_consolidation_model,
+            error_policy: ErrorPolicy::Abort,
+            skipped: Vec::new(),
+            cleaner: None,
+            corporate_actions: None,
+            adjust_dividends: false,
             data: Vec::new(),
         }
     }
 
-    fn load_data(&mut self) -> Result<Vec<MarketData>, Box<dyn Error>> {
-        let mut reader = ReaderBuilder::new()
-            .has_headers(true)
-            .from_path(&self.file_path)?;
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
 
-        let mut data: Vec<MarketData> = Vec::new();
+    /// Rows skipped with `ErrorPolicy::SkipAndReport`
+    pub fn skipped_rows(&self) -> &[DataHandlerError] {
+        &self.skipped
+    }
 
-        for result in reader.records() {
-            let record = result?;
-            data.push(self.csv_row_parser.parse_row(record)?);
-        }
+    /// Clean the rows before they are consolidated
+    /// Consolidation needs chronological rows, `out_of_order` can't be `CleaningAction::Report`
+    pub fn with_cleaner(mut self, config: CleaningConfig) -> Self {
+        self.cleaner = Some(consolidation_cleaner(config));
+        self
+    }
+
+    pub fn quality_report(&self) -> Option<&DataQualityReport> {
+        self.cleaner.as_ref().map(|cleaner| cleaner.report())
+    }
 
-        let consolidated_data = self.bar_consolidation_model.consolidate_bars(&data);
-        Ok(consolidated_data)
+    /// Adjust the prices for splits, and with `dividends` for dividends, before they are consolidated
+    pub fn with_corporate_actions(mut self, corporate_actions: CorporateActions, dividends: bool) -> Self {
+        self.corporate_actions = Some(corporate_actions);
+        self.adjust_dividends = dividends;
+        self
     }
 }
 
 impl DataHandler for CSVDataHandler {
     fn start(&mut self) -> Result<(), DataHandlerError> {
-        self.data = match self.load_data() {
-            Ok(d) => d,
-            Err(e) => {
-                print!("{}", e);
-                return Err(DataHandlerError::FailedToStart);
-            }
-        };
+        self.skipped.clear();
+        if let Some(cleaner) = self.cleaner.as_mut() {
+            cleaner.reset();
+        }
+        let mut data = read_csv(
+            &self.file_path,
+            self.csv_row_parser.as_mut(),
+            self.error_policy,
+            &mut self.skipped,
+            self.cleaner.as_mut(),
+        )?;
+        if let Some(corporate_actions) = &self.corporate_actions {
+            corporate_actions.adjust(&mut data, self.adjust_dividends);
+        }
+        self.data = self.bar_consolidation_model.consolidate_bars(&data)?;
 
         Ok(())
     }
@@ -84,9 +289,459 @@ impl DataHandler for CSVDataHandler {
     }
 
     fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
-        Box::new(BacktestingDataFeed {
-            index: 0,
-            data: &self.data,
+        Box::new(BacktestingDataFeed::new(&self.data))
+    }
+}
+
+/// Data handler reading and consolidating rows lazily while the data feed is polled
+/// Only the rows of the bar being consolidated are kept in memory
+pub struct StreamingCSVDataHandler {
+    pub file_path: String,
+    csv_row_parser: Box<dyn CSVRowParser>,
+    bar_consolidation_model: HistoricBarConsolidationModel,
+    pub error_policy: ErrorPolicy,
+    skipped: Vec<DataHandlerError>,
+    cleaner: Option<MarketDataCleaner>,
+    reader: Option<Reader<File>>,
+}
+
+impl StreamingCSVDataHandler {
+    pub fn new(
+        file_path: String,
+        csv_row_parser: Box<dyn CSVRowParser>,
+        bar_consolidation_model: HistoricBarConsolidationModel,
+    ) -> Self {
+        Self {
+            file_path,
+            csv_row_parser,
+            bar_consolidation_model,
+            error_policy: ErrorPolicy::Abort,
+            skipped: Vec::new(),
+            cleaner: None,
+            reader: None,
+        }
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Rows skipped with `ErrorPolicy::SkipAndReport` while polling the data feed
+    pub fn skipped_rows(&self) -> &[DataHandlerError] {
+        &self.skipped
+    }
+
+    /// Clean the rows before they are consolidated
+    /// Consolidation needs chronological rows, `out_of_order` can't be `CleaningAction::Report`
+    pub fn with_cleaner(mut self, config: CleaningConfig) -> Self {
+        self.cleaner = Some(consolidation_cleaner(config));
+        self
+    }
+
+    /// Report of the rows read so far, complete once the data feed is exhausted
+    pub fn quality_report(&self) -> Option<&DataQualityReport> {
+        self.cleaner.as_ref().map(|cleaner| cleaner.report())
+    }
+}
+
+impl DataHandler for StreamingCSVDataHandler {
+    fn start(&mut self) -> Result<(), DataHandlerError> {
+        self.skipped.clear();
+        if let Some(cleaner) = self.cleaner.as_mut() {
+            cleaner.reset();
+        }
+        self.reader = Some(open_reader(&self.file_path, self.csv_row_parser.as_mut())?);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.reader = None;
+    }
+
+    fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
+        Box::new(StreamingCSVDataFeed {
+            file_path: &self.file_path,
+            records: self.reader.as_mut().map(|reader| reader.records()),
+            csv_row_parser: self.csv_row_parser.as_mut(),
+            validator: MarketDataValidator::new(),
+            error_policy: self.error_policy,
+            skipped: &mut self.skipped,
+            cleaner: self.cleaner.as_mut(),
+            consolidation: self.bar_consolidation_model.streaming(),
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+pub struct StreamingCSVDataFeed<'a> {
+    file_path: &'a str,
+    // None when the data handler wasn't started or the file is exhausted
+    records: Option<StringRecordsIter<'a, File>>,
+    csv_row_parser: &'a mut dyn CSVRowParser,
+    validator: MarketDataValidator,
+    error_policy: ErrorPolicy,
+    skipped: &'a mut Vec<DataHandlerError>,
+    cleaner: Option<&'a mut MarketDataCleaner>,
+    consolidation: StreamingBarConsolidation,
+    pending: VecDeque<MarketData>,
+}
+
+impl<'a> StreamingCSVDataFeed<'a> {
+    fn finish(&mut self) {
+        self.records = None;
+        log_quality_report(self.file_path, self.cleaner.as_deref());
+        self.pending
+            .extend(self.consolidation.flush());
+    }
+}
+
+impl<'a> DataFeed for StreamingCSVDataFeed<'a> {
+    fn poll(&mut self) -> Result<Option<MarketData>, DataHandlerError> {
+        loop {
+            if let Some(market_data) = self.pending.pop_front() {
+                return Ok(Some(market_data));
+            }
+
+            let Some(records) = self.records.as_mut() else {
+                return Ok(None);
+            };
+            let Some(record) = records.next() else {
+                self.finish();
+                continue;
+            };
+
+            let cleaner = self.cleaner.as_deref_mut();
+            match parse_record(self.file_path, record, self.csv_row_parser, &mut self.validator, cleaner) {
+                Ok(rows) => {
+                    for market_data in rows {
+                        self.pending.extend(self.consolidation.update(market_data));
+                    }
+                }
+                Err(e) => {
+                    if let Err(e) = handle_error(e, self.error_policy, self.skipped) {
+                        self.records = None;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Column of a CSV file, by header name or by index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CSVColumn {
+    /// Header name, matched case insensitive
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for CSVColumn {
+    fn from(name: &str) -> Self {
+        CSVColumn::Name(name.to_string())
+    }
+}
+
+impl From<usize> for CSVColumn {
+    fn from(index: usize) -> Self {
+        CSVColumn::Index(index)
+    }
+}
+
+impl fmt::Display for CSVColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CSVColumn::Name(name) => write!(f, "{}", name),
+            CSVColumn::Index(index) => write!(f, "#{}", index),
+        }
+    }
+}
+
+/// enum defining the unit of epoch timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpochUnit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+/// enum defining how the timestamp of a row is stored
+#[derive(Debug, Clone)]
+pub enum CSVTimestamp {
+    /// Date and time in one column, e.g. "20150102 090100" with "%Y%m%d %H%M%S"
+    DateTime { column: CSVColumn, format: String },
+    /// Date and time in separate columns
+    SplitDateTime {
+        date: CSVColumn,
+        date_format: String,
+        time: CSVColumn,
+        time_format: String,
+    },
+    /// Integer time since the Unix epoch (UTC)
+    Epoch { column: CSVColumn, unit: EpochUnit },
+}
+
+/// enum defining the market data fields of a row
+#[derive(Debug, Clone)]
+pub enum CSVFields {
+    /// Bars, the volume is 0 without a volume column
+    Bars {
+        open: CSVColumn,
+        high: CSVColumn,
+        low: CSVColumn,
+        close: CSVColumn,
+        volume: Option<CSVColumn>,
+    },
+    /// Trades, the size is 0 without a size column
+    Ticks {
+        price: CSVColumn,
+        size: Option<CSVColumn>,
+    },
+    /// Top of book quotes, the sizes are 0 without size columns
+    Quotes {
+        bid: CSVColumn,
+        ask: CSVColumn,
+        bid_size: Option<CSVColumn>,
+        ask_size: Option<CSVColumn>,
+    },
+}
+
+/// Configurable `CSVRowParser`, use one of the presets or describe the columns of the file
+pub struct GenericCSVRowParser {
+    pub timestamp: CSVTimestamp,
+    pub fields: CSVFields,
+    pub delimiter: u8,
+    pub has_headers: bool,
+    /// Time zone of dates without offset
+    pub time_zone: Tz,
+    header_indices: HashMap<String, usize>,
+}
+
+impl GenericCSVRowParser {
+    pub fn new(timestamp: CSVTimestamp, fields: CSVFields) -> Self {
+        Self {
+            timestamp,
+            fields,
+            delimiter: b',',
+            has_headers: true,
+            time_zone: Tz::UTC,
+            header_indices: HashMap::new(),
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// The first row is data instead of a header, columns have to be mapped by index
+    pub fn without_headers(mut self) -> Self {
+        self.has_headers = false;
+        self
+    }
+
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// TradeStation export, e.g. "01/02/2015,09:01,2050.25,2051,2049.5,2050.75,10,12,22"
+    /// with date, time, open, high, low, close, up, down and volume columns
+    pub fn tradestation(time_zone: Tz) -> Self {
+        Self::new(
+            CSVTimestamp::SplitDateTime {
+                date: 0.into(),
+                date_format: String::from("%m/%d/%Y"),
+                time: 1.into(),
+                time_format: String::from("%H:%M"),
+            },
+            Self::bar_columns(2, Some(8)),
+        )
+        .with_time_zone(time_zone)
+    }
+
+    /// NinjaTrader export without header, e.g. "20150102 090100;2050.25;2051;2049.5;2050.75;22"
+    pub fn ninjatrader(time_zone: Tz) -> Self {
+        Self::new(
+            CSVTimestamp::DateTime {
+                column: 0.into(),
+                format: String::from("%Y%m%d %H%M%S"),
+            },
+            Self::bar_columns(1, Some(5)),
+        )
+        .with_delimiter(b';')
+        .without_headers()
+        .with_time_zone(time_zone)
+    }
+
+    /// MetaTrader history export without header, e.g. "2015.01.02,09:01,1.2050,1.2060,1.2040,1.2055,22"
+    pub fn metatrader(time_zone: Tz) -> Self {
+        Self::new(
+            CSVTimestamp::SplitDateTime {
+                date: 0.into(),
+                date_format: String::from("%Y.%m.%d"),
+                time: 1.into(),
+                time_format: String::from("%H:%M"),
+            },
+            Self::bar_columns(2, Some(6)),
+        )
+        .without_headers()
+        .with_time_zone(time_zone)
+    }
+
+    /// Yahoo Finance daily bars with a "Date,Open,High,Low,Close,Adj Close,Volume" header
+    pub fn yahoo(time_zone: Tz) -> Self {
+        Self::new(
+            CSVTimestamp::DateTime {
+                column: "Date".into(),
+                format: String::from("%Y-%m-%d"),
+            },
+            CSVFields::Bars {
+                open: "Open".into(),
+                high: "High".into(),
+                low: "Low".into(),
+                close: "Close".into(),
+                volume: Some("Volume".into()),
+            },
+        )
+        .with_time_zone(time_zone)
+    }
+
+    // Open, high, low and close in consecutive columns starting at `open`
+    fn bar_columns(open: usize, volume: Option<usize>) -> CSVFields {
+        CSVFields::Bars {
+            open: open.into(),
+            high: (open + 1).into(),
+            low: (open + 2).into(),
+            close: (open + 3).into(),
+            volume: volume.map(CSVColumn::Index),
+        }
+    }
+
+    fn field<'r>(&self, row: &'r StringRecord, column: &CSVColumn) -> Result<&'r str, CSVFieldError> {
+        let index = match column {
+            CSVColumn::Index(index) => *index,
+            CSVColumn::Name(name) => *self
+                .header_indices
+                .get(&name.to_lowercase())
+                .ok_or_else(|| CSVFieldError::new(column, "not found in header"))?,
+        };
+        let value = row
+            .get(index)
+            .ok_or_else(|| CSVFieldError::new(column, "missing in row"))?;
+        Ok(value.trim())
+    }
+
+    fn number(&self, row: &StringRecord, column: &CSVColumn) -> Result<f64, CSVFieldError> {
+        let value = self.field(row, column)?;
+        value
+            .parse::<f64>()
+            .map_err(|e| CSVFieldError::new(column, format!("invalid number {:?}: {}", value, e)))
+    }
+
+    fn optional_number(&self, row: &StringRecord, column: &Option<CSVColumn>) -> Result<f64, CSVFieldError> {
+        match column {
+            Some(column) => self.number(row, column),
+            None => Ok(0.0),
+        }
+    }
+
+    fn timestamp(&self, row: &StringRecord) -> Result<Timestamp, CSVFieldError> {
+        let (column, value, format) = match &self.timestamp {
+            CSVTimestamp::Epoch { column, unit } => {
+                let value = self.field(row, column)?;
+                let epoch = value
+                    .parse::<i64>()
+                    .map_err(|e| CSVFieldError::new(column, format!("invalid timestamp {:?}: {}", value, e)))?;
+                let timestamp = match unit {
+                    EpochUnit::Seconds => Timestamp::checked_from_seconds(epoch),
+                    EpochUnit::Milliseconds => Timestamp::checked_from_millis(epoch),
+                    EpochUnit::Microseconds => Timestamp::checked_from_micros(epoch),
+                    EpochUnit::Nanoseconds => Some(Timestamp::from_nanos(epoch)),
+                };
+                return timestamp
+                    .ok_or_else(|| CSVFieldError::new(column, format!("timestamp {} is out of range", epoch)));
+            }
+            CSVTimestamp::DateTime { column, format } => {
+                (column, self.field(row, column)?.to_string(), format.clone())
+            }
+            CSVTimestamp::SplitDateTime {
+                date,
+                date_format,
+                time,
+                time_format,
+            } => (
+                date,
+                format!("{} {}", self.field(row, date)?, self.field(row, time)?),
+                format!("{} {}", date_format, time_format),
+            ),
+        };
+
+        parse_local_timestamp(&value, &format, &self.time_zone).map_err(|e| {
+            CSVFieldError::new(column, format!("invalid date {:?} for format {:?}: {}", value, format, e))
+        })
+    }
+}
+
+impl CSVRowParser for GenericCSVRowParser {
+    fn parse_row(&mut self, row: StringRecord) -> Result<MarketData, Box<dyn Error>> {
+        let timestamp = self.timestamp(&row)?;
+
+        Ok(match &self.fields {
+            CSVFields::Bars {
+                open,
+                high,
+                low,
+                close,
+                volume,
+            } => MarketData::Bar(Bar {
+                timestamp,
+                open: self.number(&row, open)?,
+                high: self.number(&row, high)?,
+                low: self.number(&row, low)?,
+                close: self.number(&row, close)?,
+                volume: self.optional_number(&row, volume)?,
+            }),
+            CSVFields::Ticks { price, size } => MarketData::Tick(Tick {
+                timestamp,
+                price: self.number(&row, price)?,
+                size: self.optional_number(&row, size)?,
+            }),
+            CSVFields::Quotes {
+                bid,
+                ask,
+                bid_size,
+                ask_size,
+            } => MarketData::Quote(Quote {
+                timestamp,
+                bid: self.number(&row, bid)?,
+                bid_size: self.optional_number(&row, bid_size)?,
+                ask: self.number(&row, ask)?,
+                ask_size: self.optional_number(&row, ask_size)?,
+            }),
         })
     }
+
+    fn set_headers(&mut self, headers: &StringRecord) {
+        self.header_indices = headers
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.trim().to_lowercase(), index))
+            .collect();
+    }
+
+    fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    fn has_headers(&self) -> bool {
+        self.has_headers
+    }
+
+    fn settings(&self) -> String {
+        format!("{:?} {:?} {}", self.timestamp, self.fields, self.time_zone)
+    }
 }
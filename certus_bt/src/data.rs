@@ -1,52 +1,206 @@
 use std::collections::HashMap;
 
-use chrono::NaiveDateTime;
-use chrono::prelude::*;
+use chrono::NaiveTime;
+use chrono_tz::Tz;
 
-use certus_core::data::{Bar, MarketData};
+use certus_core::consolidator::{BarConsolidator, QuoteBarAggregator, TickBarAggregator};
+use certus_core::data::{Bar, DataHandlerError, MarketData, Timestamp};
+use certus_core::session::{SessionType, TradingCalendar};
+use certus_core::timeframe::Timeframe;
 
 pub struct HistoricBarConsolidationModel {
-    pub input_minutes: u32,
-    pub output_minutes: u32,
+    pub input: Timeframe,
+    pub output: Timeframe,
+    /// Buckets are aligned to the session open, e.g. 17:00 for CME Globex
+    pub session_open: NaiveTime,
+    /// Time zone of the session open
+    pub time_zone: Tz,
+    /// Bars outside the sessions of the calendar are dropped
+    pub calendar: Option<TradingCalendar>,
+    pub session_type: SessionType,
 }
 
 impl HistoricBarConsolidationModel {
     pub fn new(input_minutes: u32, output_minutes: u32) -> Self {
         assert!(
-            output_minutes % input_minutes == 0,
+            output_minutes.is_multiple_of(input_minutes),
             "output_minutes ({}) must be a multiple of input_minutes ({})",
             output_minutes,
             input_minutes
         );
 
+        Self::from_timeframes(Timeframe::Minutes(input_minutes), Timeframe::Minutes(output_minutes))
+    }
+
+    pub fn from_timeframes(input: Timeframe, output: Timeframe) -> Self {
+        assert!(
+            output.is_multiple_of(&input),
+            "output timeframe ({}) must be a multiple of input timeframe ({})",
+            output,
+            input
+        );
+
         Self {
-            input_minutes,
-            output_minutes,
+            input,
+            output,
+            session_open: NaiveTime::MIN,
+            time_zone: Tz::UTC,
+            calendar: None,
+            session_type: SessionType::Regular,
+        }
+    }
+
+    /// Align buckets to the session open instead of midnight
+    pub fn with_session_open(mut self, session_open: NaiveTime) -> Self {
+        self.session_open = session_open;
+        self
+    }
+
+    /// Time zone in which the session open is given, UTC by default
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Only consolidate bars inside the sessions of the calendar, aligned to the session open
+    pub fn with_calendar(mut self, calendar: TradingCalendar, session_type: SessionType) -> Self {
+        self.session_open = calendar.hours(session_type).open;
+        self.time_zone = calendar.time_zone;
+        self.calendar = Some(calendar);
+        self.session_type = session_type;
+        self
+    }
+
+    /// Description of the settings that change the consolidated data, e.g. to invalidate caches
+    pub fn settings(&self) -> String {
+        let calendar = self.calendar.as_ref().map(|calendar| {
+            let mut holidays: Vec<_> = calendar.holidays.iter().collect();
+            holidays.sort();
+            let mut early_closes: Vec<_> = calendar.early_closes.iter().collect();
+            early_closes.sort();
+            format!(
+                "{} {:?} {:?} {:?} {:?} {:?}",
+                calendar.time_zone,
+                calendar.regular_hours,
+                calendar.extended_hours,
+                calendar.trading_days,
+                holidays,
+                early_closes
+            )
+        });
+        format!(
+            "{:?} {:?} {} {} {:?} {:?}",
+            self.input, self.output, self.session_open, self.time_zone, calendar, self.session_type
+        )
+    }
+
+    fn is_in_session(&self, timestamp: Timestamp) -> bool {
+        self.calendar
+            .as_ref()
+            .is_none_or(|calendar| calendar.is_session_open_at(timestamp, self.session_type))
+    }
+
+    fn bucket_start(&self, timestamp: Timestamp) -> Timestamp {
+        self.output
+            .bucket_start_at(timestamp, self.session_open, &self.time_zone)
+    }
+
+    fn tick_aggregator(&self) -> TickBarAggregator {
+        TickBarAggregator::from_timeframe(self.output)
+            .with_session_open(self.session_open)
+            .with_time_zone(self.time_zone)
+    }
+
+    fn quote_aggregator(&self) -> QuoteBarAggregator {
+        QuoteBarAggregator::from_timeframe(self.output)
+            .with_session_open(self.session_open)
+            .with_time_zone(self.time_zone)
+    }
+
+    /// Streaming consolidation with the same settings, for data that is read row by row
+    pub fn streaming(&self) -> StreamingBarConsolidation {
+        let mut bars = BarConsolidator::from_timeframes(self.input, self.output)
+            .with_session_open(self.session_open)
+            .with_time_zone(self.time_zone);
+        bars.calendar = self.calendar.clone();
+        bars.session_type = self.session_type;
+
+        StreamingBarConsolidation {
+            bars,
+            ticks: self.tick_aggregator(),
+            quotes: self.quote_aggregator(),
+            calendar: self.calendar.clone(),
+            session_type: self.session_type,
+        }
+    }
+
+    /// Aggregate chronologically ordered ticks into bars of the `output` timeframe
+    /// Fails on data other than ticks
+    pub fn aggregate_ticks(&self, data: &[MarketData]) -> Result<Vec<MarketData>, DataHandlerError> {
+        let mut aggregator = self.tick_aggregator();
+        let mut result: Vec<MarketData> = Vec::new();
+
+        for single_data in data.iter() {
+            let tick = match single_data {
+                MarketData::Tick(tick) => tick,
+                other => return Err(mixed_data("ticks", other)),
+            };
+            if !self.is_in_session(tick.timestamp) {
+                continue;
+            }
+            if let Some(bar) = aggregator.update(*single_data) {
+                result.push(MarketData::Bar(bar));
+            }
+        }
+
+        if let Some(bar) = aggregator.flush() {
+            result.push(MarketData::Bar(bar));
         }
+        Ok(result)
     }
 
-    fn bucket_start(&self, dt: NaiveDateTime) -> NaiveDateTime {
-        let minute = dt.minute() as u32;
-        let bucket_minute = (minute / self.output_minutes) * self.output_minutes;
+    /// Aggregate chronologically ordered quotes or quote bars into quote bars of the `output` timeframe
+    /// Fails on data other than quotes and quote bars
+    pub fn aggregate_quotes(&self, data: &[MarketData]) -> Result<Vec<MarketData>, DataHandlerError> {
+        let mut aggregator = self.quote_aggregator();
+        let mut result: Vec<MarketData> = Vec::new();
 
-        dt.with_minute(bucket_minute as u32)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
+        for single_data in data.iter() {
+            if !matches!(single_data, MarketData::Quote(_) | MarketData::QuoteBar(_)) {
+                return Err(mixed_data("quotes", single_data));
+            }
+            if !self.is_in_session(single_data.timestamp()) {
+                continue;
+            }
+            result.extend(aggregator.update(*single_data).map(MarketData::QuoteBar));
+        }
+
+        result.extend(aggregator.flush().map(MarketData::QuoteBar));
+        Ok(result)
     }
 
-    pub fn consolidate_bars(&self, data: &Vec<MarketData>) -> Vec<MarketData> {
-        let mut buckets: HashMap<NaiveDateTime, Vec<Bar>> = HashMap::new();
+    /// Consolidate bars into bars of the `output` timeframe
+    /// Tick data is aggregated into bars and quote data into quote bars of the `output` timeframe instead
+    /// Fails when the data mixes kinds of market data, the kind of the first data is expected throughout
+    pub fn consolidate_bars(&self, data: &[MarketData]) -> Result<Vec<MarketData>, DataHandlerError> {
+        match data.first() {
+            Some(MarketData::Tick(_)) => return self.aggregate_ticks(data),
+            Some(MarketData::Quote(_) | MarketData::QuoteBar(_)) => return self.aggregate_quotes(data),
+            _ => {}
+        }
+
+        let mut buckets: HashMap<Timestamp, Vec<Bar>> = HashMap::new();
 
         for single_data in data.iter() {
             let bar = match single_data {
                 MarketData::Bar(bar) => bar,
-                other => panic!("Expected MarkeData::Bar, got {:?}", other),
+                other => return Err(mixed_data("bars", other)),
             };
+            if !self.is_in_session(bar.timestamp) {
+                continue;
+            }
 
-            let bucket_start = self.bucket_start(bar.date);
+            let bucket_start = self.bucket_start(bar.timestamp);
             buckets.entry(bucket_start).or_default().push(*bar);
         }
 
@@ -54,7 +208,7 @@ impl HistoricBarConsolidationModel {
 
         for (bucket_start, mut bars) in buckets {
             // Sort bars inside the bucket
-            bars.sort_by_key(|b| b.date);
+            bars.sort_by_key(|b| b.timestamp);
 
             let open = bars.first().unwrap().open;
             let close = bars.last().unwrap().close;
@@ -64,7 +218,7 @@ impl HistoricBarConsolidationModel {
             let volume = bars.iter().map(|b| b.volume).sum();
 
             result.push(MarketData::Bar(Bar {
-                date: bucket_start,
+                timestamp: bucket_start,
                 open,
                 high,
                 low,
@@ -74,9 +228,66 @@ impl HistoricBarConsolidationModel {
         }
 
         result.sort_by_key(|data| match data {
-            MarketData::Bar(bar) => bar.date,
+            MarketData::Bar(bar) => bar.timestamp,
             _ => unreachable!("Expected only MarketData::Bar"),
         });
-        result
+        Ok(result)
+    }
+}
+
+fn mixed_data(expected: &str, market_data: &MarketData) -> DataHandlerError {
+    let found = match market_data {
+        MarketData::Tick(_) => "a tick",
+        MarketData::Bar(_) => "a bar",
+        MarketData::Quote(_) => "a quote",
+        MarketData::QuoteBar(_) => "a quote bar",
+    };
+    DataHandlerError::MixedData {
+        timestamp: market_data.timestamp(),
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+/// Streaming counterpart of `HistoricBarConsolidationModel::consolidate_bars`
+/// Only completed bars are returned, input must be chronologically ordered
+pub struct StreamingBarConsolidation {
+    bars: BarConsolidator,
+    ticks: TickBarAggregator,
+    quotes: QuoteBarAggregator,
+    calendar: Option<TradingCalendar>,
+    session_type: SessionType,
+}
+
+impl StreamingBarConsolidation {
+    /// Feed new market data, returns the bars completed by it
+    /// Quotes and quote bars complete quote bars, all other data bars
+    pub fn update(&mut self, market_data: MarketData) -> Vec<MarketData> {
+        if let MarketData::Bar(_) = market_data {
+            return self.bars.update(market_data).into_iter().map(MarketData::Bar).collect();
+        }
+
+        let in_session = self
+            .calendar
+            .as_ref()
+            .is_none_or(|calendar| calendar.is_session_open_at(market_data.timestamp(), self.session_type));
+        if !in_session {
+            return Vec::new();
+        }
+        match market_data {
+            MarketData::Tick(_) => self.ticks.update(market_data).map(MarketData::Bar).into_iter().collect(),
+            _ => self.quotes.update(market_data).map(MarketData::QuoteBar).into_iter().collect(),
+        }
+    }
+
+    /// Emit the partially formed bars at the end of the data
+    pub fn flush(&mut self) -> Vec<MarketData> {
+        self.bars
+            .flush()
+            .into_iter()
+            .chain(self.ticks.flush())
+            .map(MarketData::Bar)
+            .chain(self.quotes.flush().map(MarketData::QuoteBar))
+            .collect()
     }
 }
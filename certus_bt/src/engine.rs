@@ -1,8 +1,7 @@
-use certus_core::broker::Broker;
 use certus_core::core::Order;
-use certus_core::data::DataHandler;
+use certus_core::data::{DataHandler, DataHandlerError};
 use certus_core::engine::{Engine, ExecutionEngine};
-use certus_core::strategy::Strategy;
+use certus_core::strategy::{Strategy, dispatch_market_data};
 
 use log;
 
@@ -18,36 +17,26 @@ pub struct BacktestingEngine {
 impl Engine for BacktestingEngine {
     fn init(&mut self) {
         log::debug!("Initializing strategies");
-        let mut strategy_index: usize = 1;
-        for strategy in self.strategies.iter_mut() {
-            strategy.init(strategy_index);
-            strategy_index += 1;
+        for (index, strategy) in self.strategies.iter_mut().enumerate() {
+            strategy.init(index + 1);
         }
     }
 
-    fn run(&mut self) {
+    fn run(&mut self) -> Result<(), DataHandlerError> {
         log::debug!("Start running");
 
-        let _ = self.data_handler.start();
+        self.data_handler.start()?;
         let mut data_feed = self.data_handler.get_data_feed();
 
         log::debug!("Start polling data feed");
-        while let Some(market_data) = data_feed.poll() {
+        while let Some(market_data) = data_feed.poll()? {
             log::debug!("{}", market_data);
 
             log::debug!("Simulating order fills");
             self.broker.simulate_fills(market_data);
 
-            let broker_ref: &mut dyn Broker = &mut self.broker;
-            log::debug!("Calling update() on strategies");
-            for strategy in self.strategies.iter_mut() {
-                strategy.update(market_data, broker_ref);
-            }
-
-            log::debug!("Calling next() on strategies");
-            for strategy in self.strategies.iter_mut() {
-                strategy.next(market_data, broker_ref);
-            }
+            log::debug!("Calling the strategies");
+            dispatch_market_data(&mut self.strategies, market_data, &mut self.broker);
             // if let Some(orders) = strategy.next(market_data) {
             //     if let Some(fills) = self.execution_engine.execute(orders) {
             //         strategy.on_fill(fill);
@@ -55,6 +44,7 @@ impl Engine for BacktestingEngine {
             // }
         }
         log::debug!("Finished data feed");
+        Ok(())
     }
 }
 
@@ -0,0 +1,55 @@
+/// enum defining how the price impact grows with the size of a fill relative to the average volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImpactModel {
+    /// Impact proportional to the participation
+    Linear,
+    /// Impact proportional to the square root of the participation, the usual empirical law
+    SquareRoot,
+}
+
+/// Price impact of fills, as a fraction of the price: coefficient * volatility * f(size / average volume)
+/// The temporary impact only moves the price of the fill itself, the permanent impact moves the prices
+/// of all later fills
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketImpact {
+    pub model: ImpactModel,
+    /// Volatility of returns over the period of the average volume, e.g. the daily volatility
+    pub volatility: f64,
+    /// Average volume over the same period, e.g. the average daily volume
+    pub average_volume: f64,
+    pub temporary: f64,
+    pub permanent: f64,
+}
+
+impl MarketImpact {
+    /// Temporary impact with a coefficient of 1, without permanent impact
+    pub fn new(model: ImpactModel, volatility: f64, average_volume: f64) -> Self {
+        Self {
+            model,
+            volatility,
+            average_volume,
+            temporary: 1.0,
+            permanent: 0.0,
+        }
+    }
+
+    pub fn with_coefficients(mut self, temporary: f64, permanent: f64) -> Self {
+        self.temporary = temporary;
+        self.permanent = permanent;
+        self
+    }
+
+    /// Temporary and permanent impact of a fill of `size` as a fraction of the price
+    pub fn impact(&self, size: f64) -> (f64, f64) {
+        if self.average_volume <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let participation = size.abs() / self.average_volume;
+        let shape = match self.model {
+            ImpactModel::Linear => participation,
+            ImpactModel::SquareRoot => participation.sqrt(),
+        };
+        let impact = self.volatility * shape;
+        (self.temporary * impact, self.permanent * impact)
+    }
+}
@@ -1,4 +1,13 @@
+pub mod bars;
+pub mod book_simulator;
 pub mod broker;
+pub mod cache;
+pub mod cleaning;
+#[cfg(feature = "parquet")]
+pub mod columnar_data_handler;
+pub mod continuous;
+pub mod corporate_actions;
 pub mod csv_data_handler;
 pub mod data;
 pub mod engine;
+pub mod impact;
@@ -0,0 +1,126 @@
+use certus_bt::broker::{BacktestingBroker, VolumeLimits};
+use certus_core::broker::Broker;
+use certus_core::core::{Instrument, InstrumentType, Order, OrderSide, OrderType};
+use certus_core::data::{MarketData, Timestamp};
+use certus_core::session::TradingCalendar;
+use chrono::NaiveDate;
+
+mod common;
+use common::{bar_at, order};
+
+/// Hourly bar starting at a New York time
+fn new_york_bar(day: u32, hour: u32, minute: u32, open: f64, close: f64) -> MarketData {
+    let dt = NaiveDate::from_ymd_opt(2024, 1, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap();
+    bar_between(Timestamp::from_local(dt, &chrono_tz::America::New_York), open, close)
+}
+
+/// Bar trading one point beyond its open and close
+fn bar_between(timestamp: Timestamp, open: f64, close: f64) -> MarketData {
+    bar_at(timestamp, open, open.max(close) + 1.0, open.min(close) - 1.0, close)
+}
+
+/// Buy of one unit
+fn buy(order_type: OrderType) -> Order {
+    order(OrderSide::Buy, order_type, 1.0)
+}
+
+fn entry_price(broker: &BacktestingBroker, order_id: usize) -> Option<f64> {
+    broker.get_trade_for_order(order_id).map(|trade| trade.entry_price)
+}
+
+#[test]
+fn auction_orders_without_calendar_fill_at_the_next_bar() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(0), 100.0, 101.0));
+    let on_open = broker.place_order(buy(OrderType::MarketOnOpen)).id.unwrap();
+    let on_close = broker.place_order(buy(OrderType::MarketOnClose)).id.unwrap();
+
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(86_400), 102.0, 104.0));
+    assert_eq!(entry_price(&broker, on_open), Some(102.0));
+    assert_eq!(entry_price(&broker, on_close), Some(104.0));
+}
+
+#[test]
+fn signal_close_fills_at_the_close_of_the_signal_bar() {
+    let mut broker = BacktestingBroker::new(100_000.0).with_fill_on_signal_close(true);
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(0), 100.0, 101.0));
+    let market = broker.place_order(buy(OrderType::Market)).id.unwrap();
+    let on_close = broker.place_order(buy(OrderType::MarketOnClose)).id.unwrap();
+    let on_open = broker.place_order(buy(OrderType::MarketOnOpen)).id.unwrap();
+
+    assert_eq!(entry_price(&broker, market), Some(101.0));
+    assert_eq!(entry_price(&broker, on_close), Some(101.0));
+    assert_eq!(broker.fill_report(market).unwrap().first_fill, Some(Timestamp::from_seconds(0)));
+    assert_eq!(entry_price(&broker, on_open), None);
+
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(86_400), 102.0, 104.0));
+    assert_eq!(entry_price(&broker, on_open), Some(102.0));
+}
+
+#[test]
+fn auction_orders_follow_the_sessions_of_the_calendar() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    let instrument = Instrument::new(String::from("SPY"), None, InstrumentType::Stock)
+        .with_calendar(TradingCalendar::us_equities());
+    broker.add_instrument(instrument);
+
+    broker.simulate_fills(new_york_bar(2, 14, 0, 100.0, 100.5));
+    let on_close = broker.place_order(buy(OrderType::MarketOnClose)).id.unwrap();
+    let on_open = broker.place_order(buy(OrderType::MarketOnOpen)).id.unwrap();
+
+    // The last bar of the session
+    broker.simulate_fills(new_york_bar(2, 15, 0, 100.5, 101.5));
+    assert_eq!(entry_price(&broker, on_close), None);
+    assert_eq!(entry_price(&broker, on_open), None);
+
+    broker.simulate_fills(new_york_bar(3, 9, 30, 103.0, 102.0));
+    assert_eq!(entry_price(&broker, on_close), Some(101.5));
+    assert_eq!(entry_price(&broker, on_open), Some(103.0));
+
+    // Placed inside the session, waits for its close
+    let next_close = broker.place_order(buy(OrderType::MarketOnClose)).id.unwrap();
+    broker.simulate_fills(new_york_bar(3, 10, 30, 102.0, 104.0));
+    assert_eq!(entry_price(&broker, next_close), None);
+    broker.simulate_fills(new_york_bar(4, 9, 30, 105.0, 105.0));
+    assert_eq!(entry_price(&broker, next_close), Some(104.0));
+}
+
+#[test]
+fn auctions_are_not_limited_by_the_volume_limits() {
+    let limits = VolumeLimits {
+        max_participation: 0.001,
+        min_volume: 0.0,
+    };
+    let mut broker = BacktestingBroker::new(100_000.0).with_volume_limits(limits);
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(0), 100.0, 101.0));
+    let large = |order_type| Order {
+        size: 10.0,
+        ..buy(order_type)
+    };
+    let on_open = broker.place_order(large(OrderType::MarketOnOpen)).id.unwrap();
+    let market = broker.place_order(large(OrderType::Market)).id.unwrap();
+
+    broker.simulate_fills(bar_between(Timestamp::from_seconds(86_400), 102.0, 104.0));
+    assert_eq!(broker.fill_report(on_open).unwrap().remaining, 0.0);
+    assert_eq!(broker.fill_report(market).unwrap().filled, 1.0);
+}
+
+#[test]
+fn calendar_auctions_fill_at_their_session_across_overnight_gaps() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    let instrument = Instrument::new(String::from("SPY"), None, InstrumentType::Stock)
+        .with_calendar(TradingCalendar::us_equities());
+    broker.add_instrument(instrument);
+
+    broker.simulate_fills(new_york_bar(2, 15, 0, 100.0, 100.5));
+    let on_close = broker.place_order(buy(OrderType::MarketOnClose)).id.unwrap();
+    let on_open = broker.place_order(buy(OrderType::MarketOnOpen)).id.unwrap();
+
+    // Gaps down overnight, the close fills at the prior close and the open at the gapped open
+    broker.simulate_fills(new_york_bar(3, 9, 30, 90.0, 91.0));
+    assert_eq!(entry_price(&broker, on_close), Some(100.5));
+    assert_eq!(entry_price(&broker, on_open), Some(90.0));
+}
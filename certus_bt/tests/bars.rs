@@ -0,0 +1,146 @@
+use certus_bt::bars::{ActivityBarBuilder, BarBuilder, HeikinAshiBuilder, RangeBarBuilder, RenkoBuilder};
+use certus_core::data::{Bar, MarketData, Tick, Timestamp};
+use chrono::{NaiveDate, NaiveDateTime};
+
+fn at(second: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 1, 2)
+        .unwrap()
+        .and_hms_opt(9, 0, second)
+        .unwrap()
+}
+
+fn create_tick(second: u32, price: f64, size: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_naive_utc(at(second)),
+        price,
+        size,
+    })
+}
+
+fn create_bar(open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_naive_utc(at(0)),
+        open,
+        high,
+        low,
+        close,
+        volume: 10.0,
+    })
+}
+
+fn bars(data: Vec<MarketData>) -> Vec<Bar> {
+    data.into_iter()
+        .map(|d| match d {
+            MarketData::Bar(bar) => bar,
+            other => panic!("expected bar, got {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn tick_bars_contain_n_ticks() {
+    let data: Vec<MarketData> = (0..7).map(|i| create_tick(i, 100.0 + i as f64, 1.0)).collect();
+    let result = bars(ActivityBarBuilder::ticks(3).build(&data));
+
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[0].timestamp.naive_utc(), at(0));
+    assert_eq!(result[0].open, 100.0);
+    assert_eq!(result[0].close, 102.0);
+    assert_eq!(result[0].volume, 3.0);
+    assert_eq!(result[1].open, 103.0);
+    assert_eq!(result[1].timestamp.naive_utc(), at(3));
+    // Last bar is the partial one
+    assert_eq!(result[2].volume, 1.0);
+}
+
+#[test]
+fn volume_bars_complete_once_threshold_is_reached() {
+    let data = vec![
+        create_tick(0, 100.0, 4.0),
+        create_tick(1, 101.0, 4.0),
+        create_tick(2, 99.0, 4.0),
+        create_tick(3, 98.0, 5.0),
+        create_tick(4, 97.0, 5.0),
+    ];
+    let result = bars(ActivityBarBuilder::volume(10.0).build(&data));
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].volume, 12.0);
+    assert_eq!(result[0].high, 101.0);
+    assert_eq!(result[0].low, 99.0);
+    assert_eq!(result[1].volume, 10.0);
+    assert_eq!(result[1].close, 97.0);
+}
+
+#[test]
+fn dollar_bars_use_traded_value() {
+    let data = vec![
+        create_tick(0, 100.0, 5.0),
+        create_tick(1, 100.0, 4.0),
+        create_tick(2, 100.0, 1.0),
+        create_tick(3, 50.0, 10.0),
+    ];
+    let mut builder = ActivityBarBuilder::dollar(1_000.0);
+    let result = bars(builder.build(&data));
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].volume, 10.0);
+    assert_eq!(result[1].volume, 10.0);
+    assert_eq!(result[1].close, 50.0);
+}
+
+#[test]
+fn range_bars_never_exceed_range() {
+    let data = vec![
+        create_tick(0, 100.0, 1.0),
+        create_tick(1, 101.0, 1.0),
+        create_tick(2, 103.0, 1.0),
+        create_tick(3, 102.0, 1.0),
+        create_tick(4, 101.0, 1.0),
+    ];
+    let result = bars(RangeBarBuilder::new(2.0).build(&data));
+
+    assert_eq!(result.len(), 2);
+    assert_eq!((result[0].open, result[0].close), (100.0, 101.0));
+    assert_eq!((result[1].open, result[1].close), (103.0, 101.0));
+    assert_eq!((result[1].high, result[1].low), (103.0, 101.0));
+}
+
+#[test]
+fn renko_bricks_follow_price_moves() {
+    let mut builder = RenkoBuilder::new(1.0);
+    assert!(builder.update(create_tick(0, 100.0, 1.0)).is_empty());
+    assert!(builder.update(create_tick(1, 100.5, 1.0)).is_empty());
+
+    let up = builder.update(create_tick(2, 102.2, 1.0));
+    assert_eq!(up.len(), 2);
+    assert_eq!((up[0].open, up[0].close), (100.0, 101.0));
+    assert_eq!((up[1].open, up[1].close), (101.0, 102.0));
+    assert_eq!(up[0].volume, 3.0);
+    assert_eq!(up[1].volume, 0.0);
+
+    // Price needs to fall below the bottom of the last brick by a full brick
+    assert!(builder.update(create_tick(3, 100.5, 1.0)).is_empty());
+    let down = builder.update(create_tick(4, 99.9, 1.0));
+    assert_eq!(down.len(), 1);
+    assert_eq!((down[0].open, down[0].close), (101.0, 100.0));
+    assert_eq!((down[0].high, down[0].low), (101.0, 100.0));
+    assert!(builder.flush().is_none());
+}
+
+#[test]
+fn heikin_ashi_smooths_bars() {
+    let mut builder = HeikinAshiBuilder::new();
+    let first = builder.update(create_bar(10.0, 14.0, 8.0, 12.0));
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].open, 11.0);
+    assert_eq!(first[0].close, 11.0);
+    assert_eq!(first[0].high, 14.0);
+    assert_eq!(first[0].low, 8.0);
+
+    let second = builder.update(create_bar(12.0, 13.0, 11.0, 13.0));
+    assert_eq!(second[0].open, 11.0);
+    assert_eq!(second[0].close, 12.25);
+    assert_eq!(second[0].high, 13.0);
+    assert_eq!(second[0].low, 11.0);
+}
@@ -0,0 +1,144 @@
+use certus_bt::book_simulator::OrderBookFillSimulator;
+use certus_core::core::{OrderSide, OrderType};
+use certus_core::data::{BookDelta, BookLevel, BookSide, BookSnapshot, OrderBookUpdate, Tick, Timestamp};
+
+mod common;
+use common::order;
+
+fn level(price: f64, size: f64) -> BookLevel {
+    BookLevel { price, size }
+}
+
+fn simulator() -> OrderBookFillSimulator {
+    let mut simulator = OrderBookFillSimulator::new();
+    simulator.on_book_update(&OrderBookUpdate::Snapshot(BookSnapshot {
+        timestamp: Timestamp::from_seconds(0),
+        bids: vec![level(99.5, 10.0), level(99.0, 20.0)],
+        asks: vec![level(100.0, 3.0), level(100.5, 4.0), level(101.0, 5.0)],
+    }));
+    simulator
+}
+
+fn delta(side: BookSide, price: f64, size: f64) -> OrderBookUpdate {
+    OrderBookUpdate::Delta(BookDelta {
+        timestamp: Timestamp::from_seconds(1),
+        side,
+        price,
+        size,
+    })
+}
+
+fn trade(price: f64, size: f64) -> Tick {
+    Tick {
+        timestamp: Timestamp::from_seconds(1),
+        price,
+        size,
+    }
+}
+
+#[test]
+fn market_orders_walk_the_book() {
+    let mut simulator = simulator();
+    simulator.submit(order(OrderSide::Buy, OrderType::Market, 5.0));
+
+    let fills: Vec<(f64, f64)> = simulator.fills().iter().map(|fill| (fill.size, fill.price)).collect();
+    assert_eq!(fills, vec![(3.0, 100.0), (2.0, 100.5)]);
+    // The consumed liquidity is gone until the feed updates the level
+    assert_eq!(simulator.book().best_ask(), Some(level(100.5, 2.0)));
+
+    // Without enough liquidity the rest waits for the next update
+    let order_id = simulator.submit(order(OrderSide::Buy, OrderType::Market, 10.0));
+    assert_eq!(simulator.open_orders().next().unwrap().size, 3.0);
+    simulator.on_book_update(&delta(BookSide::Ask, 102.0, 5.0));
+    assert_eq!(simulator.fills().last().unwrap().order_id, order_id);
+    assert_eq!(simulator.fills().last().unwrap().price, 102.0);
+    assert_eq!(simulator.open_orders().count(), 0);
+}
+
+#[test]
+fn marketable_limit_orders_take_up_to_the_limit_and_rest() {
+    let mut simulator = simulator();
+    let order_id = simulator.submit(order(OrderSide::Buy, OrderType::Limit(100.5), 10.0));
+
+    assert_eq!(simulator.fills().len(), 2);
+    assert_eq!(simulator.open_orders().next().unwrap().size, 3.0);
+    // Nobody is ahead at the new best bid
+    assert_eq!(simulator.queue_ahead(order_id), Some(0.0));
+}
+
+#[test]
+fn resting_limit_orders_wait_for_the_queue_ahead() {
+    let mut simulator = simulator();
+    let order_id = simulator.submit(order(OrderSide::Buy, OrderType::Limit(99.5), 4.0));
+    assert_eq!(simulator.queue_ahead(order_id), Some(10.0));
+
+    simulator.on_trade(&trade(99.5, 6.0));
+    assert_eq!(simulator.queue_ahead(order_id), Some(4.0));
+    assert!(simulator.fills().is_empty());
+
+    // Cancels shrink the level below the queue ahead
+    simulator.on_book_update(&delta(BookSide::Bid, 99.5, 7.0));
+    simulator.on_book_update(&delta(BookSide::Bid, 99.5, 3.0));
+    assert_eq!(simulator.queue_ahead(order_id), Some(3.0));
+
+    simulator.on_trade(&trade(99.5, 5.0));
+    assert_eq!(simulator.fills()[0].size, 2.0);
+    assert_eq!(simulator.fills()[0].price, 99.5);
+
+    // A trade below the limit fills the rest
+    simulator.on_trade(&trade(99.25, 1.0));
+    assert_eq!(simulator.fills()[1].size, 2.0);
+    assert_eq!(simulator.open_orders().count(), 0);
+}
+
+#[test]
+fn resting_limit_orders_fill_when_the_book_crosses() {
+    let mut simulator = simulator();
+    simulator.submit(order(OrderSide::Sell, OrderType::Limit(100.5), 2.0));
+
+    // Only the size on the crossing levels fills, the rest keeps resting
+    simulator.on_book_update(&delta(BookSide::Bid, 100.5, 1.0));
+    assert_eq!(simulator.fills()[0].size, 1.0);
+    assert_eq!(simulator.fills()[0].price, 100.5);
+    assert_eq!(simulator.open_orders().next().unwrap().size, 1.0);
+    assert_eq!(simulator.book().best_bid(), Some(level(99.5, 10.0)));
+
+    // A higher bid fills the rest at the limit
+    simulator.on_book_update(&delta(BookSide::Bid, 101.0, 5.0));
+    let fills: Vec<(f64, f64)> = simulator.fills().iter().map(|fill| (fill.size, fill.price)).collect();
+    assert_eq!(fills, vec![(1.0, 100.5), (1.0, 100.5)]);
+    assert_eq!(simulator.book().best_bid(), Some(level(101.0, 4.0)));
+    assert_eq!(simulator.open_orders().count(), 0);
+}
+
+#[test]
+fn duplicate_order_ids_are_remapped() {
+    let mut simulator = simulator();
+    let mut first = order(OrderSide::Buy, OrderType::Limit(99.0), 1.0);
+    first.id = Some(2);
+    assert_eq!(simulator.submit(first.clone()), 2);
+    // A reused id gets a new one, as do orders without an id
+    assert_eq!(simulator.submit(first), 3);
+    assert_eq!(simulator.submit(order(OrderSide::Buy, OrderType::Limit(99.0), 1.0)), 4);
+
+    let ids: Vec<Option<usize>> = simulator.open_orders().map(|order| order.id).collect();
+    assert_eq!(ids, vec![Some(2), Some(3), Some(4)]);
+}
+
+#[test]
+fn stops_trigger_on_trades_and_cancel_removes_orders() {
+    let mut simulator = simulator();
+    simulator.submit(order(OrderSide::Sell, OrderType::Stop(99.0), 12.0));
+    let stop_limit = simulator.submit(order(OrderSide::Buy, OrderType::StopLimit(100.5, 100.5), 1.0));
+
+    simulator.on_trade(&trade(99.5, 1.0));
+    assert!(simulator.fills().is_empty());
+
+    simulator.on_trade(&trade(99.0, 1.0));
+    let fills: Vec<(f64, f64)> = simulator.fills().iter().map(|fill| (fill.size, fill.price)).collect();
+    assert_eq!(fills, vec![(10.0, 99.5), (2.0, 99.0)]);
+
+    assert!(simulator.cancel(stop_limit));
+    assert!(!simulator.cancel(stop_limit));
+    assert_eq!(simulator.open_orders().count(), 0);
+}
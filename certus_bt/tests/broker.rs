@@ -1,7 +1,11 @@
-use certus_bt::broker::BacktestingBroker;
+use certus_bt::broker::{BacktestingBroker, IntrabarPath, LimitFillModel, VolumeLimits};
 use certus_core::broker::Broker;
 use certus_core::core::{Order, OrderSide, OrderType};
-use certus_core::data::{MarketData, Tick};
+use certus_core::data::{Bar, MarketData, Quote, QuoteBar, Timestamp};
+use chrono::Duration;
+
+mod common;
+use common::{sized_tick, tick};
 
 fn make_market_order(side: OrderSide, size: f64, related_id: Option<usize>) -> Order {
     Order {
@@ -67,11 +71,13 @@ fn make_stop_limit_order(
     }
 }
 
-fn make_tick(price: f64, size: f64) -> MarketData {
-    MarketData::Tick(Tick {
-        timestamp: 0,
-        price,
-        size,
+fn make_quote(bid: f64, ask: f64, size: f64) -> MarketData {
+    MarketData::Quote(Quote {
+        timestamp: Timestamp::from_nanos(0),
+        bid,
+        bid_size: size,
+        ask,
+        ask_size: size,
     })
 }
 
@@ -83,7 +89,7 @@ fn simulate_fills_creates_trade_with_fill_details() {
         .id
         .unwrap();
 
-    broker.simulate_fills(make_tick(123.45, 10.0));
+    broker.simulate_fills(tick(0, 123.45));
 
     assert_eq!(broker.unfilled_orders_len(), 0);
     let trade = broker
@@ -116,7 +122,7 @@ fn simulate_multiple_fills_updates_average_entry_price() {
         .id
         .unwrap();
 
-    broker.simulate_fills(make_tick(100.0, 6.0));
+    broker.simulate_fills(sized_tick(0, 100.0, 6.0));
 
     let trade = broker
         .get_trade_for_order(order_id)
@@ -130,7 +136,7 @@ fn simulate_multiple_fills_updates_average_entry_price() {
     assert_eq!(trade.size, 6.0);
     assert_eq!(broker.unfilled_orders_len(), 1);
 
-    broker.simulate_fills(make_tick(110.0, 4.0));
+    broker.simulate_fills(sized_tick(0, 110.0, 4.0));
 
     let trade = broker
         .get_trade_for_order(order_id)
@@ -168,7 +174,7 @@ fn related_orders_scale_and_close_trade() {
         .place_order(make_market_order(OrderSide::Buy, 5.0, None))
         .id
         .unwrap();
-    broker.simulate_fills(make_tick(100.0, 5.0));
+    broker.simulate_fills(sized_tick(0, 100.0, 5.0));
 
     let trade = broker
         .get_trade_for_order(entry_order_id)
@@ -189,7 +195,7 @@ fn related_orders_scale_and_close_trade() {
         ))
         .id
         .unwrap();
-    broker.simulate_fills(make_tick(105.0, 3.0));
+    broker.simulate_fills(sized_tick(0, 105.0, 3.0));
 
     let trade = broker
         .get_trade_for_order(add_order_id)
@@ -212,7 +218,7 @@ fn related_orders_scale_and_close_trade() {
         ))
         .id
         .unwrap();
-    broker.simulate_fills(make_tick(110.0, 3.0));
+    broker.simulate_fills(sized_tick(0, 110.0, 3.0));
 
     let trade = broker
         .get_trade_for_order(reduce_order_id)
@@ -234,7 +240,7 @@ fn related_orders_scale_and_close_trade() {
         ))
         .id
         .unwrap();
-    broker.simulate_fills(make_tick(115.0, 5.0));
+    broker.simulate_fills(sized_tick(0, 115.0, 5.0));
 
     let trade = broker
         .get_trade_for_order(close_order_id)
@@ -253,7 +259,7 @@ fn market_orders_fill_on_first_available_tick() {
 
     assert_eq!(broker.unfilled_orders_len(), 1);
 
-    broker.simulate_fills(make_tick(250.5, 2.0));
+    broker.simulate_fills(sized_tick(0, 250.5, 2.0));
 
     assert_eq!(broker.unfilled_orders_len(), 0);
     let trade = broker
@@ -274,14 +280,14 @@ fn limit_orders_wait_for_price_to_meet_limit() {
     let order = make_limit_order(OrderSide::Buy, 5.0, limit_price, None);
     let order_id = broker.place_order(order).id.unwrap();
 
-    broker.simulate_fills(make_tick(101.0, 5.0));
+    broker.simulate_fills(sized_tick(0, 101.0, 5.0));
     assert!(
         broker.get_trade_for_order(order_id).is_none(),
         "limit order should not fill above limit price"
     );
     assert_eq!(broker.unfilled_orders_len(), 1);
 
-    broker.simulate_fills(make_tick(98.5, 5.0));
+    broker.simulate_fills(sized_tick(0, 98.5, 5.0));
     assert_eq!(broker.unfilled_orders_len(), 0);
 
     let trade = broker
@@ -313,13 +319,13 @@ fn stop_orders_trigger_after_threshold_is_crossed() {
     let order = make_stop_order(OrderSide::Buy, 4.0, stop_price, None);
     let order_id = broker.place_order(order).id.unwrap();
 
-    broker.simulate_fills(make_tick(100.25, 4.0));
+    broker.simulate_fills(sized_tick(0, 100.25, 4.0));
     assert!(
         broker.get_trade_for_order(order_id).is_none(),
         "stop order should not fill before stop price is reached"
     );
 
-    broker.simulate_fills(make_tick(101.75, 4.0));
+    broker.simulate_fills(sized_tick(0, 101.75, 4.0));
     assert_eq!(broker.unfilled_orders_len(), 0);
 
     let trade = broker
@@ -342,19 +348,19 @@ fn stop_limit_orders_trigger_then_respect_limit_price() {
     let order = make_stop_limit_order(OrderSide::Buy, 3.0, stop_price, limit_price, None);
     let order_id = broker.place_order(order).id.unwrap();
 
-    broker.simulate_fills(make_tick(100.5, 3.0));
+    broker.simulate_fills(sized_tick(0, 100.5, 3.0));
     assert!(
         broker.get_trade_for_order(order_id).is_none(),
         "stop-limit order should not fill before stop is triggered"
     );
 
-    broker.simulate_fills(make_tick(102.0, 3.0));
+    broker.simulate_fills(sized_tick(0, 102.0, 3.0));
     assert!(
         broker.get_trade_for_order(order_id).is_none(),
         "stop-limit order should trigger but remain pending while price is beyond limit"
     );
 
-    broker.simulate_fills(make_tick(101.25, 3.0));
+    broker.simulate_fills(sized_tick(0, 101.25, 3.0));
     assert_eq!(broker.unfilled_orders_len(), 0);
 
     let trade = broker
@@ -374,3 +380,351 @@ fn stop_limit_orders_trigger_then_respect_limit_price() {
     );
     assert_eq!(trade.size, 3.0);
 }
+
+#[test]
+fn market_orders_cross_the_spread() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let buy_id = broker.place_order(make_market_order(OrderSide::Buy, 2.0, None)).id.unwrap();
+    let sell_id = broker
+        .place_order(Order {
+            strategy_id: 2,
+            ..make_market_order(OrderSide::Sell, 2.0, None)
+        })
+        .id
+        .unwrap();
+
+    broker.simulate_fills(make_quote(99.9, 100.1, 5.0));
+
+    assert_eq!(broker.get_trade_for_order(buy_id).unwrap().entry_price, 100.1);
+    assert_eq!(broker.get_trade_for_order(sell_id).unwrap().entry_price, 99.9);
+}
+
+#[test]
+fn quote_sizes_limit_fills_per_side() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let buy_id = broker.place_order(make_market_order(OrderSide::Buy, 5.0, None)).id.unwrap();
+    let sell_id = broker
+        .place_order(Order {
+            strategy_id: 2,
+            ..make_market_order(OrderSide::Sell, 5.0, None)
+        })
+        .id
+        .unwrap();
+
+    broker.simulate_fills(MarketData::Quote(Quote {
+        timestamp: Timestamp::from_nanos(0),
+        bid: 99.0,
+        bid_size: 5.0,
+        ask: 101.0,
+        ask_size: 3.0,
+    }));
+
+    assert_eq!(broker.get_trade_for_order(buy_id).unwrap().size, 3.0);
+    assert_eq!(broker.get_trade_for_order(sell_id).unwrap().size, -5.0);
+    assert_eq!(broker.unfilled_orders_len(), 1);
+}
+
+#[test]
+fn passive_limit_orders_fill_when_the_opposite_side_trades_through() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let order_id = broker.place_order(make_limit_order(OrderSide::Buy, 1.0, 100.0, None)).id.unwrap();
+
+    // The bid touching the limit doesn't fill a buy, neither does the ask touching it
+    broker.simulate_fills(make_quote(100.0, 100.2, 5.0));
+    broker.simulate_fills(make_quote(99.8, 100.0, 5.0));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+
+    broker.simulate_fills(make_quote(99.7, 99.9, 5.0));
+    let trade = broker.get_trade_for_order(order_id).unwrap();
+    assert_eq!(trade.entry_price, 100.0);
+    assert_eq!(broker.unfilled_orders_len(), 0);
+}
+
+#[test]
+fn marketable_limit_orders_fill_at_the_quote() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let buy_id = broker.place_order(make_limit_order(OrderSide::Buy, 1.0, 100.5, None)).id.unwrap();
+    let sell_id = broker
+        .place_order(Order {
+            strategy_id: 2,
+            ..make_limit_order(OrderSide::Sell, 1.0, 99.5, None)
+        })
+        .id
+        .unwrap();
+
+    broker.simulate_fills(make_quote(99.9, 100.1, 5.0));
+
+    assert_eq!(broker.get_trade_for_order(buy_id).unwrap().entry_price, 100.1);
+    assert_eq!(broker.get_trade_for_order(sell_id).unwrap().entry_price, 99.9);
+}
+
+#[test]
+fn stop_orders_trigger_on_the_opposite_side_of_quote_bars() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let buy_id = broker.place_order(make_stop_order(OrderSide::Buy, 1.0, 101.0, None)).id.unwrap();
+    let sell_id = broker
+        .place_order(Order {
+            strategy_id: 2,
+            ..make_limit_order(OrderSide::Sell, 1.0, 101.0, None)
+        })
+        .id
+        .unwrap();
+
+    let quote_bar = QuoteBar {
+        timestamp: Timestamp::from_nanos(0),
+        bid_open: 100.0,
+        bid_high: 101.0,
+        bid_low: 99.5,
+        bid_close: 100.5,
+        bid_size: 5.0,
+        ask_open: 100.2,
+        ask_high: 101.2,
+        ask_low: 99.7,
+        ask_close: 100.7,
+        ask_size: 5.0,
+    };
+    broker.simulate_fills(MarketData::QuoteBar(quote_bar));
+
+    // The ask reaches the stop, the bid only touches the sell limit
+    assert_eq!(broker.get_trade_for_order(buy_id).unwrap().entry_price, 101.0);
+    assert!(broker.get_trade_for_order(sell_id).is_none());
+
+    broker.simulate_fills(MarketData::QuoteBar(QuoteBar {
+        bid_high: 101.1,
+        ..quote_bar
+    }));
+    assert_eq!(broker.get_trade_for_order(sell_id).unwrap().entry_price, 101.0);
+}
+
+#[test]
+fn trade_through_model_ignores_touches() {
+    let mut broker = BacktestingBroker::new(10_000.0).with_limit_fill_model(LimitFillModel::TradeThrough);
+    let order_id = broker.place_order(make_limit_order(OrderSide::Buy, 1.0, 99.0, None)).id.unwrap();
+
+    broker.simulate_fills(tick(0, 99.0));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+
+    broker.simulate_fills(sized_tick(0, 98.75, 1.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().entry_price, 99.0);
+}
+
+#[test]
+fn touch_probability_model_is_reproducible() {
+    let touches_until_fill = |seed: u64| {
+        let model = LimitFillModel::TouchProbability { probability: 0.3, seed };
+        let mut broker = BacktestingBroker::new(10_000.0).with_limit_fill_model(model);
+        broker.place_order(make_limit_order(OrderSide::Sell, 1.0, 101.0, None));
+        (1..=1_000)
+            .find(|_| {
+                broker.simulate_fills(sized_tick(0, 101.0, 1.0));
+                broker.unfilled_orders_len() == 0
+            })
+            .unwrap()
+    };
+
+    assert_eq!(touches_until_fill(7), touches_until_fill(7));
+
+    let never = LimitFillModel::TouchProbability { probability: 0.0, seed: 7 };
+    let mut broker = BacktestingBroker::new(10_000.0).with_limit_fill_model(never);
+    broker.place_order(make_limit_order(OrderSide::Sell, 1.0, 101.0, None));
+    for _ in 0..100 {
+        broker.simulate_fills(sized_tick(0, 101.0, 1.0));
+    }
+    assert_eq!(broker.unfilled_orders_len(), 1);
+}
+
+#[test]
+fn fifo_queue_model_fills_after_the_volume_ahead_traded() {
+    let model = LimitFillModel::FifoQueue { queue_ahead: 5.0 };
+    let mut broker = BacktestingBroker::new(10_000.0).with_limit_fill_model(model);
+    let order_id = broker.place_order(make_limit_order(OrderSide::Buy, 4.0, 99.0, None)).id.unwrap();
+
+    broker.simulate_fills(sized_tick(0, 99.0, 3.0));
+    broker.simulate_fills(tick(0, 99.5));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+
+    // 2 more trade ahead of the order, 1 fills it
+    broker.simulate_fills(sized_tick(0, 99.0, 3.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().size, 1.0);
+
+    broker.simulate_fills(sized_tick(0, 99.0, 2.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().size, 3.0);
+
+    // A trade through the limit fills the rest
+    broker.simulate_fills(sized_tick(0, 98.5, 1.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().size, 4.0);
+    assert_eq!(broker.unfilled_orders_len(), 0);
+}
+
+#[test]
+fn fifo_queue_model_only_fills_bars_trading_through_the_limit() {
+    let model = LimitFillModel::FifoQueue { queue_ahead: 0.0 };
+    let mut broker = BacktestingBroker::new(10_000.0).with_limit_fill_model(model);
+    broker.simulate_fills(make_bar(0, 100.0, 100.5, 99.5, 100.0));
+    let order_id = broker.place_order(make_limit_order(OrderSide::Buy, 1.0, 99.0, None)).id.unwrap();
+
+    // Bars don't tell the volume traded at the limit
+    broker.simulate_fills(make_bar(60, 100.0, 100.5, 99.0, 100.0));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+
+    broker.simulate_fills(make_bar(120, 100.0, 100.5, 98.5, 100.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().entry_price, 99.0);
+}
+
+fn make_bar(seconds: i64, open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_seconds(seconds),
+        open,
+        high,
+        low,
+        close,
+        volume: 100.0,
+    })
+}
+
+/// Long trade entered at 100 with a stop at 95 and a target at 105, returns the stop and target order ids
+fn open_bracket(broker: &mut BacktestingBroker) -> (usize, usize) {
+    let entry_id = broker.place_order(make_market_order(OrderSide::Buy, 1.0, None)).id.unwrap();
+    broker.simulate_fills(sized_tick(0, 100.0, 1.0));
+    let trade_id = broker.get_trade_for_order(entry_id).unwrap().id;
+    let stop_id = broker.place_order(make_stop_order(OrderSide::Sell, 1.0, 95.0, Some(trade_id))).id.unwrap();
+    let target_id = broker.place_order(make_limit_order(OrderSide::Sell, 1.0, 105.0, Some(trade_id))).id.unwrap();
+    (stop_id, target_id)
+}
+
+/// Order and price of the first exit fill
+fn first_exit(path: IntrabarPath, bar: MarketData) -> (usize, f64) {
+    let mut broker = BacktestingBroker::new(10_000.0).with_intrabar_path(path);
+    open_bracket(&mut broker);
+    broker.simulate_fills(bar);
+    let fill = broker.get_fill(2).unwrap();
+    (fill.order_id, fill.price)
+}
+
+#[test]
+fn intrabar_path_decides_which_bracket_exit_fills_first() {
+    let bar = make_bar(60, 100.0, 106.0, 94.0, 100.0);
+    assert_eq!(first_exit(IntrabarPath::OpenHighLowClose, bar), (3, 105.0));
+    assert_eq!(first_exit(IntrabarPath::OpenLowHighClose, bar), (2, 95.0));
+
+    // The low is closer to the open
+    let near_low = make_bar(60, 96.0, 106.0, 94.0, 100.0);
+    assert_eq!(first_exit(IntrabarPath::NearestExtreme, near_low), (2, 95.0));
+    assert_eq!(first_exit(IntrabarPath::NearestExtreme, make_bar(60, 104.0, 106.0, 94.0, 100.0)), (3, 105.0));
+
+    // Long position, the stop is hit first
+    assert_eq!(first_exit(IntrabarPath::Pessimistic, bar), (2, 95.0));
+}
+
+#[test]
+fn intrabar_data_replaces_the_path() {
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let (stop_id, _) = open_bracket(&mut broker);
+    broker.set_intrabar_data(
+        vec![
+            make_bar(3_660, 100.0, 100.0, 94.0, 96.0),
+            make_bar(3_600, 100.0, 101.0, 99.0, 100.0),
+            make_bar(3_720, 96.0, 106.0, 96.0, 106.0),
+            // Belongs to the next hour
+            make_bar(7_200, 90.0, 90.0, 90.0, 90.0),
+        ],
+        Duration::hours(1),
+    );
+
+    broker.simulate_fills(make_bar(3_600, 100.0, 106.0, 94.0, 106.0));
+    let fill = broker.get_fill(2).unwrap();
+    assert_eq!(fill.order_id, stop_id);
+    assert_eq!(fill.price, 95.0);
+
+    // Bars without lower timeframe data use the path
+    let mut broker = BacktestingBroker::new(10_000.0);
+    let (_, target_id) = open_bracket(&mut broker);
+    broker.set_intrabar_data(Vec::new(), Duration::hours(1));
+    broker.simulate_fills(make_bar(3_600, 100.0, 106.0, 94.0, 106.0));
+    assert_eq!(broker.get_fill(2).unwrap().order_id, target_id);
+}
+
+#[test]
+fn pessimistic_path_only_counts_the_positions_of_the_orders() {
+    let mut broker = BacktestingBroker::new(10_000.0).with_intrabar_path(IntrabarPath::Pessimistic);
+    // A larger short of another strategy and instrument without orders
+    let other = Order {
+        instrument: 2,
+        strategy_id: 2,
+        ..make_market_order(OrderSide::Sell, 2.0, None)
+    };
+    broker.place_order(other);
+    broker.simulate_fills(sized_tick(0, 100.0, 2.0));
+    let (stop_id, _) = open_bracket(&mut broker);
+
+    broker.simulate_fills(make_bar(60, 100.0, 106.0, 94.0, 100.0));
+    let fill = broker.get_fill(3).unwrap();
+    assert_eq!((fill.order_id, fill.price), (stop_id, 95.0));
+}
+
+#[test]
+fn orders_after_intrabar_data_see_the_parent_bar() {
+    let mut broker = BacktestingBroker::new(10_000.0).with_fill_on_signal_close(true);
+    broker.set_intrabar_data(
+        vec![
+            make_bar(3_600, 100.0, 101.0, 99.0, 100.0),
+            make_bar(3_660, 100.0, 102.0, 100.0, 101.0),
+        ],
+        Duration::hours(1),
+    );
+    broker.simulate_fills(make_bar(3_600, 100.0, 103.0, 99.0, 103.0));
+
+    // Fills at the close of the hour bar, not of the last minute bar inside it
+    let order_id = broker.place_order(make_market_order(OrderSide::Buy, 1.0, None)).id.unwrap();
+    assert_eq!(broker.fill_report(order_id).unwrap().average_price, Some(103.0));
+}
+
+#[test]
+fn large_orders_fill_over_several_bars_within_the_participation_cap() {
+    let limits = VolumeLimits {
+        max_participation: 0.1,
+        min_volume: 50.0,
+    };
+    let mut broker = BacktestingBroker::new(10_000.0).with_volume_limits(limits);
+    let order_id = broker.place_order(make_market_order(OrderSide::Buy, 25.0, None)).id.unwrap();
+
+    broker.simulate_fills(make_bar(60, 100.0, 101.0, 99.0, 100.0));
+    broker.simulate_fills(make_bar(120, 102.0, 103.0, 101.0, 102.0));
+    let report = broker.fill_report(order_id).unwrap();
+    assert_eq!(report.filled, 20.0);
+    assert_eq!(report.remaining, 5.0);
+    assert_eq!(report.average_price, Some(101.0));
+
+    // Too thin to trade
+    let MarketData::Bar(thin) = make_bar(180, 90.0, 90.0, 90.0, 90.0) else {
+        unreachable!()
+    };
+    broker.simulate_fills(MarketData::Bar(Bar { volume: 40.0, ..thin }));
+    assert_eq!(broker.fill_report(order_id).unwrap().remaining, 5.0);
+
+    broker.simulate_fills(make_bar(240, 104.0, 105.0, 103.0, 104.0));
+    let report = broker.fill_report(order_id).unwrap();
+    assert_eq!(report.size, 25.0);
+    assert_eq!(report.remaining, 0.0);
+    assert_eq!(report.fills.len(), 3);
+    assert_eq!(report.average_price, Some((10.0 * 100.0 + 10.0 * 102.0 + 5.0 * 104.0) / 25.0));
+    assert_eq!(report.first_fill, Some(Timestamp::from_seconds(60)));
+    assert_eq!(report.last_fill, Some(Timestamp::from_seconds(240)));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().size, 25.0);
+}
+
+#[test]
+fn participation_cap_applies_to_quoted_sizes() {
+    let limits = VolumeLimits {
+        max_participation: 0.5,
+        ..VolumeLimits::default()
+    };
+    let mut broker = BacktestingBroker::new(10_000.0).with_volume_limits(limits);
+    let order_id = broker.place_order(make_market_order(OrderSide::Sell, 5.0, None)).id.unwrap();
+
+    broker.simulate_fills(make_quote(99.0, 101.0, 4.0));
+    let report = broker.fill_report(order_id).unwrap();
+    assert_eq!(report.filled, 2.0);
+    assert_eq!(report.average_price, Some(99.0));
+    assert!(broker.fill_report(99).is_none());
+}
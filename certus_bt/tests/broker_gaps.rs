@@ -0,0 +1,169 @@
+use certus_bt::broker::{BacktestingBroker, SlippageModel};
+use certus_core::broker::Broker;
+use certus_core::core::{OrderSide, OrderType};
+use certus_core::data::{MarketData, QuoteBar, Timestamp};
+
+mod common;
+use common::{bar, order};
+
+/// Price of the order placed after a bar closing at 100, None when the next bar doesn't fill it
+fn fill_price_with(broker: BacktestingBroker, side: OrderSide, order_type: OrderType, next: MarketData) -> Option<f64> {
+    let mut broker = broker;
+    broker.simulate_fills(bar(0, 100.0, 100.5, 99.5, 100.0));
+    let order_id = broker.place_order(order(side, order_type, 1.0)).id.unwrap();
+    broker.simulate_fills(next);
+    broker.get_trade_for_order(order_id).map(|trade| trade.entry_price)
+}
+
+fn fill_price(side: OrderSide, order_type: OrderType, next: MarketData) -> Option<f64> {
+    fill_price_with(BacktestingBroker::new(10_000.0), side, order_type, next)
+}
+
+/// Quote bar with the bid half a point below the ask
+fn quote_bar(seconds: i64, ask_open: f64, ask_high: f64, ask_low: f64, ask_close: f64) -> MarketData {
+    MarketData::QuoteBar(QuoteBar {
+        timestamp: Timestamp::from_seconds(seconds),
+        bid_open: ask_open - 0.5,
+        bid_high: ask_high - 0.5,
+        bid_low: ask_low - 0.5,
+        bid_close: ask_close - 0.5,
+        bid_size: 1000.0,
+        ask_open,
+        ask_high,
+        ask_low,
+        ask_close,
+        ask_size: 1000.0,
+    })
+}
+
+fn gap_up() -> MarketData {
+    bar(60, 105.0, 106.0, 104.5, 105.5)
+}
+
+fn gap_down() -> MarketData {
+    bar(60, 95.0, 95.5, 94.0, 94.5)
+}
+
+#[test]
+fn market_orders_fill_at_the_open() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Market, gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Market, gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Market, gap_down()), Some(95.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Market, gap_down()), Some(95.0));
+}
+
+#[test]
+fn stops_gapped_through_fill_at_the_open() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Stop(102.0), gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Stop(98.0), gap_down()), Some(95.0));
+}
+
+#[test]
+fn stops_reached_inside_the_bar_fill_at_the_stop() {
+    let bar = bar(60, 100.5, 103.0, 97.0, 101.0);
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Stop(102.0), bar), Some(102.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Stop(98.0), bar), Some(98.0));
+}
+
+#[test]
+fn stops_gapped_away_from_do_not_fill() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Stop(102.0), gap_down()), None);
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Stop(98.0), gap_up()), None);
+}
+
+#[test]
+fn limits_gapped_through_fill_at_the_better_open() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Limit(98.0), gap_down()), Some(95.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Limit(102.0), gap_up()), Some(105.0));
+}
+
+#[test]
+fn limits_reached_inside_the_bar_fill_at_the_limit() {
+    let bar = bar(60, 100.5, 103.0, 97.0, 101.0);
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Limit(98.0), bar), Some(98.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Limit(102.0), bar), Some(102.0));
+}
+
+#[test]
+fn limits_gapped_away_from_do_not_fill() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::Limit(98.0), gap_up()), None);
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::Limit(102.0), gap_down()), None);
+}
+
+#[test]
+fn stop_limits_gapped_within_the_limit_fill_at_the_open() {
+    let buy = OrderType::StopLimit(102.0, 105.5);
+    let sell = OrderType::StopLimit(98.0, 94.5);
+    assert_eq!(fill_price(OrderSide::Buy, buy, gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Sell, sell, gap_down()), Some(95.0));
+}
+
+#[test]
+fn stop_limits_gapped_beyond_the_limit_rest_as_limits() {
+    // Opens beyond the limit and trades back to it later in the bar
+    let buy = OrderType::StopLimit(102.0, 103.0);
+    let sell = OrderType::StopLimit(98.0, 97.0);
+    assert_eq!(fill_price(OrderSide::Buy, buy.clone(), bar(60, 105.0, 106.0, 102.5, 103.0)), Some(103.0));
+    assert_eq!(fill_price(OrderSide::Sell, sell.clone(), bar(60, 95.0, 97.5, 94.0, 96.0)), Some(97.0));
+
+    // Never trades back
+    assert_eq!(fill_price(OrderSide::Buy, buy, gap_up()), None);
+    assert_eq!(fill_price(OrderSide::Sell, sell, gap_down()), None);
+}
+
+#[test]
+fn stop_limits_gapped_beyond_the_limit_fill_when_the_gap_bar_trades_back() {
+    // The bar opens at its extreme beyond the limit and only trades back towards the other one
+    let buy = OrderType::StopLimit(102.0, 103.0);
+    let sell = OrderType::StopLimit(98.0, 97.0);
+    assert_eq!(fill_price(OrderSide::Buy, buy.clone(), bar(60, 105.0, 105.0, 102.0, 102.5)), Some(103.0));
+    assert_eq!(fill_price(OrderSide::Sell, sell.clone(), bar(60, 95.0, 98.0, 95.0, 97.5)), Some(97.0));
+
+    // Same on the quotes: the ask opens beyond the buy limit and the bid beyond the sell limit
+    assert_eq!(fill_price(OrderSide::Buy, buy, quote_bar(60, 105.0, 105.0, 102.5, 103.0)), Some(103.0));
+    assert_eq!(fill_price(OrderSide::Sell, sell, quote_bar(60, 95.5, 98.5, 95.5, 98.0)), Some(97.0));
+}
+
+#[test]
+fn stop_limits_reached_inside_the_bar_fill_at_the_stop() {
+    let bar = bar(60, 100.5, 103.0, 97.0, 101.0);
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::StopLimit(102.0, 102.5), bar), Some(102.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::StopLimit(98.0, 97.5), bar), Some(98.0));
+}
+
+#[test]
+fn stop_limits_gapped_away_from_do_not_fill() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::StopLimit(102.0, 103.0), gap_down()), None);
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::StopLimit(98.0, 97.0), gap_up()), None);
+}
+
+#[test]
+fn market_on_open_orders_fill_at_the_gapped_open() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::MarketOnOpen, gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::MarketOnOpen, gap_up()), Some(105.0));
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::MarketOnOpen, gap_down()), Some(95.0));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::MarketOnOpen, gap_down()), Some(95.0));
+}
+
+#[test]
+fn market_on_close_orders_fill_at_the_close_after_a_gap() {
+    assert_eq!(fill_price(OrderSide::Buy, OrderType::MarketOnClose, gap_up()), Some(105.5));
+    assert_eq!(fill_price(OrderSide::Sell, OrderType::MarketOnClose, gap_down()), Some(94.5));
+}
+
+#[test]
+fn slippage_makes_gapped_stops_worse() {
+    let fixed = || BacktestingBroker::new(10_000.0).with_slippage_model(SlippageModel::Fixed(0.25));
+    assert_eq!(fill_price_with(fixed(), OrderSide::Buy, OrderType::Stop(102.0), gap_up()), Some(105.25));
+    assert_eq!(fill_price_with(fixed(), OrderSide::Sell, OrderType::Stop(98.0), gap_down()), Some(94.75));
+    assert_eq!(fill_price_with(fixed(), OrderSide::Buy, OrderType::Market, gap_down()), Some(95.25));
+
+    // Never beyond the limit of a stop-limit, limits have no slippage
+    let stop_limit = OrderType::StopLimit(102.0, 105.1);
+    assert_eq!(fill_price_with(fixed(), OrderSide::Buy, stop_limit, gap_up()), Some(105.1));
+    assert_eq!(fill_price_with(fixed(), OrderSide::Buy, OrderType::Limit(98.0), gap_down()), Some(95.0));
+
+    let percentage = BacktestingBroker::new(10_000.0).with_slippage_model(SlippageModel::Percentage(0.01));
+    let price = fill_price_with(percentage, OrderSide::Sell, OrderType::Stop(98.0), gap_down()).unwrap();
+    assert!((price - 94.05).abs() < 1e-9);
+}
@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use certus_bt::cache::{
+    CacheDataKind, CachedCSVDataHandler, MarketDataCache, SourceFingerprint, write_cache,
+};
+use certus_bt::csv_data_handler::{CSVFields, CSVRowParser, CSVTimestamp, EpochUnit, GenericCSVRowParser};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_core::data::{Bar, DataHandler, MarketData, Quote, Tick, Timestamp};
+use certus_core::timeframe::Timeframe;
+use chrono::NaiveTime;
+use csv::StringRecord;
+
+/// Rows of "seconds,open,high,low,close,volume"
+struct TestRowParser;
+
+impl CSVRowParser for TestRowParser {
+    fn parse_row(&mut self, row: StringRecord) -> Result<MarketData, Box<dyn Error>> {
+        Ok(MarketData::Bar(Bar {
+            timestamp: Timestamp::from_seconds(row[0].parse::<i64>()?),
+            open: row[1].parse::<f64>()?,
+            high: row[2].parse::<f64>()?,
+            low: row[3].parse::<f64>()?,
+            close: row[4].parse::<f64>()?,
+            volume: row[5].parse::<f64>()?,
+        }))
+    }
+}
+
+fn temp_path(name: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("certus_{}_{}.{}", name, std::process::id(), extension))
+}
+
+fn write_csv(path: &PathBuf, minutes: i64) {
+    let mut content = String::from("Time,Open,High,Low,Close,Volume\n");
+    for minute in 0..minutes {
+        let price = 100.0 + minute as f64;
+        content.push_str(&format!("{},{},{},{},{},1\n", minute * 60, price, price + 1.0, price - 1.0, price));
+    }
+    std::fs::write(path, content).unwrap();
+}
+
+fn create_bar(seconds: i64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_seconds(seconds),
+        open: close - 1.0,
+        high: close + 2.0,
+        low: close - 2.0,
+        close,
+        volume: 10.0,
+    })
+}
+
+fn poll_all(data_handler: &mut dyn DataHandler) -> Vec<MarketData> {
+    data_handler.start().unwrap();
+    let mut data_feed = data_handler.get_data_feed();
+    let mut data = Vec::new();
+    while let Some(market_data) = data_feed.poll().unwrap() {
+        data.push(market_data);
+    }
+    data
+}
+
+#[test]
+fn bars_round_trip_through_cache() {
+    let path = temp_path("cache_bars", "bin");
+    let source = SourceFingerprint { len: 42, modified: 7 };
+    let data = vec![create_bar(0, 100.0), create_bar(60, 101.5), create_bar(120, 99.25)];
+
+    write_cache(&path, "ES", Some(Timeframe::Minutes(1)), source, 3, &data).unwrap();
+    let cache = MarketDataCache::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let header = cache.header();
+    assert_eq!(header.instrument, "ES");
+    assert_eq!(header.kind, CacheDataKind::Bars);
+    assert_eq!(header.timeframe, Some(Timeframe::Minutes(1)));
+    assert_eq!(header.start, Timestamp::from_seconds(0));
+    assert_eq!(header.end, Timestamp::from_seconds(120));
+    assert_eq!(header.source, source);
+    assert_eq!(header.settings, 3);
+    assert_eq!(cache.len(), 3);
+
+    let Some(MarketData::Bar(bar)) = cache.get(1) else {
+        panic!("expected bar");
+    };
+    assert_eq!(bar.timestamp, Timestamp::from_seconds(60));
+    assert_eq!(bar.open, 100.5);
+    assert_eq!(bar.high, 103.5);
+    assert_eq!(bar.low, 99.5);
+    assert_eq!(bar.close, 101.5);
+    assert_eq!(bar.volume, 10.0);
+    assert!(cache.get(3).is_none());
+}
+
+#[test]
+fn ticks_round_trip_through_cache() {
+    let path = temp_path("cache_ticks", "bin");
+    let data = vec![
+        MarketData::Tick(Tick {
+            timestamp: Timestamp::from_nanos(1),
+            price: 100.25,
+            size: 3.0,
+        }),
+        MarketData::Tick(Tick {
+            timestamp: Timestamp::from_nanos(2),
+            price: 100.5,
+            size: 1.0,
+        }),
+    ];
+
+    write_cache(&path, "NQ", None, SourceFingerprint { len: 0, modified: 0 }, 0, &data).unwrap();
+    let cache = MarketDataCache::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(cache.header().kind, CacheDataKind::Ticks);
+    assert_eq!(cache.header().timeframe, None);
+    let Some(MarketData::Tick(tick)) = cache.get(1) else {
+        panic!("expected tick");
+    };
+    assert_eq!(tick.timestamp, Timestamp::from_nanos(2));
+    assert_eq!(tick.price, 100.5);
+    assert_eq!(tick.size, 1.0);
+}
+
+#[test]
+fn quotes_round_trip_through_cache() {
+    let path = temp_path("cache_quotes", "bin");
+    let data = vec![MarketData::Quote(Quote {
+        timestamp: Timestamp::from_nanos(1),
+        bid: 100.25,
+        bid_size: 3.0,
+        ask: 100.5,
+        ask_size: 4.0,
+    })];
+
+    write_cache(&path, "EURUSD", None, SourceFingerprint { len: 0, modified: 0 }, 0, &data).unwrap();
+    let cache = MarketDataCache::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(cache.header().kind, CacheDataKind::Quotes);
+    let Some(MarketData::Quote(quote)) = cache.get(0) else {
+        panic!("expected quote");
+    };
+    assert_eq!((quote.bid, quote.bid_size, quote.ask, quote.ask_size), (100.25, 3.0, 100.5, 4.0));
+}
+
+#[test]
+fn cached_handler_caches_quote_bars() {
+    let csv_path = temp_path("cache_quote_bars", "csv");
+    let cache_path = temp_path("cache_quote_bars", "bin");
+    std::fs::write(&csv_path, "time,bid,ask\n0,100,100.5\n30,99.5,100\n60,101,101.5\n").unwrap();
+    let parser = GenericCSVRowParser::new(
+        CSVTimestamp::Epoch {
+            column: "time".into(),
+            unit: EpochUnit::Seconds,
+        },
+        CSVFields::Quotes {
+            bid: "bid".into(),
+            ask: "ask".into(),
+            bid_size: None,
+            ask_size: None,
+        },
+    );
+    let mut data_handler = CachedCSVDataHandler::new(
+        csv_path.to_str().unwrap().to_string(),
+        cache_path.to_str().unwrap().to_string(),
+        String::from("EURUSD"),
+        Box::new(parser),
+        HistoricBarConsolidationModel::new(1, 1),
+    );
+    let data = poll_all(&mut data_handler);
+    assert_eq!(data_handler.cache().unwrap().header().kind, CacheDataKind::QuoteBars);
+    std::fs::remove_file(csv_path).unwrap();
+    std::fs::remove_file(cache_path).unwrap();
+
+    assert_eq!(data.len(), 2);
+    let MarketData::QuoteBar(quote_bar) = data[0] else {
+        panic!("expected quote bar");
+    };
+    assert_eq!((quote_bar.bid_open, quote_bar.bid_low, quote_bar.ask_close), (100.0, 99.5, 100.0));
+}
+
+#[test]
+fn mixed_data_and_invalid_files_are_rejected() {
+    let path = temp_path("cache_invalid", "bin");
+    let tick = MarketData::Tick(Tick {
+        timestamp: Timestamp::from_nanos(0),
+        price: 1.0,
+        size: 1.0,
+    });
+    let source = SourceFingerprint { len: 0, modified: 0 };
+    assert!(write_cache(&path, "ES", None, source, 0, &[create_bar(0, 1.0), tick]).is_err());
+
+    std::fs::write(&path, b"Time,Open,High,Low,Close,Volume").unwrap();
+    assert!(MarketDataCache::open(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn corrupt_lengths_are_invalid_data() {
+    let path = temp_path("cache_corrupt", "bin");
+    let source = SourceFingerprint { len: 0, modified: 0 };
+    write_cache(&path, "ES", None, source, 0, &[create_bar(0, 1.0)]).unwrap();
+
+    // A length that overflows the size of the data
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[24..32].copy_from_slice(&(u64::MAX / 8).to_le_bytes());
+    std::fs::write(&path, bytes).unwrap();
+    let result = MarketDataCache::open(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(result.is_err_and(|error| error.kind() == std::io::ErrorKind::InvalidData));
+}
+
+#[test]
+fn cached_handler_rebuilds_when_source_changes() {
+    let csv_path = temp_path("cache_source", "csv");
+    let cache_path = temp_path("cache_source", "bin");
+    let handler = || {
+        CachedCSVDataHandler::new(
+            csv_path.to_str().unwrap().to_string(),
+            cache_path.to_str().unwrap().to_string(),
+            String::from("ES"),
+            Box::new(TestRowParser),
+            HistoricBarConsolidationModel::new(1, 5),
+        )
+    };
+
+    write_csv(&csv_path, 10);
+    let mut first = handler();
+    assert_eq!(poll_all(&mut first).len(), 2);
+    assert_eq!(first.cache().unwrap().header().timeframe, Some(Timeframe::Minutes(5)));
+
+    // Replayed from the existing cache
+    let mut second = handler();
+    let replayed = poll_all(&mut second);
+    assert_eq!(replayed.len(), 2);
+    let MarketData::Bar(bar) = replayed[1] else {
+        panic!("expected bar");
+    };
+    assert_eq!(bar.timestamp, Timestamp::from_seconds(300));
+    assert_eq!(bar.open, 105.0);
+    assert_eq!(bar.close, 109.0);
+    assert_eq!(bar.volume, 5.0);
+
+    write_csv(&csv_path, 15);
+    let mut third = handler();
+    assert_eq!(poll_all(&mut third).len(), 3);
+    assert_eq!(third.cache().unwrap().header().end, Timestamp::from_seconds(600));
+
+    std::fs::remove_file(csv_path).unwrap();
+    std::fs::remove_file(cache_path).unwrap();
+}
+
+#[test]
+fn cached_handler_rebuilds_when_settings_change() {
+    let csv_path = temp_path("cache_settings", "csv");
+    let cache_path = temp_path("cache_settings", "bin");
+    let handler = |model: HistoricBarConsolidationModel| {
+        CachedCSVDataHandler::new(
+            csv_path.to_str().unwrap().to_string(),
+            cache_path.to_str().unwrap().to_string(),
+            String::from("ES"),
+            Box::new(TestRowParser),
+            model,
+        )
+    };
+
+    write_csv(&csv_path, 10);
+    let mut first = handler(HistoricBarConsolidationModel::new(1, 5));
+    assert_eq!(poll_all(&mut first)[0].timestamp(), Timestamp::from_seconds(0));
+    let settings = first.cache().unwrap().header().settings;
+
+    // Same output timeframe, buckets aligned to another session open
+    let shifted = HistoricBarConsolidationModel::new(1, 5).with_session_open(NaiveTime::from_hms_opt(0, 2, 0).unwrap());
+    let mut second = handler(shifted);
+    let rebuilt = poll_all(&mut second);
+    assert_ne!(second.cache().unwrap().header().settings, settings);
+    assert_eq!(rebuilt.len(), 3);
+    assert_eq!(rebuilt[1].timestamp(), Timestamp::from_seconds(120));
+
+    std::fs::remove_file(csv_path).unwrap();
+    std::fs::remove_file(cache_path).unwrap();
+}
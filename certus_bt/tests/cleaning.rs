@@ -0,0 +1,200 @@
+use certus_bt::cleaning::{CleaningAction, CleaningConfig, DataIssueKind, MarketDataCleaner};
+use certus_core::data::{Bar, MarketData, Tick, Timestamp};
+use certus_core::session::{SessionType, TradingCalendar};
+use chrono::{Duration, NaiveDate};
+
+fn create_bar(seconds: i64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_seconds(seconds),
+        open,
+        high,
+        low,
+        close,
+        volume,
+    })
+}
+
+fn create_tick(seconds: i64, price: f64, size: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_seconds(seconds),
+        price,
+        size,
+    })
+}
+
+fn clean_all(cleaner: &mut MarketDataCleaner, data: Vec<MarketData>) -> Vec<MarketData> {
+    data.into_iter()
+        .enumerate()
+        .flat_map(|(index, market_data)| cleaner.clean(market_data, Some(index as u64 + 2)))
+        .collect()
+}
+
+#[test]
+fn duplicates_and_out_of_order_rows_are_dropped() {
+    let mut cleaner = MarketDataCleaner::new(CleaningConfig::default());
+    let cleaned = clean_all(
+        &mut cleaner,
+        vec![
+            create_bar(60, 100.0, 101.0, 99.0, 100.0, 1.0),
+            create_bar(60, 100.0, 101.0, 99.0, 100.5, 1.0),
+            create_bar(0, 100.0, 101.0, 99.0, 100.0, 1.0),
+            create_bar(120, 100.0, 101.0, 99.0, 100.0, 1.0),
+        ],
+    );
+
+    assert_eq!(cleaned.len(), 2);
+    assert_eq!(cleaned[1].timestamp(), Timestamp::from_seconds(120));
+    let report = cleaner.report();
+    assert_eq!(report.rows, 4);
+    assert_eq!(report.dropped, 2);
+    assert_eq!(report.count(DataIssueKind::Duplicate), 1);
+    assert_eq!(report.count(DataIssueKind::OutOfOrder), 1);
+    assert_eq!(report.issues[0].line, Some(3));
+    assert_eq!(report.issues[1].line, Some(4));
+}
+
+#[test]
+fn identical_ticks_are_duplicates_but_same_time_trades_are_not() {
+    let mut cleaner = MarketDataCleaner::new(CleaningConfig::default());
+    let cleaned = clean_all(
+        &mut cleaner,
+        vec![create_tick(0, 100.0, 1.0), create_tick(0, 100.25, 2.0), create_tick(0, 100.25, 2.0)],
+    );
+
+    assert_eq!(cleaned.len(), 2);
+    assert_eq!(cleaner.report().count(DataIssueKind::Duplicate), 1);
+}
+
+#[test]
+fn invalid_bars_are_repaired() {
+    let config = CleaningConfig {
+        invalid_prices: CleaningAction::Repair,
+        ..CleaningConfig::default()
+    };
+    let mut cleaner = MarketDataCleaner::new(config);
+    let cleaned = clean_all(
+        &mut cleaner,
+        vec![
+            create_bar(0, 100.0, 99.0, 101.0, 100.5, -1.0),
+            create_bar(60, f64::NAN, 101.0, 99.0, 100.0, 1.0),
+        ],
+    );
+
+    assert_eq!(cleaned.len(), 1);
+    let MarketData::Bar(bar) = cleaned[0] else {
+        panic!("expected bar");
+    };
+    assert_eq!(bar.high, 101.0);
+    assert_eq!(bar.low, 99.0);
+    assert_eq!(bar.volume, 0.0);
+    let report = cleaner.report();
+    assert_eq!(report.repaired, 1);
+    assert_eq!(report.dropped, 1);
+    assert_eq!(report.issues[0].action, CleaningAction::Repair);
+    // The repaired volume is zero
+    assert_eq!(report.issues[1].kind, DataIssueKind::ZeroVolume);
+    assert_eq!(report.issues[2].action, CleaningAction::Drop);
+}
+
+#[test]
+fn zero_volume_is_reported_and_kept_by_default() {
+    let mut cleaner = MarketDataCleaner::new(CleaningConfig::default());
+    let cleaned = clean_all(&mut cleaner, vec![create_bar(0, 100.0, 101.0, 99.0, 100.0, 0.0)]);
+
+    assert_eq!(cleaned.len(), 1);
+    assert_eq!(cleaner.report().count(DataIssueKind::ZeroVolume), 1);
+    assert_eq!(cleaner.report().issues[0].action, CleaningAction::Report);
+}
+
+#[test]
+fn price_spikes_are_detected_against_recent_returns() {
+    let config = CleaningConfig {
+        price_spikes: CleaningAction::Drop,
+        spike_sigma: 5.0,
+        spike_window: 20,
+        ..CleaningConfig::default()
+    };
+    let mut cleaner = MarketDataCleaner::new(config);
+    let mut ticks: Vec<MarketData> = (0..30)
+        .map(|second| create_tick(second, 100.0 + if second % 2 == 0 { 0.25 } else { 0.0 }, 1.0))
+        .collect();
+    ticks.push(create_tick(30, 150.0, 1.0));
+    ticks.push(create_tick(31, 100.25, 1.0));
+
+    let cleaned = clean_all(&mut cleaner, ticks);
+
+    // The price after the dropped spike is compared with the price before it
+    assert_eq!(cleaned.len(), 31);
+    assert_eq!(cleaner.report().count(DataIssueKind::PriceSpike), 1);
+    assert_eq!(cleaner.report().issues[0].timestamp, Timestamp::from_seconds(30));
+}
+
+#[test]
+fn level_shifts_are_not_dropped_as_spikes() {
+    let config = CleaningConfig {
+        price_spikes: CleaningAction::Drop,
+        spike_sigma: 5.0,
+        spike_window: 20,
+        ..CleaningConfig::default()
+    };
+    let mut cleaner = MarketDataCleaner::new(config);
+    let mut ticks: Vec<MarketData> = (0..30)
+        .map(|second| create_tick(second, 100.0 + if second % 2 == 0 { 0.25 } else { 0.0 }, 1.0))
+        .collect();
+    // The price gaps to a new level and stays there
+    ticks.extend((30..40).map(|second| create_tick(second, 150.0 + if second % 2 == 0 { 0.25 } else { 0.0 }, 1.0)));
+
+    let cleaned = clean_all(&mut cleaner, ticks);
+
+    assert_eq!(cleaned.len(), 40);
+    assert_eq!(cleaned[30].timestamp(), Timestamp::from_seconds(30));
+    assert_eq!(cleaned[39].timestamp(), Timestamp::from_seconds(39));
+    let report = cleaner.report();
+    assert_eq!(report.dropped, 0);
+    assert_eq!(report.count(DataIssueKind::PriceSpike), 1);
+    assert_eq!(report.issues[0].action, CleaningAction::Report);
+}
+
+#[test]
+fn gaps_are_only_reported_while_the_session_is_open() {
+    let local = |day: u32, hour: u32, minute: u32| {
+        Timestamp::from_local(
+            NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap(),
+            &chrono_tz::America::New_York,
+        )
+    };
+    let bar = |timestamp: Timestamp| {
+        MarketData::Bar(Bar {
+            timestamp,
+            open: 100.0,
+            high: 100.0,
+            low: 100.0,
+            close: 100.0,
+            volume: 1.0,
+        })
+    };
+    let config = CleaningConfig {
+        max_gap: Some(Duration::minutes(5)),
+        calendar: Some(TradingCalendar::us_equities()),
+        session_type: SessionType::Regular,
+        ..CleaningConfig::default()
+    };
+    let mut cleaner = MarketDataCleaner::new(config);
+
+    let data = vec![
+        bar(local(2, 15, 55)),
+        // Overnight, the session reopens at 9:30
+        bar(local(3, 9, 30)),
+        bar(local(3, 9, 35)),
+        // 25 minutes without data during the session
+        bar(local(3, 10, 0)),
+    ];
+    let cleaned = clean_all(&mut cleaner, data);
+
+    assert_eq!(cleaned.len(), 4);
+    let report = cleaner.report();
+    assert_eq!(report.count(DataIssueKind::SessionGap), 1);
+    assert_eq!(report.issues[0].timestamp, local(3, 10, 0));
+    assert_eq!(report.issues[0].line, Some(5));
+    assert!(report.to_string().contains("1 SessionGap"));
+}
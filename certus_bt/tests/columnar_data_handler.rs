@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, TimestampMicrosecondArray, TimestampMillisecondArray};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::TimeUnit;
+use certus_bt::columnar_data_handler::{ColumnMapping, ColumnarDataHandler, ColumnarDataKind, partition_date};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_core::data::{Bar, DataHandler, DataHandlerError, MarketData, Timestamp};
+use chrono::{Duration, NaiveDate};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certus_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+fn utc(day: u32, hour: u32, minute: u32) -> Timestamp {
+    Timestamp::from_naive_utc(
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap(),
+    )
+}
+
+fn minute_bars(name: &str, start: Timestamp, minutes: i64) -> Vec<(&str, ArrayRef)> {
+    let timestamps: Vec<i64> = (0..minutes)
+        .map(|minute| (start + Duration::minutes(minute)).nanos() / 1_000)
+        .collect();
+    let prices: Vec<f64> = (0..minutes).map(|minute| 100.0 + minute as f64).collect();
+    vec![
+        (name, Arc::new(TimestampMicrosecondArray::from(timestamps).with_timezone("UTC")) as ArrayRef),
+        ("open", Arc::new(Float64Array::from(prices.clone())) as ArrayRef),
+        ("high", Arc::new(Float64Array::from(prices.iter().map(|p| p + 1.0).collect::<Vec<_>>())) as ArrayRef),
+        ("low", Arc::new(Float64Array::from(prices.iter().map(|p| p - 1.0).collect::<Vec<_>>())) as ArrayRef),
+        ("close", Arc::new(Float64Array::from(prices)) as ArrayRef),
+        ("volume", Arc::new(Int64Array::from(vec![1; minutes as usize])) as ArrayRef),
+    ]
+}
+
+fn write_parquet(path: &Path, columns: Vec<(&str, ArrayRef)>, row_group_size: usize) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let batch = RecordBatch::try_from_iter(columns).unwrap();
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(row_group_size)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), Some(properties)).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+}
+
+fn read_bars(data_handler: &mut ColumnarDataHandler) -> Vec<Bar> {
+    data_handler.start().unwrap();
+    let mut data_feed = data_handler.get_data_feed();
+    let mut bars = Vec::new();
+    while let Some(market_data) = data_feed.poll().unwrap() {
+        match market_data {
+            MarketData::Bar(bar) => bars.push(bar),
+            other => panic!("expected bar, got {:?}", other),
+        }
+    }
+    bars
+}
+
+#[test]
+fn reads_bars_with_mapped_epoch_columns() {
+    let dir = temp_dir("columnar_mapping");
+    let path = dir.join("bars.parquet");
+    let start = utc(2, 14, 30);
+    let millis: Vec<i64> = (0..3).map(|minute| (start + Duration::minutes(minute)).nanos() / 1_000_000).collect();
+    write_parquet(
+        &path,
+        vec![
+            ("ts", Arc::new(Int64Array::from(millis)) as ArrayRef),
+            ("o", Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])) as ArrayRef),
+            ("h", Arc::new(Float64Array::from(vec![1.5, 2.5, 3.5])) as ArrayRef),
+            ("l", Arc::new(Float64Array::from(vec![0.5, 1.5, 2.5])) as ArrayRef),
+            ("c", Arc::new(Float64Array::from(vec![1.25, 2.25, 3.25])) as ArrayRef),
+            ("v", Arc::new(Int64Array::from(vec![10, 20, 30])) as ArrayRef),
+        ],
+        1024,
+    );
+
+    let columns = ColumnMapping {
+        timestamp: String::from("ts"),
+        open: String::from("o"),
+        high: String::from("h"),
+        low: String::from("l"),
+        close: String::from("c"),
+        volume: String::from("v"),
+        epoch_unit: TimeUnit::Millisecond,
+        ..ColumnMapping::default()
+    };
+    let mut data_handler = ColumnarDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        ColumnarDataKind::Bars,
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_columns(columns);
+
+    let bars = read_bars(&mut data_handler);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(bars.len(), 3);
+    assert_eq!(bars[1].timestamp, start + Duration::minutes(1));
+    assert_eq!(bars[1].open, 2.0);
+    assert_eq!(bars[1].high, 2.5);
+    assert_eq!(bars[1].low, 1.5);
+    assert_eq!(bars[1].close, 2.25);
+    assert_eq!(bars[1].volume, 20.0);
+}
+
+#[test]
+fn date_range_is_applied_across_row_groups() {
+    let dir = temp_dir("columnar_range");
+    let path = dir.join("bars.parquet");
+    let start = utc(2, 0, 0);
+    write_parquet(&path, minute_bars("timestamp", start, 50), 10);
+
+    let mut data_handler = ColumnarDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        ColumnarDataKind::Bars,
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_date_range(Some(start + Duration::minutes(15)), Some(start + Duration::minutes(32)));
+
+    let bars = read_bars(&mut data_handler);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(bars.len(), 17);
+    assert_eq!(bars[0].timestamp, start + Duration::minutes(15));
+    assert_eq!(bars[16].timestamp, start + Duration::minutes(31));
+}
+
+#[test]
+fn partitioned_dataset_skips_partitions_outside_range() {
+    let dir = temp_dir("columnar_partitions");
+    for day in [2, 5, 8] {
+        let partition = dir.join(format!("date=2024-01-0{}", day)).join("part-0.parquet");
+        write_parquet(&partition, minute_bars("timestamp", utc(day, 15, 0), 60), 1024);
+    }
+
+    let mut data_handler = ColumnarDataHandler::new(
+        dir.to_str().unwrap().to_string(),
+        ColumnarDataKind::Bars,
+        HistoricBarConsolidationModel::new(1, 30),
+    )
+    .with_date_range(Some(utc(5, 0, 0)), Some(utc(6, 0, 0)));
+
+    let files = data_handler.dataset_files().unwrap();
+    let bars = read_bars(&mut data_handler);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(files.len(), 1);
+    assert!(files[0].to_str().unwrap().contains("date=2024-01-05"));
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0].timestamp, utc(5, 15, 0));
+    assert_eq!(bars[1].timestamp, utc(5, 15, 30));
+    assert_eq!(bars[1].volume, 30.0);
+}
+
+#[test]
+fn reads_trades_from_arrow_ipc_in_local_time() {
+    let dir = temp_dir("columnar_ipc");
+    let path = dir.join("trades.arrow");
+    // Exchange local time without time zone, 08:30 CT is 14:30 UTC in January
+    let local = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(8, 30, 0).unwrap();
+    let millis: Vec<i64> = [0, 20, 70]
+        .iter()
+        .map(|second| (local + Duration::seconds(*second)).and_utc().timestamp_millis())
+        .collect();
+    let batch = RecordBatch::try_from_iter(vec![
+        ("timestamp", Arc::new(TimestampMillisecondArray::from(millis)) as ArrayRef),
+        ("price", Arc::new(Float64Array::from(vec![100.0, 101.0, 99.0])) as ArrayRef),
+        ("size", Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])) as ArrayRef),
+    ])
+    .unwrap();
+    let mut writer = FileWriter::try_new(File::create(&path).unwrap(), &batch.schema()).unwrap();
+    writer.write(&batch).unwrap();
+    writer.finish().unwrap();
+
+    let columns = ColumnMapping {
+        time_zone: chrono_tz::America::Chicago,
+        ..ColumnMapping::default()
+    };
+    let mut data_handler = ColumnarDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        ColumnarDataKind::Trades,
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_columns(columns);
+
+    let bars = read_bars(&mut data_handler);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0].timestamp, utc(2, 14, 30));
+    assert_eq!(bars[0].high, 101.0);
+    assert_eq!(bars[0].volume, 3.0);
+    assert_eq!(bars[1].timestamp, utc(2, 14, 31));
+}
+
+#[test]
+fn missing_columns_fail_to_start() {
+    let dir = temp_dir("columnar_missing");
+    let path = dir.join("bars.parquet");
+    write_parquet(&path, minute_bars("time", utc(2, 0, 0), 5), 1024);
+
+    let mut data_handler = ColumnarDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        ColumnarDataKind::Bars,
+        HistoricBarConsolidationModel::new(1, 1),
+    );
+    let result = data_handler.start();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn epoch_timestamps_out_of_range_fail_to_start() {
+    let dir = temp_dir("columnar_overflow");
+    let path = dir.join("bars.parquet");
+    let mut columns = minute_bars("time", utc(2, 0, 0), 2);
+    columns[0] = ("timestamp", Arc::new(Int64Array::from(vec![0, i64::MAX / 10])) as ArrayRef);
+    write_parquet(&path, columns, 1024);
+
+    let mut data_handler = ColumnarDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        ColumnarDataKind::Bars,
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_columns(ColumnMapping {
+        epoch_unit: TimeUnit::Second,
+        ..ColumnMapping::default()
+    });
+    let result = data_handler.start();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(DataHandlerError::Parse { column: Some(column), .. }) if column == "timestamp"
+    ));
+}
+
+#[test]
+fn partition_dates_are_parsed_from_paths() {
+    let date = NaiveDate::from_ymd_opt(2024, 1, 2);
+    assert_eq!(partition_date(Path::new("data/date=2024-01-02/part-0.parquet")), date);
+    assert_eq!(partition_date(Path::new("data/ES/20240102.parquet")), date);
+    assert_eq!(partition_date(Path::new("data/ES/bars.parquet")), None);
+}
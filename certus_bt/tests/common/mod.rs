@@ -0,0 +1,44 @@
+#![allow(dead_code)]
+
+use certus_core::core::{Order, OrderSide, OrderType};
+use certus_core::data::{Bar, MarketData, Tick, Timestamp};
+
+/// Order of instrument 1 and strategy 1
+pub fn order(side: OrderSide, order_type: OrderType, size: f64) -> Order {
+    Order {
+        id: None,
+        related_id: None,
+        instrument: 1,
+        strategy_id: 1,
+        side,
+        order_type,
+        size,
+    }
+}
+
+pub fn bar(seconds: i64, open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    bar_at(Timestamp::from_seconds(seconds), open, high, low, close)
+}
+
+pub fn bar_at(timestamp: Timestamp, open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp,
+        open,
+        high,
+        low,
+        close,
+        volume: 1_000.0,
+    })
+}
+
+pub fn tick(millis: i64, price: f64) -> MarketData {
+    sized_tick(millis, price, 10.0)
+}
+
+pub fn sized_tick(millis: i64, price: f64, size: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_millis(millis),
+        price,
+        size,
+    })
+}
@@ -0,0 +1,221 @@
+use certus_bt::broker::BacktestingBroker;
+use certus_bt::continuous::{
+    AdjustmentMethod, ContinuousFuturesDataHandler, ContinuousFuturesModel, ContractData, RollRule,
+};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_core::broker::Broker;
+use certus_core::core::{Order, OrderSide, OrderType};
+use certus_core::data::{Bar, DataHandler, MarketData, Timestamp};
+use certus_core::timeframe::Timeframe;
+use chrono::NaiveDate;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+fn at(day: u32) -> Timestamp {
+    Timestamp::from_naive_utc(date(3, day).and_hms_opt(14, 0, 0).unwrap())
+}
+
+/// Daily bars from March 1st, the price rises by one each day
+fn contract(symbol: &str, expiry: NaiveDate, base: f64, volumes: &[f64]) -> ContractData {
+    let bars = volumes
+        .iter()
+        .enumerate()
+        .map(|(index, volume)| {
+            let price = base + index as f64 + 1.0;
+            Bar {
+                timestamp: at(index as u32 + 1),
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+                volume: *volume,
+            }
+        })
+        .collect();
+    ContractData::new(String::from(symbol), expiry, bars)
+}
+
+fn contracts() -> Vec<ContractData> {
+    // Passed in any order, sorted by expiry
+    vec![
+        contract("ESM24", date(6, 21), 110.0, &[10.0; 20]),
+        contract("ESH24", date(3, 15), 100.0, &[10.0; 14]),
+    ]
+}
+
+#[test]
+fn back_adjusted_series_rolls_days_before_expiry() {
+    let series = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5)).build(&contracts());
+
+    assert_eq!(series.bars.len(), 20);
+    assert_eq!(series.rolls.len(), 1);
+    let roll = &series.rolls[0];
+    assert_eq!(roll.timestamp, at(10));
+    assert_eq!(roll.from_symbol, "ESH24");
+    assert_eq!(roll.to_symbol, "ESM24");
+    assert_eq!(roll.from_price, 109.0);
+    assert_eq!(roll.to_price, 119.0);
+    assert_eq!(roll.exit_price, 119.0);
+    assert_eq!(roll.entry_price, 119.0);
+
+    // Earlier prices are shifted by the difference at the roll, the series has no gap
+    assert_eq!(series.bars[0].close, 111.0);
+    assert_eq!(series.bars[8].close, 119.0);
+    assert_eq!(series.bars[9].timestamp, at(10));
+    assert_eq!(series.bars[9].close, 120.0);
+    assert_eq!(series.bars[19].close, 130.0);
+}
+
+#[test]
+fn ratio_and_unadjusted_series() {
+    let ratio = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5))
+        .with_adjustment(AdjustmentMethod::RatioAdjusted)
+        .build(&contracts());
+    let factor = 119.0 / 109.0;
+    assert!((ratio.bars[0].close - 101.0 * factor).abs() < 1e-9);
+    assert!((ratio.bars[8].close - 119.0).abs() < 1e-9);
+    assert!((ratio.rolls[0].exit_price - 119.0).abs() < 1e-9);
+    assert_eq!(ratio.bars[9].close, 120.0);
+
+    let unadjusted = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5))
+        .with_adjustment(AdjustmentMethod::Unadjusted)
+        .build(&contracts());
+    assert_eq!(unadjusted.bars[8].close, 109.0);
+    assert_eq!(unadjusted.bars[9].close, 120.0);
+    assert_eq!(unadjusted.rolls[0].exit_price, 109.0);
+    assert_eq!(unadjusted.rolls[0].entry_price, 119.0);
+}
+
+#[test]
+fn rolls_the_day_after_the_volume_crossover() {
+    let mut front_volumes = [100.0; 14];
+    let mut next_volumes = [50.0; 20];
+    for volume in front_volumes.iter_mut().skip(5) {
+        *volume = 10.0;
+    }
+    for volume in next_volumes.iter_mut().skip(5) {
+        *volume = 200.0;
+    }
+    let contracts = vec![
+        contract("ESH24", date(3, 15), 100.0, &front_volumes),
+        contract("ESM24", date(6, 21), 110.0, &next_volumes),
+    ];
+
+    // Volume crosses on March 6th
+    let series = ContinuousFuturesModel::new(RollRule::VolumeCrossover).build(&contracts);
+    assert_eq!(series.rolls[0].timestamp, at(7));
+    assert_eq!(series.rolls[0].from_price, 106.0);
+    assert_eq!(series.rolls[0].to_price, 116.0);
+}
+
+#[test]
+fn rolls_on_open_interest_or_at_expiry() {
+    let front = contract("ESH24", date(3, 15), 100.0, &[1.0; 14]);
+    let next = contract("ESM24", date(6, 21), 110.0, &[1.0; 20]);
+
+    let front_open_interest: Vec<f64> = (0..14).map(|day| 1000.0 - 50.0 * day as f64).collect();
+    let next_open_interest: Vec<f64> = (0..20).map(|day| 500.0 + 50.0 * day as f64).collect();
+    let contracts = vec![
+        front.clone().with_open_interest(front_open_interest),
+        next.clone().with_open_interest(next_open_interest),
+    ];
+    // 1000 - 50d < 500 + 50d from day index 6, March 7th
+    let series = ContinuousFuturesModel::new(RollRule::OpenInterestCrossover).build(&contracts);
+    assert_eq!(series.rolls[0].timestamp, at(8));
+
+    // Without open interest the roll happens at the expiry, after the last bar of the front contract
+    let series = ContinuousFuturesModel::new(RollRule::OpenInterestCrossover).build(&[front, next]);
+    assert_eq!(series.rolls[0].timestamp, at(15));
+    assert_eq!(series.bars.len(), 20);
+}
+
+#[test]
+fn broker_rolls_open_trades() {
+    let series = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5))
+        .with_adjustment(AdjustmentMethod::Unadjusted)
+        .build(&contracts());
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.set_roll_schedule(1, series.rolls.clone());
+
+    broker.place_order(Order {
+        id: None,
+        related_id: None,
+        instrument: 1,
+        strategy_id: 1,
+        side: OrderSide::Buy,
+        order_type: OrderType::Market,
+        size: 2.0,
+    });
+    broker.simulate_fills(MarketData::Bar(series.bars[2]));
+    broker.simulate_fills(MarketData::Bar(series.bars[8]));
+    assert_eq!(broker.get_open_trades(1, 1).len(), 1);
+
+    broker.simulate_fills(MarketData::Bar(series.bars[9]));
+
+    let rolled = broker.get_trade_for_order(1).unwrap();
+    assert_eq!(rolled.entry_price, 103.0);
+    assert_eq!(rolled.exit_price, Some(109.0));
+    let open_trades = broker.get_open_trades(1, 1);
+    assert_eq!(open_trades.len(), 1);
+    assert_eq!(open_trades[0].size, 2.0);
+    assert_eq!(open_trades[0].entry_price, 119.0);
+}
+
+#[test]
+fn data_handler_replays_the_series() {
+    let series = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5)).build(&contracts());
+    let mut data_handler = ContinuousFuturesDataHandler::new(
+        series,
+        HistoricBarConsolidationModel::from_timeframes(Timeframe::Days(1), Timeframe::Days(1)),
+    );
+    data_handler.start().unwrap();
+    let mut data_feed = data_handler.get_data_feed();
+    let mut count = 0;
+    while let Some(market_data) = data_feed.poll().unwrap() {
+        assert!(matches!(market_data, MarketData::Bar(_)));
+        count += 1;
+    }
+    assert_eq!(count, 20);
+}
+
+#[test]
+fn broker_moves_exit_orders_to_the_rolled_trade_and_rolls_every_instrument() {
+    let series = ContinuousFuturesModel::new(RollRule::DaysBeforeExpiry(5))
+        .with_adjustment(AdjustmentMethod::Unadjusted)
+        .build(&contracts());
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.set_roll_schedule(1, series.rolls.clone());
+    broker.set_roll_schedule(2, series.rolls.clone());
+
+    let order = |instrument: u32, side: OrderSide, order_type: OrderType, related_id: Option<usize>| Order {
+        id: None,
+        related_id,
+        instrument,
+        strategy_id: 1,
+        side,
+        order_type,
+        size: 2.0,
+    };
+    broker.place_order(order(1, OrderSide::Buy, OrderType::Market, None));
+    broker.place_order(order(2, OrderSide::Buy, OrderType::Market, None));
+    broker.simulate_fills(MarketData::Bar(series.bars[2]));
+    let trade_id = broker.get_trade_for_order(1).unwrap().id;
+    let target = broker
+        .place_order(order(1, OrderSide::Sell, OrderType::Limit(121.0), Some(trade_id)))
+        .id
+        .unwrap();
+
+    broker.simulate_fills(MarketData::Bar(series.bars[9]));
+    assert_eq!(broker.get_open_trades(1, 2).len(), 1);
+    assert_eq!(broker.get_open_trades(1, 2)[0].entry_price, 119.0);
+    let rolled_id = broker.get_open_trades(1, 1)[0].id;
+    assert_ne!(rolled_id, trade_id);
+
+    // The target closes the new trade instead of reopening the old one
+    broker.simulate_fills(MarketData::Bar(series.bars[10]));
+    assert_eq!(broker.get_trade_for_order(target).unwrap().id, rolled_id);
+    assert!(broker.get_open_trades(1, 1).is_empty());
+    assert_eq!(broker.get_trade_for_order(1).unwrap().exit_price, Some(109.0));
+}
@@ -0,0 +1,167 @@
+use certus_bt::broker::BacktestingBroker;
+use certus_bt::corporate_actions::{CorporateAction, CorporateActionKind, CorporateActions};
+use certus_bt::csv_data_handler::{CSVDataHandler, GenericCSVRowParser};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_core::broker::Broker;
+use certus_core::core::{Order, OrderSide, OrderType};
+use certus_core::data::{Bar, DataHandler, DataHandlerError, MarketData, Timestamp};
+use certus_core::timeframe::Timeframe;
+use chrono::NaiveDate;
+
+mod common;
+use common::order;
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 8, day).unwrap()
+}
+
+fn at(day: u32) -> Timestamp {
+    Timestamp::from_naive_utc(date(day).and_hms_opt(14, 30, 0).unwrap())
+}
+
+fn create_bar(day: u32, price: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: at(day),
+        open: price,
+        high: price + 2.0,
+        low: price - 2.0,
+        close: price,
+        volume: 100.0,
+    })
+}
+
+fn close(market_data: &MarketData) -> f64 {
+    match market_data {
+        MarketData::Bar(bar) => bar.close,
+        MarketData::Tick(tick) => tick.price,
+        MarketData::Quote(quote) => quote.mid(),
+        MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar().close,
+    }
+}
+
+fn actions() -> CorporateActions {
+    CorporateActions::new(vec![
+        CorporateAction {
+            ex_date: date(31),
+            kind: CorporateActionKind::Split(4.0),
+        },
+        CorporateAction {
+            ex_date: date(7),
+            kind: CorporateActionKind::Dividend(0.8),
+        },
+    ])
+}
+
+#[test]
+fn historical_prices_are_adjusted() {
+    let mut data = vec![create_bar(6, 400.0), create_bar(7, 399.2), create_bar(28, 500.0), create_bar(31, 125.0)];
+    actions().adjust(&mut data, false);
+
+    assert_eq!(close(&data[0]), 100.0);
+    assert_eq!(close(&data[2]), 125.0);
+    assert_eq!(close(&data[3]), 125.0);
+    let MarketData::Bar(bar) = data[2] else {
+        unreachable!()
+    };
+    assert_eq!(bar.high, 125.5);
+    assert_eq!(bar.volume, 400.0);
+
+    let mut data = vec![create_bar(6, 400.0), create_bar(7, 399.2)];
+    actions().adjust(&mut data, true);
+    assert!((close(&data[0]) - 400.0 * 0.998 / 4.0).abs() < 1e-9);
+    assert_eq!(close(&data[1]), 399.2 / 4.0);
+}
+
+#[test]
+fn actions_are_read_from_csv() {
+    let path = std::env::temp_dir().join(format!("certus_actions_{}.csv", std::process::id()));
+    std::fs::write(&path, "ex_date,type,value\n2020-08-31,split,4\n2020-08-07,Dividend,0.82\n").unwrap();
+    let actions = CorporateActions::from_csv(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(actions.actions.len(), 2);
+    assert_eq!(actions.actions[0].kind, CorporateActionKind::Dividend(0.82));
+    assert_eq!(actions.actions[1].ex_date, date(31));
+
+    std::fs::write(&path, "ex_date,type,value\n2020-08-31,merger,1\n").unwrap();
+    let error = CorporateActions::from_csv(path.to_str().unwrap()).unwrap_err();
+    std::fs::remove_file(path).unwrap();
+
+    let DataHandlerError::Parse { line, column, .. } = error else {
+        panic!("expected parse error");
+    };
+    assert_eq!(line, Some(2));
+    assert_eq!(column.as_deref(), Some("type"));
+}
+
+#[test]
+fn broker_applies_splits_to_trades_and_orders() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.set_corporate_actions(1, actions());
+
+    broker.place_order(order(OrderSide::Buy, OrderType::Market, 10.0));
+    broker.simulate_fills(create_bar(28, 500.0));
+    let trade_id = broker.get_trade_for_order(1).unwrap().id;
+    broker.place_order(Order {
+        related_id: Some(trade_id),
+        ..order(OrderSide::Sell, OrderType::Limit(520.0), 10.0)
+    });
+
+    broker.simulate_fills(create_bar(31, 125.0));
+    let trade = broker.get_trade_for_order(1).unwrap();
+    assert_eq!(trade.size, 40.0);
+    assert_eq!(trade.entry_price, 125.0);
+    assert_eq!(broker.unfilled_orders_len(), 1);
+
+    // The limit is now 130 for 40 shares
+    broker.simulate_fills(create_bar(31, 129.0));
+    let trade = broker.get_trade_for_order(1).unwrap();
+    assert_eq!(trade.size, 0.0);
+    assert_eq!(trade.exit_price, Some(130.0));
+    assert_eq!(broker.get_fill(2).unwrap().size, 40.0);
+}
+
+#[test]
+fn broker_pays_dividends_on_the_ex_date() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.set_corporate_actions(1, actions());
+
+    broker.place_order(order(OrderSide::Buy, OrderType::Market, 10.0));
+    broker.place_order(Order {
+        strategy_id: 2,
+        ..order(OrderSide::Sell, OrderType::Market, 5.0)
+    });
+    broker.simulate_fills(create_bar(6, 400.0));
+    broker.simulate_fills(create_bar(7, 399.2));
+    broker.simulate_fills(create_bar(10, 399.0));
+
+    let dividends = broker.dividends();
+    assert_eq!(dividends.len(), 2);
+    assert_eq!(dividends[0].strategy_id, 1);
+    assert_eq!(dividends[0].amount, 8.0);
+    assert_eq!(dividends[1].amount, -4.0);
+    assert_eq!(dividends[1].timestamp, at(7));
+    assert_eq!(broker.balance(), 100_004.0);
+}
+
+#[test]
+fn csv_data_handler_adjusts_before_consolidation() {
+    let path = std::env::temp_dir().join(format!("certus_adjusted_{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        "Date,Open,High,Low,Close,Volume\n2020-08-28,500,502,498,500,100\n2020-08-31,125,126,124,125,400\n",
+    )
+    .unwrap();
+
+    let mut data_handler = CSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(GenericCSVRowParser::yahoo(chrono_tz::UTC)),
+        HistoricBarConsolidationModel::from_timeframes(Timeframe::Days(1), Timeframe::Days(1)),
+    )
+    .with_corporate_actions(actions(), false);
+    data_handler.start().unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(data_handler.data.len(), 2);
+    assert_eq!(close(&data_handler.data[0]), 125.0);
+    assert_eq!(close(&data_handler.data[1]), 125.0);
+}
@@ -0,0 +1,497 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use certus_bt::cleaning::{CleaningAction, CleaningConfig, DataIssueKind};
+use certus_bt::csv_data_handler::{
+    CSVColumn, CSVDataHandler, CSVFields, CSVRowParser, CSVTimestamp, EpochUnit, GenericCSVRowParser,
+    StreamingCSVDataHandler, parse_local_timestamp,
+};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_core::data::{Bar, DataHandler, DataHandlerError, ErrorPolicy, MarketData, Tick, Timestamp};
+use chrono::NaiveDate;
+use chrono_tz::America::Chicago;
+use csv::StringRecord;
+
+/// Rows of "seconds,open,high,low,close,volume" or "seconds,price,size"
+struct TestRowParser;
+
+impl CSVRowParser for TestRowParser {
+    fn parse_row(&mut self, row: StringRecord) -> Result<MarketData, Box<dyn Error>> {
+        let timestamp = Timestamp::from_seconds(row[0].parse::<i64>()?);
+        if row.len() == 3 {
+            return Ok(MarketData::Tick(Tick {
+                timestamp,
+                price: row[1].parse::<f64>()?,
+                size: row[2].parse::<f64>()?,
+            }));
+        }
+        Ok(MarketData::Bar(Bar {
+            timestamp,
+            open: row[1].parse::<f64>()?,
+            high: row[2].parse::<f64>()?,
+            low: row[3].parse::<f64>()?,
+            close: row[4].parse::<f64>()?,
+            volume: row[5].parse::<f64>()?,
+        }))
+    }
+}
+
+fn write_csv(name: &str, header: &str, rows: &[String]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certus_{}_{}.csv", name, std::process::id()));
+    let mut content = format!("{}\n", header);
+    for row in rows {
+        content.push_str(row);
+        content.push('\n');
+    }
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn poll_all(data_handler: &mut dyn DataHandler) -> Vec<Bar> {
+    data_handler.start().unwrap();
+    let mut data_feed = data_handler.get_data_feed();
+    let mut bars = Vec::new();
+    while let Some(market_data) = data_feed.poll().unwrap() {
+        match market_data {
+            MarketData::Bar(bar) => bars.push(bar),
+            other => panic!("expected bar, got {:?}", other),
+        }
+    }
+    bars
+}
+
+fn assert_same_bars(left: &[Bar], right: &[Bar]) {
+    assert_eq!(left.len(), right.len());
+    for (left, right) in left.iter().zip(right) {
+        assert_eq!(left.timestamp, right.timestamp);
+        assert_eq!(left.open, right.open);
+        assert_eq!(left.high, right.high);
+        assert_eq!(left.low, right.low);
+        assert_eq!(left.close, right.close);
+        assert_eq!(left.volume, right.volume);
+    }
+}
+
+#[test]
+fn streaming_handler_matches_in_memory_handler() {
+    let rows: Vec<String> = (0..95)
+        .map(|minute| {
+            let price = 100.0 + (minute % 7) as f64;
+            format!("{},{},{},{},{},{}", minute * 60, price, price + 1.0, price - 1.0, price + 0.5, minute)
+        })
+        .collect();
+    let path = write_csv("streaming_bars", "Time,Open,High,Low,Close,Volume", &rows);
+    let file_path = path.to_str().unwrap().to_string();
+
+    let mut in_memory = CSVDataHandler::new(
+        file_path.clone(),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 30),
+    );
+    let mut streaming = StreamingCSVDataHandler::new(
+        file_path,
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 30),
+    );
+
+    let expected = poll_all(&mut in_memory);
+    let bars = poll_all(&mut streaming);
+    std::fs::remove_file(path).unwrap();
+
+    // 3 full bars and the partial bar at the end of the file
+    assert_eq!(bars.len(), 4);
+    assert_eq!(bars[3].volume, (90..95).sum::<i32>() as f64);
+    assert_same_bars(&bars, &expected);
+}
+
+#[test]
+fn streaming_handler_aggregates_ticks() {
+    let rows: Vec<String> = (0..10)
+        .map(|second| format!("{},{},1", second * 45, 100.0 + second as f64))
+        .collect();
+    let path = write_csv("streaming_ticks", "Time,Price,Size", &rows);
+    let file_path = path.to_str().unwrap().to_string();
+
+    let mut in_memory = CSVDataHandler::new(
+        file_path.clone(),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 5),
+    );
+    let mut streaming = StreamingCSVDataHandler::new(
+        file_path,
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 5),
+    );
+
+    let expected = poll_all(&mut in_memory);
+    let bars = poll_all(&mut streaming);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0].open, 100.0);
+    assert_eq!(bars[0].close, 106.0);
+    assert_eq!(bars[1].volume, 3.0);
+    assert_same_bars(&bars, &expected);
+}
+
+#[test]
+fn streaming_handler_fails_to_start_without_file() {
+    let mut streaming = StreamingCSVDataHandler::new(
+        String::from("./does-not-exist.csv"),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 5),
+    );
+
+    assert_eq!(
+        streaming.start(),
+        Err(DataHandlerError::FileNotFound {
+            path: String::from("./does-not-exist.csv"),
+        })
+    );
+    assert!(streaming.get_data_feed().poll().unwrap().is_none());
+}
+
+fn bad_rows() -> Vec<String> {
+    vec![
+        String::from("60,100,101,99,100,1"),
+        String::from("120,100,99,101,100,1"),
+        String::from("30,100,101,99,100,1"),
+        String::from("180,100,101,99,100,1"),
+    ]
+}
+
+#[test]
+fn invalid_rows_abort_by_default() {
+    let path = write_csv("abort_rows", "Time,Open,High,Low,Close,Volume", &bad_rows());
+    let mut data_handler = CSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 1),
+    );
+    let result = data_handler.start();
+    std::fs::remove_file(path).unwrap();
+
+    let error = result.unwrap_err();
+    assert!(matches!(error, DataHandlerError::InvalidOhlc { line: Some(3), .. }));
+}
+
+#[test]
+fn invalid_rows_are_skipped_and_reported() {
+    let path = write_csv("skip_rows", "Time,Open,High,Low,Close,Volume", &bad_rows());
+    let file_path = path.to_str().unwrap().to_string();
+
+    let mut in_memory = CSVDataHandler::new(
+        file_path.clone(),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_error_policy(ErrorPolicy::SkipAndReport);
+    let mut streaming = StreamingCSVDataHandler::new(
+        file_path,
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_error_policy(ErrorPolicy::SkipAndReport);
+
+    let expected = poll_all(&mut in_memory);
+    let bars = poll_all(&mut streaming);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[1].timestamp, Timestamp::from_seconds(180));
+    assert_same_bars(&bars, &expected);
+    for skipped in [in_memory.skipped_rows(), streaming.skipped_rows()] {
+        assert_eq!(skipped.len(), 2);
+        assert!(matches!(skipped[0], DataHandlerError::InvalidOhlc { line: Some(3), .. }));
+        assert_eq!(
+            skipped[1],
+            DataHandlerError::OutOfOrder {
+                line: Some(4),
+                timestamp: Timestamp::from_seconds(30),
+                previous: Timestamp::from_seconds(60),
+            }
+        );
+    }
+}
+
+#[test]
+fn cleaner_replaces_validation_and_reports_quality() {
+    let path = write_csv("cleaned_rows", "Time,Open,High,Low,Close,Volume", &bad_rows());
+    let mut data_handler = StreamingCSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_cleaner(CleaningConfig::default());
+    let bars = poll_all(&mut data_handler);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(bars.len(), 2);
+    assert!(data_handler.skipped_rows().is_empty());
+    let report = data_handler.quality_report().unwrap();
+    assert_eq!(report.rows, 4);
+    assert_eq!(report.dropped, 2);
+    assert_eq!(report.count(DataIssueKind::InvalidPrice), 1);
+    assert_eq!(report.count(DataIssueKind::OutOfOrder), 1);
+}
+
+#[test]
+fn parse_errors_report_line_and_column() {
+    let rows = [
+        String::from("01/02/2015,09:00,100,101,99,100,0,0,1"),
+        String::from("01/02/2015,09:01,abc,101,99,100,0,0,1"),
+    ];
+    let path = write_csv("parse_error", "Date,Time,Open,High,Low,Close,Up,Down,Vol", &rows);
+    let mut data_handler = StreamingCSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(GenericCSVRowParser::tradestation(Chicago)),
+        HistoricBarConsolidationModel::new(1, 1),
+    );
+    data_handler.start().unwrap();
+    let mut data_feed = data_handler.get_data_feed();
+    let first = data_feed.poll();
+    let error = data_feed.poll().unwrap_err();
+    drop(data_feed);
+    std::fs::remove_file(path).unwrap();
+
+    assert!(first.is_ok());
+    let DataHandlerError::Parse { line, column, .. } = &error else {
+        panic!("expected parse error, got {:?}", error);
+    };
+    assert_eq!(*line, Some(3));
+    assert_eq!(column.as_deref(), Some("#2"));
+    assert!(error.to_string().starts_with("line 3: "));
+}
+
+#[test]
+fn parses_dates_in_the_declared_time_zone() {
+    let winter = parse_local_timestamp("01/02/2015 09:01", "%m/%d/%Y %H:%M", &Chicago).unwrap();
+    let summer = parse_local_timestamp("07/01/2015 09:01", "%m/%d/%Y %H:%M", &Chicago).unwrap();
+
+    let utc = |month: u32, day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(2015, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 1, 0)
+            .unwrap()
+    };
+    assert_eq!(winter.naive_utc(), utc(1, 2, 15));
+    assert_eq!(summer.naive_utc(), utc(7, 1, 14));
+}
+
+#[test]
+fn invalid_dates_are_an_error() {
+    assert!(parse_local_timestamp("2015-01-02", "%m/%d/%Y %H:%M", &Chicago).is_err());
+}
+
+fn parse(parser: &mut GenericCSVRowParser, headers: Option<&[&str]>, row: &[&str]) -> Result<MarketData, Box<dyn Error>> {
+    if let Some(headers) = headers {
+        parser.set_headers(&StringRecord::from(headers.to_vec()));
+    }
+    parser.parse_row(StringRecord::from(row.to_vec()))
+}
+
+fn parse_bar(parser: &mut GenericCSVRowParser, headers: Option<&[&str]>, row: &[&str]) -> Bar {
+    match parse(parser, headers, row).unwrap() {
+        MarketData::Bar(bar) => bar,
+        other => panic!("expected bar, got {:?}", other),
+    }
+}
+
+fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> Timestamp {
+    Timestamp::from_naive_utc(
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap(),
+    )
+}
+
+#[test]
+fn tradestation_preset() {
+    let mut parser = GenericCSVRowParser::tradestation(Chicago);
+    let bar = parse_bar(
+        &mut parser,
+        None,
+        &["01/02/2015", "09:01", "2050.25", "2051", "2049.5", "2050.75", "10", "12", "22"],
+    );
+
+    assert!(parser.has_headers());
+    assert_eq!(bar.timestamp, utc(2015, 1, 2, 15, 1));
+    assert_eq!(bar.open, 2050.25);
+    assert_eq!(bar.high, 2051.0);
+    assert_eq!(bar.low, 2049.5);
+    assert_eq!(bar.close, 2050.75);
+    assert_eq!(bar.volume, 22.0);
+}
+
+#[test]
+fn ninjatrader_preset() {
+    let mut parser = GenericCSVRowParser::ninjatrader(Chicago);
+    let bar = parse_bar(&mut parser, None, &["20150702 090100", "2050.25", "2051", "2049.5", "2050.75", "22"]);
+
+    assert_eq!(parser.delimiter(), b';');
+    assert!(!parser.has_headers());
+    assert_eq!(bar.timestamp, utc(2015, 7, 2, 14, 1));
+    assert_eq!(bar.close, 2050.75);
+    assert_eq!(bar.volume, 22.0);
+}
+
+#[test]
+fn metatrader_preset() {
+    let mut parser = GenericCSVRowParser::metatrader(chrono_tz::UTC);
+    let bar = parse_bar(&mut parser, None, &["2015.01.02", "09:01", "1.2050", "1.2060", "1.2040", "1.2055", "22"]);
+
+    assert_eq!(bar.timestamp, utc(2015, 1, 2, 9, 1));
+    assert_eq!(bar.high, 1.206);
+    assert_eq!(bar.volume, 22.0);
+}
+
+#[test]
+fn yahoo_preset_maps_columns_by_header() {
+    let mut parser = GenericCSVRowParser::yahoo(chrono_tz::America::New_York);
+    let headers = ["Date", "Open", "High", "Low", "Close", "Adj Close", "Volume"];
+    let bar = parse_bar(
+        &mut parser,
+        Some(&headers),
+        &["2024-01-02", "187.15", "188.44", "183.89", "185.64", "185.40", "82488700"],
+    );
+
+    assert_eq!(bar.timestamp, utc(2024, 1, 2, 5, 0));
+    assert_eq!(bar.open, 187.15);
+    assert_eq!(bar.close, 185.64);
+    assert_eq!(bar.volume, 82488700.0);
+}
+
+#[test]
+fn epoch_ticks_without_size() {
+    let mut parser = GenericCSVRowParser::new(
+        CSVTimestamp::Epoch {
+            column: "time".into(),
+            unit: EpochUnit::Milliseconds,
+        },
+        CSVFields::Ticks {
+            price: "price".into(),
+            size: None,
+        },
+    );
+    let tick = match parse(&mut parser, Some(&["price", "time"]), &["100.5", "1420189260000"]).unwrap() {
+        MarketData::Tick(tick) => tick,
+        other => panic!("expected tick, got {:?}", other),
+    };
+
+    assert_eq!(tick.timestamp, utc(2015, 1, 2, 9, 1));
+    assert_eq!(tick.price, 100.5);
+    assert_eq!(tick.size, 0.0);
+}
+
+#[test]
+fn epoch_timestamps_out_of_range_are_row_errors() {
+    // Milliseconds read as seconds
+    let mut parser = GenericCSVRowParser::new(
+        CSVTimestamp::Epoch {
+            column: "time".into(),
+            unit: EpochUnit::Seconds,
+        },
+        CSVFields::Ticks {
+            price: "price".into(),
+            size: None,
+        },
+    );
+    let error = parse(&mut parser, Some(&["time", "price"]), &["1420189260000", "100.5"]).unwrap_err();
+    assert!(error.to_string().contains("timestamp 1420189260000 is out of range"));
+    assert!(error.to_string().contains("time"));
+
+    let rows = [String::from("1420189260000,100.5"), String::from("1420189320,101")];
+    let path = write_csv("epoch_range", "time,price", &rows);
+    let mut data_handler = StreamingCSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(parser),
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_error_policy(ErrorPolicy::SkipAndReport);
+    let bars = poll_all(&mut data_handler);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(bars.len(), 1);
+    assert_eq!(bars[0].timestamp, utc(2015, 1, 2, 9, 2));
+    assert!(matches!(data_handler.skipped_rows()[0], DataHandlerError::Parse { line: Some(2), .. }));
+}
+
+#[test]
+fn epoch_quotes() {
+    let mut parser = GenericCSVRowParser::new(
+        CSVTimestamp::Epoch {
+            column: "time".into(),
+            unit: EpochUnit::Milliseconds,
+        },
+        CSVFields::Quotes {
+            bid: "bid".into(),
+            ask: "ask".into(),
+            bid_size: Some("bid_size".into()),
+            ask_size: None,
+        },
+    );
+    let headers = ["time", "bid", "bid_size", "ask"];
+    let quote = match parse(&mut parser, Some(&headers), &["1420189260000", "100.25", "3", "100.5"]).unwrap() {
+        MarketData::Quote(quote) => quote,
+        other => panic!("expected quote, got {:?}", other),
+    };
+
+    assert_eq!(quote.timestamp, utc(2015, 1, 2, 9, 1));
+    assert_eq!(quote.bid, 100.25);
+    assert_eq!(quote.bid_size, 3.0);
+    assert_eq!(quote.ask, 100.5);
+    assert_eq!(quote.ask_size, 0.0);
+}
+
+#[test]
+fn invalid_rows_are_reported() {
+    let mut parser = GenericCSVRowParser::yahoo(chrono_tz::UTC);
+    let headers = ["Date", "Open", "High", "Low", "Close"];
+    let missing = parse(&mut parser, Some(&headers), &["2024-01-02", "1", "2", "0.5", "1.5"]);
+    assert!(missing.unwrap_err().to_string().contains("Volume"));
+
+    let mut parser = GenericCSVRowParser::tradestation(Chicago);
+    let invalid = parse(&mut parser, None, &["2015-01-02", "09:01", "1", "1", "1", "1", "0", "0", "1"]);
+    assert!(invalid.is_err());
+    let invalid = parse(&mut parser, None, &["01/02/2015", "09:01", "abc", "1", "1", "1", "0", "0", "1"]);
+    assert!(invalid.unwrap_err().to_string().contains(&CSVColumn::Index(2).to_string()));
+}
+
+#[test]
+#[should_panic(expected = "out of order rows can't be consolidated")]
+fn cleaner_must_not_pass_out_of_order_rows_to_consolidation() {
+    let config = CleaningConfig {
+        out_of_order: CleaningAction::Report,
+        ..CleaningConfig::default()
+    };
+    let _ = StreamingCSVDataHandler::new(
+        String::from("./unused.csv"),
+        Box::new(TestRowParser),
+        HistoricBarConsolidationModel::new(1, 1),
+    )
+    .with_cleaner(config);
+}
+
+#[test]
+fn data_handler_uses_parser_delimiter() {
+    let rows = [
+        "20150102 090000;100;101;99;100.5;10",
+        "20150102 090100;100.5;102;100;101.5;20",
+    ];
+    let path = std::env::temp_dir().join(format!("certus_ninjatrader_{}.csv", std::process::id()));
+    std::fs::write(&path, rows.join("\n")).unwrap();
+
+    let mut data_handler = StreamingCSVDataHandler::new(
+        path.to_str().unwrap().to_string(),
+        Box::new(GenericCSVRowParser::ninjatrader(chrono_tz::UTC)),
+        HistoricBarConsolidationModel::new(1, 2),
+    );
+    let bars = poll_all(&mut data_handler);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(bars.len(), 1);
+    assert_eq!(bars[0].timestamp, utc(2015, 1, 2, 9, 0));
+    assert_eq!(bars[0].high, 102.0);
+    assert_eq!(bars[0].volume, 30.0);
+}
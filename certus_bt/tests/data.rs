@@ -1,6 +1,8 @@
 use certus_bt::data::HistoricBarConsolidationModel;
-use certus_core::data::{Bar, MarketData};
-use chrono::{NaiveDate, NaiveDateTime};
+use certus_core::data::{Bar, DataHandlerError, MarketData, Quote, Tick, Timestamp};
+use certus_core::session::{SessionType, TradingCalendar};
+use certus_core::timeframe::Timeframe;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 
 fn create_bar(
     date: NaiveDateTime,
@@ -11,7 +13,7 @@ fn create_bar(
     volume: f64,
 ) -> MarketData {
     MarketData::Bar(Bar {
-        date,
+        timestamp: Timestamp::from_naive_utc(date),
         open,
         high,
         low,
@@ -20,6 +22,14 @@ fn create_bar(
     })
 }
 
+fn create_tick(date: NaiveDateTime, price: f64, size: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_naive_utc(date),
+        price,
+        size,
+    })
+}
+
 #[test]
 fn test_normal_consolidation_1_to_5_min() {
     let model = HistoricBarConsolidationModel::new(1, 5);
@@ -80,11 +90,11 @@ fn test_normal_consolidation_1_to_5_min() {
             1400.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 1);
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -137,11 +147,11 @@ fn test_consolidation_with_missing_bars() {
             1400.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 1);
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -216,11 +226,11 @@ fn test_consolidation_late_start() {
             1600.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 2); // Buckets at 9:0 and 9:5
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -234,7 +244,7 @@ fn test_consolidation_late_start() {
     }
     if let MarketData::Bar(bar) = &consolidated[1] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 5, 0)
@@ -332,12 +342,12 @@ fn test_consolidation_multiple_buckets() {
             1600.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 2);
     // First bar
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -349,7 +359,7 @@ fn test_consolidation_multiple_buckets() {
     // Second bar
     if let MarketData::Bar(bar) = &consolidated[1] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 5, 0)
@@ -364,7 +374,7 @@ fn test_consolidation_multiple_buckets() {
 fn test_consolidation_empty_data() {
     let model = HistoricBarConsolidationModel::new(1, 5);
     let data: Vec<MarketData> = vec![];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 0);
 }
 
@@ -382,11 +392,11 @@ fn test_consolidation_single_bar() {
         100.5,
         1000.0,
     )];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 1);
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -438,11 +448,11 @@ fn test_consolidation_unordered_bars() {
             1200.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 1);
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -492,11 +502,11 @@ fn test_consolidation_different_timeframes() {
             1200.0,
         ),
     ];
-    let consolidated = model.consolidate_bars(&data);
+    let consolidated = model.consolidate_bars(&data).unwrap();
     assert_eq!(consolidated.len(), 1);
     if let MarketData::Bar(bar) = &consolidated[0] {
         assert_eq!(
-            bar.date,
+            bar.timestamp.naive_utc(),
             NaiveDate::from_ymd_opt(2023, 1, 1)
                 .unwrap()
                 .and_hms_opt(9, 0, 0)
@@ -515,3 +525,218 @@ fn test_consolidation_different_timeframes() {
 fn test_invalid_timeframe_multiples() {
     HistoricBarConsolidationModel::new(5, 7);
 }
+
+fn create_quote(date: NaiveDateTime, bid: f64, ask: f64) -> MarketData {
+    MarketData::Quote(Quote {
+        timestamp: Timestamp::from_naive_utc(date),
+        bid,
+        bid_size: 1.0,
+        ask,
+        ask_size: 2.0,
+    })
+}
+
+#[test]
+fn test_consolidation_of_quotes_into_quote_bars() {
+    let model = HistoricBarConsolidationModel::new(1, 5);
+    let date = |minute: u32, second: u32| {
+        NaiveDate::from_ymd_opt(2023, 1, 1)
+            .unwrap()
+            .and_hms_opt(9, minute, second)
+            .unwrap()
+    };
+    let data = vec![
+        create_quote(date(0, 1), 99.9, 100.1),
+        create_quote(date(2, 30), 100.4, 100.5),
+        create_quote(date(4, 59), 99.5, 99.8),
+        create_quote(date(5, 0), 99.6, 99.7),
+    ];
+
+    let consolidated = model.consolidate_bars(&data).unwrap();
+    assert_eq!(consolidated.len(), 2);
+
+    let MarketData::QuoteBar(first) = consolidated[0] else {
+        panic!("expected quote bar");
+    };
+    assert_eq!(first.timestamp.naive_utc(), date(0, 0));
+    assert_eq!(first.bid_open, 99.9);
+    assert_eq!(first.bid_high, 100.4);
+    assert_eq!(first.bid_low, 99.5);
+    assert_eq!(first.bid_close, 99.5);
+    assert_eq!(first.ask_high, 100.5);
+    assert_eq!(first.ask_low, 99.8);
+    assert_eq!(first.ask_size, 2.0);
+
+    // Consolidating the quote bars again merges their bid and ask prices
+    let daily = HistoricBarConsolidationModel::new(5, 10).consolidate_bars(&consolidated).unwrap();
+    assert_eq!(daily.len(), 1);
+    let MarketData::QuoteBar(merged) = daily[0] else {
+        panic!("expected quote bar");
+    };
+    assert_eq!(merged.bid_open, 99.9);
+    assert_eq!(merged.bid_close, 99.6);
+    assert_eq!(merged.ask_close, 99.7);
+    assert_eq!(merged.ask_high, 100.5);
+
+    let mut streaming = model.streaming();
+    let mut streamed: Vec<MarketData> = data.iter().flat_map(|quote| streaming.update(*quote)).collect();
+    streamed.extend(streaming.flush());
+    assert_eq!(streamed.len(), 2);
+    assert!(matches!(streamed[1], MarketData::QuoteBar(quote_bar) if quote_bar.bid_open == 99.6));
+}
+
+#[test]
+fn test_consolidation_of_ticks_into_bars() {
+    let model = HistoricBarConsolidationModel::new(1, 5);
+    let date = |minute: u32, second: u32| {
+        NaiveDate::from_ymd_opt(2023, 1, 1)
+            .unwrap()
+            .and_hms_opt(9, minute, second)
+            .unwrap()
+    };
+    let data = vec![
+        create_tick(date(0, 1), 100.0, 1.0),
+        create_tick(date(2, 30), 102.0, 2.0),
+        create_tick(date(4, 59), 99.0, 3.0),
+        create_tick(date(5, 0), 99.5, 4.0),
+        create_tick(date(7, 15), 98.0, 5.0),
+    ];
+
+    let consolidated = model.consolidate_bars(&data).unwrap();
+    assert_eq!(consolidated.len(), 2);
+
+    let MarketData::Bar(first) = consolidated[0] else {
+        panic!("expected bar");
+    };
+    assert_eq!(first.timestamp.naive_utc(), date(0, 0));
+    assert_eq!(first.open, 100.0);
+    assert_eq!(first.high, 102.0);
+    assert_eq!(first.low, 99.0);
+    assert_eq!(first.close, 99.0);
+    assert_eq!(first.volume, 6.0);
+
+    let MarketData::Bar(second) = consolidated[1] else {
+        panic!("expected bar");
+    };
+    assert_eq!(second.timestamp.naive_utc(), date(5, 0));
+    assert_eq!(second.open, 99.5);
+    assert_eq!(second.close, 98.0);
+    assert_eq!(second.volume, 9.0);
+}
+
+#[test]
+fn test_consolidation_of_mixed_data_fails() {
+    let model = HistoricBarConsolidationModel::new(1, 5);
+    let date = |minute: u32| NaiveDate::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(9, minute, 0).unwrap();
+
+    let ticks_then_bar = vec![
+        create_tick(date(0), 100.0, 1.0),
+        create_bar(date(1), 100.0, 101.0, 99.0, 100.5, 10.0),
+    ];
+    assert_eq!(
+        model.consolidate_bars(&ticks_then_bar).unwrap_err(),
+        DataHandlerError::MixedData {
+            timestamp: Timestamp::from_naive_utc(date(1)),
+            expected: String::from("ticks"),
+            found: String::from("a bar"),
+        }
+    );
+
+    let bars_then_quote = vec![
+        create_bar(date(0), 100.0, 101.0, 99.0, 100.5, 10.0),
+        create_quote(date(1), 100.0, 100.5),
+    ];
+    assert!(matches!(
+        model.consolidate_bars(&bars_then_quote),
+        Err(DataHandlerError::MixedData { .. })
+    ));
+}
+
+#[test]
+fn test_consolidation_to_240_minutes() {
+    let model = HistoricBarConsolidationModel::new(60, 240);
+    let date = |hour: u32| {
+        NaiveDate::from_ymd_opt(2023, 1, 2)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    };
+    let data: Vec<MarketData> = (2..7)
+        .map(|hour| create_bar(date(hour), 100.0, 101.0, 99.0, 100.0 + hour as f64, 10.0))
+        .collect();
+
+    let consolidated = model.consolidate_bars(&data).unwrap();
+    let dates: Vec<NaiveDateTime> = consolidated
+        .iter()
+        .map(|d| match d {
+            MarketData::Bar(bar) => bar.timestamp.naive_utc(),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(dates, vec![date(0), date(4)]);
+}
+
+#[test]
+fn test_daily_consolidation_aligned_to_session_open() {
+    let model = HistoricBarConsolidationModel::from_timeframes(Timeframe::Minutes(60), Timeframe::Days(1))
+        .with_session_open(NaiveTime::from_hms_opt(17, 0, 0).unwrap());
+    let date = |day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(2023, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    };
+    let data = vec![
+        create_bar(date(2, 17), 100.0, 102.0, 99.0, 101.0, 10.0),
+        create_bar(date(3, 9), 101.0, 104.0, 100.0, 103.0, 20.0),
+        create_bar(date(3, 16), 103.0, 103.5, 98.0, 99.0, 30.0),
+        create_bar(date(3, 17), 99.0, 100.0, 97.0, 98.0, 40.0),
+    ];
+
+    let consolidated = model.consolidate_bars(&data).unwrap();
+    assert_eq!(consolidated.len(), 2);
+    let MarketData::Bar(first) = consolidated[0] else {
+        panic!("expected bar");
+    };
+    assert_eq!(first.timestamp.naive_utc(), date(2, 17));
+    assert_eq!(first.open, 100.0);
+    assert_eq!(first.high, 104.0);
+    assert_eq!(first.low, 98.0);
+    assert_eq!(first.close, 99.0);
+    assert_eq!(first.volume, 60.0);
+    let MarketData::Bar(second) = consolidated[1] else {
+        panic!("expected bar");
+    };
+    assert_eq!(second.timestamp.naive_utc(), date(3, 17));
+}
+
+#[test]
+fn test_consolidation_drops_bars_outside_session() {
+    let model = HistoricBarConsolidationModel::new(30, 60)
+        .with_calendar(TradingCalendar::us_equities(), SessionType::Regular);
+    // Session hours are New York time
+    let date = |hour: u32, minute: u32| {
+        let local = NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        Timestamp::from_local(local, &chrono_tz::America::New_York).naive_utc()
+    };
+    let data = vec![
+        create_bar(date(9, 0), 90.0, 90.0, 90.0, 90.0, 10.0),
+        create_bar(date(9, 30), 100.0, 101.0, 99.0, 100.5, 10.0),
+        create_bar(date(10, 0), 100.5, 102.0, 100.0, 101.5, 10.0),
+        create_bar(date(16, 0), 110.0, 110.0, 110.0, 110.0, 10.0),
+    ];
+
+    let consolidated = model.consolidate_bars(&data).unwrap();
+    assert_eq!(consolidated.len(), 1);
+    let MarketData::Bar(first) = consolidated[0] else {
+        panic!("expected bar");
+    };
+    // Hourly buckets are aligned to the 09:30 open
+    assert_eq!(first.timestamp.naive_utc(), date(9, 30));
+    assert_eq!(first.open, 100.0);
+    assert_eq!(first.close, 101.5);
+    assert_eq!(first.volume, 20.0);
+}
@@ -0,0 +1,243 @@
+use certus_bt::broker::BacktestingBroker;
+use certus_bt::csv_data_handler::{CSVDataHandler, GenericCSVRowParser};
+use certus_bt::data::HistoricBarConsolidationModel;
+use certus_bt::engine::{BacktestingEngine, BacktestingExecutionEngine};
+use certus_core::broker::Broker;
+use certus_core::consolidator::BarConsolidator;
+use certus_core::data::{Bar, DataFeed, DataHandler, DataHandlerError, MarketData, Timestamp};
+use certus_core::engine::Engine;
+use certus_core::indicator::{Indicator, MovingAverage};
+use certus_core::strategy::{
+    IndicatorHandle, MarketDataReceiver, Strategy, StrategyBase, StrategyIndicators,
+    StrategyTimeframes, TimeframeHandle,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+mod common;
+use common::tick;
+
+struct VecDataHandler {
+    data: Vec<MarketData>,
+}
+
+struct VecDataFeed<'a> {
+    data: std::slice::Iter<'a, MarketData>,
+}
+
+impl DataFeed for VecDataFeed<'_> {
+    fn poll(&mut self) -> Result<Option<MarketData>, DataHandlerError> {
+        Ok(self.data.next().copied())
+    }
+}
+
+impl DataHandler for VecDataHandler {
+    fn start(&mut self) -> Result<(), DataHandlerError> {
+        Ok(())
+    }
+
+    fn stop(&mut self) {}
+
+    fn get_data_feed(&mut self) -> Box<dyn DataFeed + '_> {
+        Box::new(VecDataFeed {
+            data: self.data.iter(),
+        })
+    }
+}
+
+struct RecordingStrategy {
+    id: usize,
+    indicators: StrategyIndicators,
+    ma: IndicatorHandle<MovingAverage>,
+    next_values: Rc<RefCell<Vec<f64>>>,
+}
+
+impl MarketDataReceiver for RecordingStrategy {
+    fn update(&mut self, _market_data: MarketData, _broker: &mut dyn Broker) {}
+}
+
+impl StrategyBase for RecordingStrategy {
+    fn init(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn get_instrument(&self) -> u32 {
+        1
+    }
+
+    fn get_indicators(&mut self) -> Option<&mut StrategyIndicators> {
+        Some(&mut self.indicators)
+    }
+}
+
+impl Strategy for RecordingStrategy {
+    fn next(&mut self, _market_data: MarketData, _broker: &mut dyn Broker) {
+        let value = self.indicators.get(self.ma).value().unwrap();
+        self.next_values.borrow_mut().push(value);
+    }
+}
+
+#[test]
+fn next_is_only_called_once_indicators_are_warmed_up() {
+    let next_values = Rc::new(RefCell::new(Vec::new()));
+    let mut indicators = StrategyIndicators::new();
+    let ma = indicators.register(MovingAverage::new(3, 10));
+    let strategy = RecordingStrategy {
+        id: 0,
+        indicators,
+        ma,
+        next_values: next_values.clone(),
+    };
+
+    let mut engine = BacktestingEngine {
+        data_handler: Box::new(VecDataHandler {
+            data: [1.0, 2.0, 3.0, 4.0, 5.0].map(|price| tick(0, price)).to_vec(),
+        }),
+        broker: BacktestingBroker::new(1_000.0),
+        execution_engine: Box::new(BacktestingExecutionEngine {}),
+        strategies: vec![Box::new(strategy)],
+    };
+
+    engine.init();
+    engine.run().unwrap();
+
+    assert_eq!(*next_values.borrow(), vec![2.0, 3.0, 4.0]);
+}
+
+struct TimeframeStrategy {
+    id: usize,
+    timeframes: StrategyTimeframes,
+    m3: TimeframeHandle,
+    events: Rc<RefCell<Vec<String>>>,
+}
+
+impl MarketDataReceiver for TimeframeStrategy {
+    fn update(&mut self, _market_data: MarketData, _broker: &mut dyn Broker) {}
+}
+
+impl StrategyBase for TimeframeStrategy {
+    fn init(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn get_instrument(&self) -> u32 {
+        1
+    }
+
+    fn get_timeframes(&mut self) -> Option<&mut StrategyTimeframes> {
+        Some(&mut self.timeframes)
+    }
+}
+
+impl Strategy for TimeframeStrategy {
+    fn next(&mut self, market_data: MarketData, _broker: &mut dyn Broker) {
+        if let MarketData::Bar(bar) = market_data {
+            self.events.borrow_mut().push(format!("next {}", bar.close));
+        }
+    }
+
+    fn on_timeframe_bar(&mut self, timeframe: TimeframeHandle, bar: Bar, _broker: &mut dyn Broker) {
+        assert_eq!(timeframe, self.m3);
+        self.events.borrow_mut().push(format!("m3 {}", bar.close));
+    }
+}
+
+fn make_minute_bar(minute: u32, close: f64) -> MarketData {
+    let date: NaiveDateTime = NaiveDate::from_ymd_opt(2023, 1, 2)
+        .unwrap()
+        .and_hms_opt(9, minute, 0)
+        .unwrap();
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_naive_utc(date),
+        open: close,
+        high: close,
+        low: close,
+        close,
+        volume: 1.0,
+    })
+}
+
+#[test]
+fn timeframe_bars_are_delivered_before_next_once_completed() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut timeframes = StrategyTimeframes::new();
+    let m3 = timeframes.subscribe(BarConsolidator::new(1, 3));
+    let strategy = TimeframeStrategy {
+        id: 0,
+        timeframes,
+        m3,
+        events: events.clone(),
+    };
+
+    let mut engine = BacktestingEngine {
+        data_handler: Box::new(VecDataHandler {
+            data: (0..4).map(|minute| make_minute_bar(minute, minute as f64)).collect(),
+        }),
+        broker: BacktestingBroker::new(1_000.0),
+        execution_engine: Box::new(BacktestingExecutionEngine {}),
+        strategies: vec![Box::new(strategy)],
+    };
+
+    engine.init();
+    engine.run().unwrap();
+
+    assert_eq!(
+        *events.borrow(),
+        vec!["next 0", "next 1", "m3 2", "next 2", "next 3"]
+    );
+}
+
+#[test]
+fn next_waits_for_the_indicators_of_higher_timeframes() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut timeframes = StrategyTimeframes::new();
+    let m3 = timeframes.subscribe(BarConsolidator::new(1, 3));
+    timeframes.get_mut(m3).indicators.register(MovingAverage::new(2, 10));
+    let strategy = TimeframeStrategy {
+        id: 0,
+        timeframes,
+        m3,
+        events: events.clone(),
+    };
+
+    let mut engine = BacktestingEngine {
+        data_handler: Box::new(VecDataHandler {
+            data: (0..7).map(|minute| make_minute_bar(minute, minute as f64)).collect(),
+        }),
+        broker: BacktestingBroker::new(1_000.0),
+        execution_engine: Box::new(BacktestingExecutionEngine {}),
+        strategies: vec![Box::new(strategy)],
+    };
+
+    engine.init();
+    engine.run().unwrap();
+
+    // The moving average of the 3 minute bars is ready after the second of them
+    assert_eq!(*events.borrow(), vec!["m3 2", "m3 5", "next 5", "next 6"]);
+}
+
+#[test]
+fn run_returns_data_handler_errors() {
+    let mut engine = BacktestingEngine {
+        data_handler: Box::new(CSVDataHandler::new(
+            String::from("./does-not-exist.csv"),
+            Box::new(GenericCSVRowParser::yahoo(chrono_tz::UTC)),
+            HistoricBarConsolidationModel::new(1, 1),
+        )),
+        broker: BacktestingBroker::new(100_000.0),
+        execution_engine: Box::new(BacktestingExecutionEngine {}),
+        strategies: vec![],
+    };
+
+    engine.init();
+    assert!(matches!(engine.run(), Err(DataHandlerError::FileNotFound { .. })));
+}
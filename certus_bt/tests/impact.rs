@@ -0,0 +1,109 @@
+use certus_bt::broker::{BacktestingBroker, VolumeLimits};
+use certus_bt::impact::{ImpactModel, MarketImpact};
+use certus_core::broker::Broker;
+use certus_core::core::{Order, OrderSide, OrderType};
+use certus_core::data::MarketData;
+
+mod common;
+use common::{bar, order};
+
+/// Bar around a price without a gap to the previous bar
+fn flat_bar(seconds: i64, price: f64) -> MarketData {
+    bar(seconds, price, price + 1.0, price - 1.0, price)
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn impact_grows_with_participation() {
+    let square_root = MarketImpact::new(ImpactModel::SquareRoot, 0.02, 10_000.0).with_coefficients(1.0, 0.5);
+    let (temporary, permanent) = square_root.impact(100.0);
+    assert_close(temporary, 0.002);
+    assert_close(permanent, 0.001);
+    assert_close(square_root.impact(400.0).0, 0.004);
+
+    let linear = MarketImpact::new(ImpactModel::Linear, 0.02, 10_000.0);
+    assert_close(linear.impact(100.0).0, 0.0002);
+    assert_eq!(linear.impact(100.0).1, 0.0);
+    assert_eq!(MarketImpact::new(ImpactModel::Linear, 0.02, 0.0).impact(100.0), (0.0, 0.0));
+}
+
+#[test]
+fn broker_applies_temporary_and_permanent_impact() {
+    let impact = MarketImpact::new(ImpactModel::SquareRoot, 0.02, 10_000.0).with_coefficients(1.0, 0.5);
+    let mut broker = BacktestingBroker::new(100_000.0).with_market_impact(impact);
+
+    let first = broker.place_order(order(OrderSide::Buy, OrderType::Market, 100.0)).id.unwrap();
+    broker.simulate_fills(flat_bar(60, 50.0));
+    assert_close(broker.get_trade_for_order(first).unwrap().entry_price, 50.1);
+
+    // The permanent impact of the first buy raises the price of the next
+    let second = broker.place_order(order(OrderSide::Buy, OrderType::Market, 100.0)).id.unwrap();
+    broker.simulate_fills(flat_bar(120, 50.0));
+    assert_close(broker.get_trade_for_order(second).unwrap().entry_price, 50.0 * 1.003);
+
+    // Limits fill at their price, stop-limits never beyond their limit
+    let limit = broker.place_order(order(OrderSide::Buy, OrderType::Limit(49.5), 100.0)).id.unwrap();
+    let stop_limit = broker
+        .place_order(order(OrderSide::Buy, OrderType::StopLimit(50.5, 50.55), 100.0))
+        .id
+        .unwrap();
+    broker.simulate_fills(flat_bar(180, 50.0));
+    assert_eq!(broker.get_trade_for_order(limit).unwrap().entry_price, 49.5);
+    assert_eq!(broker.get_trade_for_order(stop_limit).unwrap().entry_price, 50.55);
+
+    // Selling against the temporary impact
+    let sell = broker.place_order(order(OrderSide::Sell, OrderType::Market, 100.0)).id.unwrap();
+    broker.simulate_fills(flat_bar(240, 50.0));
+    let fill = broker.get_fill(broker.get_trade_for_order(sell).unwrap().fills[0]).unwrap();
+    assert_close(fill.price, 50.0 * (1.0 + 0.003 - 0.002));
+}
+
+#[test]
+fn permanent_impact_is_kept_per_instrument() {
+    let impact = MarketImpact::new(ImpactModel::SquareRoot, 0.02, 10_000.0).with_coefficients(1.0, 0.5);
+    let mut broker = BacktestingBroker::new(100_000.0).with_market_impact(impact);
+
+    broker.place_order(order(OrderSide::Buy, OrderType::Market, 100.0));
+    broker.simulate_fills(flat_bar(60, 50.0));
+
+    // The buy of the first instrument doesn't move the price of the second
+    let other = Order {
+        instrument: 2,
+        ..order(OrderSide::Buy, OrderType::Market, 100.0)
+    };
+    let other = broker.place_order(other).id.unwrap();
+    broker.simulate_fills(flat_bar(120, 50.0));
+    assert_close(broker.get_trade_for_order(other).unwrap().entry_price, 50.1);
+}
+
+#[test]
+fn impact_is_based_on_the_whole_order() {
+    let impact = MarketImpact::new(ImpactModel::SquareRoot, 0.02, 10_000.0).with_coefficients(1.0, 0.5);
+    let limits = VolumeLimits {
+        max_participation: 0.1,
+        min_volume: 0.0,
+    };
+    let mut broker = BacktestingBroker::new(100_000.0)
+        .with_market_impact(impact)
+        .with_volume_limits(limits);
+
+    // Fills 100 per bar, every slice pays the impact of 200
+    let parent = broker.place_order(order(OrderSide::Buy, OrderType::Market, 200.0)).id.unwrap();
+    broker.simulate_fills(flat_bar(60, 50.0));
+    broker.simulate_fills(flat_bar(120, 50.0));
+    let (temporary, permanent) = impact.impact(200.0);
+    let report = broker.fill_report(parent).unwrap();
+    assert_eq!(report.remaining, 0.0);
+    let prices: Vec<f64> = report.fills.iter().map(|fill_id| broker.get_fill(*fill_id).unwrap().price).collect();
+    assert_close(prices[0], 50.0 * (1.0 + temporary));
+    assert_close(prices[1], 50.0 * (1.0 + permanent / 2.0 + temporary));
+
+    // The whole permanent impact of the order stays after it filled
+    let next = broker.place_order(order(OrderSide::Buy, OrderType::Market, 1.0)).id.unwrap();
+    broker.simulate_fills(flat_bar(180, 50.0));
+    let price = broker.get_trade_for_order(next).unwrap().entry_price;
+    assert_close(price, 50.0 * (1.0 + permanent + impact.impact(1.0).0));
+}
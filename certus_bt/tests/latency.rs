@@ -0,0 +1,87 @@
+use certus_bt::broker::{BacktestingBroker, Latency, LatencyModel, OrderStatus};
+use certus_core::broker::Broker;
+use certus_core::core::{OrderSide, OrderType};
+use chrono::Duration;
+use rand::{SeedableRng, rngs::StdRng};
+
+mod common;
+use common::{order, tick};
+
+#[test]
+fn orders_fill_after_the_submission_latency() {
+    let mut broker = BacktestingBroker::new(100_000.0).with_latency(Latency {
+        submission: LatencyModel::Fixed(Duration::milliseconds(100)),
+        acknowledgement: LatencyModel::Fixed(Duration::milliseconds(50)),
+        ..Latency::default()
+    });
+    broker.simulate_fills(tick(0, 100.0));
+    let order_id = broker.place_order(order(OrderSide::Buy, OrderType::Market, 1.0)).id.unwrap();
+
+    broker.simulate_fills(tick(60, 101.0));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+    assert_eq!(broker.order_status(order_id), Some(OrderStatus::Submitted));
+
+    broker.simulate_fills(tick(100, 102.0));
+    assert_eq!(broker.get_trade_for_order(order_id).unwrap().entry_price, 102.0);
+    assert_eq!(broker.order_status(order_id), Some(OrderStatus::Filled));
+}
+
+#[test]
+fn orders_fill_until_the_cancel_takes_effect() {
+    let mut broker = BacktestingBroker::new(100_000.0).with_latency(Latency {
+        cancel: LatencyModel::Fixed(Duration::milliseconds(200)),
+        ..Latency::default()
+    });
+    broker.simulate_fills(tick(0, 100.0));
+    let filled = broker.place_order(order(OrderSide::Buy, OrderType::Limit(99.0), 1.0)).id.unwrap();
+    let cancelled = broker.place_order(order(OrderSide::Sell, OrderType::Limit(101.0), 1.0)).id.unwrap();
+    assert_eq!(broker.order_status(filled), Some(OrderStatus::Working));
+
+    assert!(broker.cancel_order(filled));
+    assert!(broker.cancel_order(cancelled));
+    assert_eq!(broker.order_status(filled), Some(OrderStatus::PendingCancel));
+
+    broker.simulate_fills(tick(100, 99.0));
+    assert_eq!(broker.order_status(filled), Some(OrderStatus::Filled));
+    assert!(!broker.cancel_order(filled));
+
+    broker.simulate_fills(tick(200, 101.0));
+    assert_eq!(broker.order_status(cancelled), Some(OrderStatus::Cancelled));
+    assert!(broker.get_trade_for_order(cancelled).is_none());
+    assert_eq!(broker.unfilled_orders_len(), 0);
+}
+
+#[test]
+fn cancels_without_latency_are_immediate() {
+    let mut broker = BacktestingBroker::new(100_000.0);
+    broker.simulate_fills(tick(0, 100.0));
+    let order_id = broker.place_order(order(OrderSide::Buy, OrderType::Limit(99.0), 1.0)).id.unwrap();
+
+    assert!(broker.cancel_order(order_id));
+    broker.simulate_fills(tick(0, 98.0));
+    assert_eq!(broker.order_status(order_id), Some(OrderStatus::Cancelled));
+    assert!(broker.get_trade_for_order(order_id).is_none());
+    assert!(!broker.cancel_order(42));
+}
+
+#[test]
+fn distributed_latencies_stay_in_their_bounds() {
+    let uniform = LatencyModel::Uniform {
+        min: Duration::milliseconds(10),
+        max: Duration::milliseconds(20),
+    };
+    let exponential = LatencyModel::Exponential {
+        min: Duration::milliseconds(5),
+        mean: Duration::milliseconds(2),
+    };
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..100 {
+        let delay = uniform.sample(&mut rng);
+        assert!(delay >= Duration::milliseconds(10) && delay <= Duration::milliseconds(20));
+        assert!(exponential.sample(&mut rng) >= Duration::milliseconds(5));
+    }
+
+    let mut first = StdRng::seed_from_u64(1);
+    let mut second = StdRng::seed_from_u64(1);
+    assert_eq!(uniform.sample(&mut first), uniform.sample(&mut second));
+}
@@ -8,8 +8,13 @@ pub struct Account {
 pub trait Broker {
     fn place_order(&mut self, order: Order) -> &Order;
 
+    /// Cancel an unfilled order, returns false when the order is unknown or no longer open
+    fn cancel_order(&mut self, order_id: usize) -> bool;
+
     fn add_instrument(&mut self, instrument: Instrument) -> &Instrument;
 
+    fn get_instrument(&mut self, instrument_id: u32) -> Option<&Instrument>;
+
     fn get_current_position(&mut self, strategy_id: usize, instrument_id: u32) -> f64;
 
     fn get_open_trades(&mut self, strategy_id: usize, instrument_id: u32) -> Vec<&Trade>;
@@ -0,0 +1,304 @@
+use chrono::{Duration, NaiveTime};
+use chrono_tz::Tz;
+
+use crate::data::{Bar, MarketData, QuoteBar, Timestamp};
+use crate::session::{SessionType, TradingCalendar};
+use crate::timeframe::Timeframe;
+
+/// Streaming consolidator turning bars of the `input` timeframe into bars of the `output` timeframe.
+/// Only completed bars are emitted, the partially formed bar is kept internal
+/// so consumers can never look ahead into it.
+#[derive(Debug, Clone)]
+pub struct BarConsolidator {
+    pub input: Timeframe,
+    pub output: Timeframe,
+    pub session_open: NaiveTime,
+    /// Time zone of the session open
+    pub time_zone: Tz,
+    pub calendar: Option<TradingCalendar>,
+    pub session_type: SessionType,
+    current: Option<Bar>,
+}
+
+impl BarConsolidator {
+    pub fn new(input_minutes: u32, output_minutes: u32) -> Self {
+        assert!(
+            input_minutes > 0 && output_minutes.is_multiple_of(input_minutes),
+            "output_minutes ({}) must be a multiple of input_minutes ({})",
+            output_minutes,
+            input_minutes
+        );
+
+        Self::from_timeframes(Timeframe::Minutes(input_minutes), Timeframe::Minutes(output_minutes))
+    }
+
+    pub fn from_timeframes(input: Timeframe, output: Timeframe) -> Self {
+        assert!(
+            output.is_multiple_of(&input),
+            "output timeframe ({}) must be a multiple of input timeframe ({})",
+            output,
+            input
+        );
+
+        Self {
+            input,
+            output,
+            session_open: NaiveTime::MIN,
+            time_zone: Tz::UTC,
+            calendar: None,
+            session_type: SessionType::Regular,
+            current: None,
+        }
+    }
+
+    /// Align buckets to the session open instead of midnight
+    pub fn with_session_open(mut self, session_open: NaiveTime) -> Self {
+        self.session_open = session_open;
+        self
+    }
+
+    /// Time zone in which the session open is given, UTC by default
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Only consolidate bars inside the sessions of the calendar. Buckets are aligned to
+    /// the session open and complete at the session close when no trading time is left.
+    pub fn with_calendar(mut self, calendar: TradingCalendar, session_type: SessionType) -> Self {
+        self.session_open = calendar.hours(session_type).open;
+        self.time_zone = calendar.time_zone;
+        self.calendar = Some(calendar);
+        self.session_type = session_type;
+        self
+    }
+
+    fn input_end(&self, timestamp: Timestamp) -> Timestamp {
+        match self.input.seconds() {
+            Some(seconds) => timestamp + Duration::seconds(seconds),
+            None => self.input.bucket_end_at(timestamp, self.session_open, &self.time_zone),
+        }
+    }
+
+    fn is_bucket_complete(&self, input_end: Timestamp, bucket_end: Timestamp) -> bool {
+        if input_end >= bucket_end {
+            return true;
+        }
+        match &self.calendar {
+            Some(calendar) => calendar
+                .next_trading_time_at(input_end, self.session_type)
+                .is_none_or(|next| next >= bucket_end),
+            None => false,
+        }
+    }
+
+    /// Feed a new input bar, ticks and quotes are ignored (see `TickBarAggregator`) and quote bars are
+    /// consolidated by their mid prices. Returns the bars that were completed by it.
+    /// A bucket completes when its last input bar arrives, or when a bar of a
+    /// later bucket arrives after missing input bars.
+    pub fn update(&mut self, market_data: MarketData) -> Vec<Bar> {
+        let bar = match market_data {
+            MarketData::Bar(bar) => bar,
+            MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar(),
+            MarketData::Tick(_) | MarketData::Quote(_) => return Vec::new(),
+        };
+
+        if let Some(calendar) = &self.calendar
+            && !calendar.is_session_open_at(bar.timestamp, self.session_type)
+        {
+            return Vec::new();
+        }
+
+        let mut completed = Vec::new();
+        let bucket_start = self
+            .output
+            .bucket_start_at(bar.timestamp, self.session_open, &self.time_zone);
+
+        match self.current.as_mut() {
+            Some(current) if current.timestamp == bucket_start => {
+                current.high = current.high.max(bar.high);
+                current.low = current.low.min(bar.low);
+                current.close = bar.close;
+                current.volume += bar.volume;
+            }
+            _ => {
+                if let Some(previous) = self.current.take() {
+                    completed.push(previous);
+                }
+                self.current = Some(Bar {
+                    timestamp: bucket_start,
+                    ..bar
+                });
+            }
+        }
+
+        let bucket_end = self
+            .output
+            .bucket_end_at(bucket_start, self.session_open, &self.time_zone);
+        if self.is_bucket_complete(self.input_end(bar.timestamp), bucket_end)
+            && let Some(current) = self.current.take()
+        {
+            completed.push(current);
+        }
+
+        completed
+    }
+
+    /// Emit the partially formed bar, e.g. at the end of the data feed
+    pub fn flush(&mut self) -> Option<Bar> {
+        self.current.take()
+    }
+}
+
+/// Streaming aggregator building time bars of `timeframe` from ticks.
+/// A bar is completed by the first tick of a later bucket, buckets without
+/// ticks don't produce a bar.
+#[derive(Debug, Clone)]
+pub struct TickBarAggregator {
+    pub timeframe: Timeframe,
+    pub session_open: NaiveTime,
+    /// Time zone of the session open
+    pub time_zone: Tz,
+    current: Option<Bar>,
+}
+
+impl TickBarAggregator {
+    pub fn new(bar_minutes: u32) -> Self {
+        Self::from_timeframe(Timeframe::Minutes(bar_minutes))
+    }
+
+    pub fn from_timeframe(timeframe: Timeframe) -> Self {
+        assert!(
+            timeframe.is_multiple_of(&Timeframe::Seconds(1)),
+            "timeframe ({}) must be at least 1 second",
+            timeframe
+        );
+
+        Self {
+            timeframe,
+            session_open: NaiveTime::MIN,
+            time_zone: Tz::UTC,
+            current: None,
+        }
+    }
+
+    /// Align buckets to the session open instead of midnight
+    pub fn with_session_open(mut self, session_open: NaiveTime) -> Self {
+        self.session_open = session_open;
+        self
+    }
+
+    /// Time zone in which the session open is given, UTC by default
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Feed a new tick, returns the bar that was completed by it. Bars and quotes are ignored.
+    pub fn update(&mut self, market_data: MarketData) -> Option<Bar> {
+        let tick = match market_data {
+            MarketData::Tick(tick) => tick,
+            _ => return None,
+        };
+
+        let bucket_start = self
+            .timeframe
+            .bucket_start_at(tick.timestamp, self.session_open, &self.time_zone);
+        if let Some(current) = self.current.as_mut()
+            && current.timestamp == bucket_start
+        {
+            current.high = current.high.max(tick.price);
+            current.low = current.low.min(tick.price);
+            current.close = tick.price;
+            current.volume += tick.size;
+            return None;
+        }
+
+        self.current.replace(Bar {
+            timestamp: bucket_start,
+            open: tick.price,
+            high: tick.price,
+            low: tick.price,
+            close: tick.price,
+            volume: tick.size,
+        })
+    }
+
+    /// Emit the partially formed bar, e.g. at the end of the data feed
+    pub fn flush(&mut self) -> Option<Bar> {
+        self.current.take()
+    }
+}
+
+/// Streaming aggregator building quote bars of `timeframe` from quotes and quote bars.
+/// A quote bar is completed by the first data of a later bucket, buckets without
+/// quotes don't produce a quote bar.
+#[derive(Debug, Clone)]
+pub struct QuoteBarAggregator {
+    pub timeframe: Timeframe,
+    pub session_open: NaiveTime,
+    /// Time zone of the session open
+    pub time_zone: Tz,
+    current: Option<QuoteBar>,
+}
+
+impl QuoteBarAggregator {
+    pub fn new(bar_minutes: u32) -> Self {
+        Self::from_timeframe(Timeframe::Minutes(bar_minutes))
+    }
+
+    pub fn from_timeframe(timeframe: Timeframe) -> Self {
+        assert!(
+            timeframe.is_multiple_of(&Timeframe::Seconds(1)),
+            "timeframe ({}) must be at least 1 second",
+            timeframe
+        );
+
+        Self {
+            timeframe,
+            session_open: NaiveTime::MIN,
+            time_zone: Tz::UTC,
+            current: None,
+        }
+    }
+
+    /// Align buckets to the session open instead of midnight
+    pub fn with_session_open(mut self, session_open: NaiveTime) -> Self {
+        self.session_open = session_open;
+        self
+    }
+
+    /// Time zone in which the session open is given, UTC by default
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Feed a new quote or quote bar, returns the quote bar that was completed by it. Ticks and bars are ignored.
+    pub fn update(&mut self, market_data: MarketData) -> Option<QuoteBar> {
+        let bucket_start = self
+            .timeframe
+            .bucket_start_at(market_data.timestamp(), self.session_open, &self.time_zone);
+        let quote_bar = match market_data {
+            MarketData::Quote(quote) => QuoteBar::from_quote(bucket_start, &quote),
+            MarketData::QuoteBar(quote_bar) => QuoteBar {
+                timestamp: bucket_start,
+                ..quote_bar
+            },
+            _ => return None,
+        };
+
+        if let Some(current) = self.current.as_mut()
+            && current.timestamp == bucket_start
+        {
+            current.merge(&quote_bar);
+            return None;
+        }
+        self.current.replace(quote_bar)
+    }
+
+    /// Emit the partially formed quote bar, e.g. at the end of the data feed
+    pub fn flush(&mut self) -> Option<QuoteBar> {
+        self.current.take()
+    }
+}
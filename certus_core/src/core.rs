@@ -1,5 +1,7 @@
 use std::{collections::HashMap, fmt};
 
+use crate::session::TradingCalendar;
+
 /// enum defining the type of trading instrument
 #[derive(Debug, Clone)]
 pub enum InstrumentType {
@@ -20,6 +22,7 @@ pub struct Instrument {
     pub symbol: String,
     pub exchange: Option<String>,
     pub instrument_type: InstrumentType,
+    pub calendar: Option<TradingCalendar>,
 }
 
 impl Instrument {
@@ -29,8 +32,15 @@ impl Instrument {
             symbol,
             exchange,
             instrument_type,
+            calendar: None,
         }
     }
+
+    /// Attach the trading calendar of the exchange
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
 }
 
 /// enum defining possible order sides
@@ -47,6 +57,10 @@ pub enum OrderType {
     Limit(f64),
     Stop(f64),
     StopLimit(f64, f64),
+    /// Fill at the opening price of the next session
+    MarketOnOpen,
+    /// Fill at the closing price of the current session, or of the next session when the market is closed
+    MarketOnClose,
 }
 
 /// struct for defining an order
@@ -128,13 +142,7 @@ impl Trade {
     /// Calculate the PnL of the trade.
     /// Returns None if the trade is still open (no exit price yet).
     pub fn pnl(&self, big_point_value: f64) -> Option<f64> {
-        match self.exit_price {
-            Some(exit) => {
-                // PnL = size * (exit - entry)
-                Some((self.size * (exit - self.entry_price)) * big_point_value)
-            }
-            None => None, // Trade still open
-        }
+        self.exit_price.map(|exit| (self.size * (exit - self.entry_price)) * big_point_value)
     }
 }
 
@@ -159,6 +167,12 @@ pub struct PositionManager {
     pub open_trades: HashMap<usize, Vec<usize>>,
 }
 
+impl Default for PositionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PositionManager {
     pub fn new() -> Self {
         Self {
@@ -1,16 +1,141 @@
 use chrono::prelude::*;
-use std::fmt;
+use chrono::{Duration, LocalResult};
+use chrono_tz::Tz;
+use std::{fmt, ops};
+
+/// Point in time as nanoseconds since the Unix epoch (UTC)
+/// Use `to_local()` or `in_time_zone()` to show it in exchange time
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Self(nanos)
+    }
+
+    /// # Panics
+    /// Panics when the time is outside the range of nanosecond timestamps, see `checked_from_millis`
+    pub fn from_millis(millis: i64) -> Self {
+        Self::checked_from_millis(millis).expect("milliseconds out of range for a nanosecond timestamp")
+    }
+
+    /// # Panics
+    /// Panics when the time is outside the range of nanosecond timestamps, see `checked_from_seconds`
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self::checked_from_seconds(seconds).expect("seconds out of range for a nanosecond timestamp")
+    }
+
+    /// None outside the range of nanosecond timestamps, about 1677 to 2262
+    pub const fn checked_from_micros(micros: i64) -> Option<Self> {
+        match micros.checked_mul(1_000) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None,
+        }
+    }
+
+    /// None outside the range of nanosecond timestamps, about 1677 to 2262
+    pub const fn checked_from_millis(millis: i64) -> Option<Self> {
+        match millis.checked_mul(1_000_000) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None,
+        }
+    }
+
+    /// None outside the range of nanosecond timestamps, about 1677 to 2262
+    pub const fn checked_from_seconds(seconds: i64) -> Option<Self> {
+        match seconds.checked_mul(1_000_000_000) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None,
+        }
+    }
+
+    pub fn from_utc(dt: DateTime<Utc>) -> Self {
+        Self(
+            dt.timestamp_nanos_opt()
+                .expect("date out of range for a nanosecond timestamp"),
+        )
+    }
+
+    pub fn from_naive_utc(dt: NaiveDateTime) -> Self {
+        Self::from_utc(dt.and_utc())
+    }
+
+    /// Convert a local time in `time_zone`
+    /// Ambiguous times (DST fall back) resolve to the earliest instant, times inside
+    /// a DST gap (spring forward) are moved forward by the length of the gap
+    pub fn from_local(dt: NaiveDateTime, time_zone: &Tz) -> Self {
+        match time_zone.from_local_datetime(&dt) {
+            LocalResult::Single(local) | LocalResult::Ambiguous(local, _) => {
+                Self::from_utc(local.with_timezone(&Utc))
+            }
+            // Use the offset from before the gap
+            LocalResult::None => Self::from_local(dt - Duration::hours(1), time_zone) + Duration::hours(1),
+        }
+    }
+
+    pub fn nanos(&self) -> i64 {
+        self.0
+    }
+
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.0)
+    }
+
+    pub fn naive_utc(&self) -> NaiveDateTime {
+        self.to_utc().naive_utc()
+    }
+
+    pub fn in_time_zone(&self, time_zone: &Tz) -> DateTime<Tz> {
+        self.to_utc().with_timezone(time_zone)
+    }
+
+    /// Local time in `time_zone`
+    pub fn to_local(&self, time_zone: &Tz) -> NaiveDateTime {
+        self.in_time_zone(time_zone).naive_local()
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl ops::Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 + rhs.num_nanoseconds().expect("duration out of range"))
+    }
+}
+
+impl ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 - rhs.num_nanoseconds().expect("duration out of range"))
+    }
+}
+
+impl ops::Sub<Timestamp> for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Timestamp) -> Self::Output {
+        Duration::nanoseconds(self.0 - rhs.0)
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct Tick {
-    pub timestamp: i64,
+    pub timestamp: Timestamp,
     pub price: f64,
     pub size: f64,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct Bar {
-    pub date: NaiveDateTime,
+    /// Start of the bar
+    pub timestamp: Timestamp,
     pub open: f64,
     pub high: f64,
     pub low: f64,
@@ -18,10 +143,97 @@ pub struct Bar {
     pub volume: f64,
 }
 
+/// Top of book, best bid and ask with the size quoted at each
+#[derive(Debug, Copy, Clone)]
+pub struct Quote {
+    pub timestamp: Timestamp,
+    pub bid: f64,
+    pub bid_size: f64,
+    pub ask: f64,
+    pub ask_size: f64,
+}
+
+/// Bid and ask OHLC over a period, the sizes are the ones quoted at the close
+#[derive(Debug, Copy, Clone)]
+pub struct QuoteBar {
+    /// Start of the bar
+    pub timestamp: Timestamp,
+    pub bid_open: f64,
+    pub bid_high: f64,
+    pub bid_low: f64,
+    pub bid_close: f64,
+    pub bid_size: f64,
+    pub ask_open: f64,
+    pub ask_high: f64,
+    pub ask_low: f64,
+    pub ask_close: f64,
+    pub ask_size: f64,
+}
+
+/// enum defining the sides of an order book
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+/// Aggregated size resting at a price
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BookLevel {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Full depth of the book, bids from best (highest) to worst and asks from best (lowest) to worst
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSnapshot {
+    pub timestamp: Timestamp,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+/// New size of a single price level, a size of 0 removes the level
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BookDelta {
+    pub timestamp: Timestamp,
+    pub side: BookSide,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Level 2 market data, a snapshot replaces the book and deltas update it
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderBookUpdate {
+    Snapshot(BookSnapshot),
+    Delta(BookDelta),
+}
+
+impl OrderBookUpdate {
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            OrderBookUpdate::Snapshot(snapshot) => snapshot.timestamp,
+            OrderBookUpdate::Delta(delta) => delta.timestamp,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum MarketData {
     Tick(Tick),
     Bar(Bar),
+    Quote(Quote),
+    QuoteBar(QuoteBar),
+}
+
+impl MarketData {
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            MarketData::Tick(tick) => tick.timestamp,
+            MarketData::Bar(bar) => bar.timestamp,
+            MarketData::Quote(quote) => quote.timestamp,
+            MarketData::QuoteBar(quote_bar) => quote_bar.timestamp,
+        }
+    }
 }
 
 impl fmt::Display for MarketData {
@@ -34,13 +246,133 @@ impl fmt::Display for MarketData {
             ),
             MarketData::Bar(bar) => write!(
                 f,
-                "Bar(date: {}, open: {}, high: {}, low: {}, close: {}, volume: {})",
-                bar.date, bar.open, bar.high, bar.low, bar.close, bar.volume
+                "Bar(timestamp: {}, open: {}, high: {}, low: {}, close: {}, volume: {})",
+                bar.timestamp, bar.open, bar.high, bar.low, bar.close, bar.volume
             ),
+            MarketData::Quote(quote) => write!(
+                f,
+                "Quote(timestamp: {}, bid: {} x {}, ask: {} x {})",
+                quote.timestamp, quote.bid, quote.bid_size, quote.ask, quote.ask_size
+            ),
+            MarketData::QuoteBar(quote_bar) => write!(
+                f,
+                "QuoteBar(timestamp: {}, bid: {}/{}/{}/{} x {}, ask: {}/{}/{}/{} x {})",
+                quote_bar.timestamp,
+                quote_bar.bid_open,
+                quote_bar.bid_high,
+                quote_bar.bid_low,
+                quote_bar.bid_close,
+                quote_bar.bid_size,
+                quote_bar.ask_open,
+                quote_bar.ask_high,
+                quote_bar.ask_low,
+                quote_bar.ask_close,
+                quote_bar.ask_size
+            ),
+        }
+    }
+}
+
+impl Quote {
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask - self.bid
+    }
+}
+
+impl QuoteBar {
+    /// Quote bar starting at `timestamp` holding a single quote
+    pub fn from_quote(timestamp: Timestamp, quote: &Quote) -> Self {
+        Self {
+            timestamp,
+            bid_open: quote.bid,
+            bid_high: quote.bid,
+            bid_low: quote.bid,
+            bid_close: quote.bid,
+            bid_size: quote.bid_size,
+            ask_open: quote.ask,
+            ask_high: quote.ask,
+            ask_low: quote.ask,
+            ask_close: quote.ask,
+            ask_size: quote.ask_size,
+        }
+    }
+
+    /// Extend the bar with a later quote bar of the same period
+    pub fn merge(&mut self, other: &QuoteBar) {
+        self.bid_high = self.bid_high.max(other.bid_high);
+        self.bid_low = self.bid_low.min(other.bid_low);
+        self.bid_close = other.bid_close;
+        self.bid_size = other.bid_size;
+        self.ask_high = self.ask_high.max(other.ask_high);
+        self.ask_low = self.ask_low.min(other.ask_low);
+        self.ask_close = other.ask_close;
+        self.ask_size = other.ask_size;
+    }
+
+    /// Bid prices as a bar, the volume is the bid size
+    pub fn bid_bar(&self) -> Bar {
+        Bar {
+            timestamp: self.timestamp,
+            open: self.bid_open,
+            high: self.bid_high,
+            low: self.bid_low,
+            close: self.bid_close,
+            volume: self.bid_size,
+        }
+    }
+
+    /// Ask prices as a bar, the volume is the ask size
+    pub fn ask_bar(&self) -> Bar {
+        Bar {
+            timestamp: self.timestamp,
+            open: self.ask_open,
+            high: self.ask_high,
+            low: self.ask_low,
+            close: self.ask_close,
+            volume: self.ask_size,
+        }
+    }
+
+    /// Mid prices as a bar, without volume
+    pub fn mid_bar(&self) -> Bar {
+        Bar {
+            timestamp: self.timestamp,
+            open: (self.bid_open + self.ask_open) / 2.0,
+            high: (self.bid_high + self.ask_high) / 2.0,
+            low: (self.bid_low + self.ask_low) / 2.0,
+            close: (self.bid_close + self.ask_close) / 2.0,
+            volume: 0.0,
         }
     }
 }
 
+impl Bar {
+    /// Reason the prices of the bar are inconsistent, None for a valid bar
+    pub fn ohlc_error(&self) -> Option<String> {
+        let values = [self.open, self.high, self.low, self.close, self.volume];
+        if values.iter().any(|value| !value.is_finite()) {
+            return Some(String::from("prices and volume must be finite"));
+        }
+        if self.low > self.high {
+            return Some(format!("low {} is above high {}", self.low, self.high));
+        }
+        if self.open < self.low || self.open > self.high {
+            return Some(format!("open {} is outside the range {}-{}", self.open, self.low, self.high));
+        }
+        if self.close < self.low || self.close > self.high {
+            return Some(format!("close {} is outside the range {}-{}", self.close, self.low, self.high));
+        }
+        if self.volume < 0.0 {
+            return Some(format!("volume {} is negative", self.volume));
+        }
+        None
+    }
+}
+
 pub trait DataHandler {
     fn start(&mut self) -> Result<(), DataHandlerError>;
     fn stop(&mut self);
@@ -48,10 +380,158 @@ pub trait DataHandler {
 }
 
 pub trait DataFeed {
-    fn poll(&mut self) -> Option<MarketData>;
+    /// Next market data, None when there is no more data (or no new data for live feeds)
+    fn poll(&mut self) -> Result<Option<MarketData>, DataHandlerError>;
 }
 
-#[derive(Debug)]
+/// enum defining why market data could not be loaded
+/// Line numbers are 1-based lines of the source file, None when the source has no lines
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataHandlerError {
     FailedToStart,
+    FileNotFound {
+        path: String,
+    },
+    Io {
+        path: String,
+        message: String,
+    },
+    Parse {
+        line: Option<u64>,
+        column: Option<String>,
+        message: String,
+    },
+    InvalidOhlc {
+        line: Option<u64>,
+        timestamp: Timestamp,
+        message: String,
+    },
+    OutOfOrder {
+        line: Option<u64>,
+        timestamp: Timestamp,
+        previous: Timestamp,
+    },
+    /// Market data of a different kind than the data it is consolidated with, e.g. a bar between ticks
+    MixedData {
+        timestamp: Timestamp,
+        expected: String,
+        found: String,
+    },
+}
+
+impl DataHandlerError {
+    /// Error for a failed file operation, a missing file is reported as `FileNotFound`
+    pub fn io(path: impl Into<String>, error: &std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => DataHandlerError::FileNotFound { path: path.into() },
+            _ => DataHandlerError::Io {
+                path: path.into(),
+                message: error.to_string(),
+            },
+        }
+    }
+
+    /// Whether the error only affects a single row, which can be skipped
+    pub fn is_row_error(&self) -> bool {
+        matches!(
+            self,
+            DataHandlerError::Parse { .. } | DataHandlerError::InvalidOhlc { .. } | DataHandlerError::OutOfOrder { .. }
+        )
+    }
+
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            DataHandlerError::Parse { line, .. }
+            | DataHandlerError::InvalidOhlc { line, .. }
+            | DataHandlerError::OutOfOrder { line, .. } => *line,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataHandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line() {
+            write!(f, "line {}: ", line)?;
+        }
+        match self {
+            DataHandlerError::FailedToStart => write!(f, "failed to start data handler"),
+            DataHandlerError::FileNotFound { path } => write!(f, "file not found: {}", path),
+            DataHandlerError::Io { path, message } => write!(f, "failed to read {}: {}", path, message),
+            DataHandlerError::Parse {
+                column: Some(column),
+                message,
+                ..
+            } => write!(f, "failed to parse column {}: {}", column, message),
+            DataHandlerError::Parse { message, .. } => write!(f, "failed to parse row: {}", message),
+            DataHandlerError::InvalidOhlc { timestamp, message, .. } => {
+                write!(f, "invalid bar at {}: {}", timestamp, message)
+            }
+            DataHandlerError::OutOfOrder {
+                timestamp, previous, ..
+            } => write!(f, "timestamp {} is before previous timestamp {}", timestamp, previous),
+            DataHandlerError::MixedData {
+                timestamp,
+                expected,
+                found,
+            } => write!(f, "expected {} at {}, found {}", expected, timestamp, found),
+        }
+    }
+}
+
+impl std::error::Error for DataHandlerError {}
+
+/// enum defining what a data handler does with rows that fail to parse or validate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop loading at the first invalid row
+    #[default]
+    Abort,
+    /// Skip invalid rows, the errors are kept for a report
+    SkipAndReport,
+}
+
+/// Checks consecutive rows of a data source for invalid bars and timestamps out of order
+#[derive(Debug, Default)]
+pub struct MarketDataValidator {
+    previous: Option<Timestamp>,
+}
+
+impl MarketDataValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate the next row, rejected rows don't affect the order check of later rows
+    pub fn validate(&mut self, market_data: &MarketData, line: Option<u64>) -> Result<(), DataHandlerError> {
+        let timestamp = market_data.timestamp();
+        let ohlc_error = match market_data {
+            MarketData::Bar(bar) => bar.ohlc_error(),
+            MarketData::QuoteBar(quote_bar) => quote_bar
+                .bid_bar()
+                .ohlc_error()
+                .map(|message| format!("bid {}", message))
+                .or_else(|| quote_bar.ask_bar().ohlc_error().map(|message| format!("ask {}", message))),
+            _ => None,
+        };
+        if let Some(message) = ohlc_error {
+            return Err(DataHandlerError::InvalidOhlc {
+                line,
+                timestamp,
+                message,
+            });
+        }
+        if let Some(previous) = self.previous
+            && timestamp < previous
+        {
+            return Err(DataHandlerError::OutOfOrder {
+                line,
+                timestamp,
+                previous,
+            });
+        }
+
+        self.previous = Some(timestamp);
+        Ok(())
+    }
 }
@@ -1,8 +1,10 @@
 use crate::core::Order;
+use crate::data::DataHandlerError;
 
 pub trait Engine {
     fn init(&mut self);
-    fn run(&mut self);
+    /// Run until the data feed is exhausted, fails when the data can't be loaded
+    fn run(&mut self) -> Result<(), DataHandlerError>;
 }
 
 pub trait ExecutionEngine {
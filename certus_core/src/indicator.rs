@@ -1,28 +1,112 @@
-use std::{collections::VecDeque, ops::Index};
+use std::{any::Any, collections::VecDeque, ops::Index};
 
 use crate::data::MarketData;
 
-pub trait Indicator {
+/// A named output series of an indicator with a bounded history.
+/// Index 0 is the most recent value, index 1 the value before that, etc.
+#[derive(Debug, Clone)]
+pub struct IndicatorSeries {
+    name: &'static str,
+    history_period: usize,
+    history: VecDeque<f64>,
+}
+
+impl IndicatorSeries {
+    pub fn new(name: &'static str, history: usize) -> Self {
+        let history_period = history.max(1);
+        Self {
+            name,
+            history_period,
+            history: VecDeque::with_capacity(history_period),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Current (most recent) value of the series
+    pub fn value(&self) -> Option<f64> {
+        self.history.front().copied()
+    }
+
+    /// Value `index` updates back, None when not enough history is available
+    pub fn get(&self, index: usize) -> Option<f64> {
+        self.history.get(index).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &f64> {
+        self.history.iter()
+    }
+
+    pub fn push(&mut self, value: f64) {
+        // First pop_back() if length is already at capacity
+        // this ensures no new buffer is allocated
+        if self.history.len() == self.history_period {
+            self.history.pop_back();
+        }
+        self.history.push_front(value);
+    }
+}
+
+impl Index<usize> for IndicatorSeries {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.history[index]
+    }
+}
+
+pub trait Indicator: Any {
     fn is_ready(&self) -> bool;
     fn update(&mut self, market_data: MarketData);
+
+    /// Number of updates needed before the indicator is ready
+    fn warmup_period(&self) -> usize;
+
+    /// All output series of the indicator, the first one is the primary output
+    fn outputs(&self) -> &[IndicatorSeries];
+
+    /// Get an output series by its name
+    fn output(&self, name: &str) -> Option<&IndicatorSeries> {
+        self.outputs().iter().find(|series| series.name() == name)
+    }
+
+    /// Current value of the primary output
+    fn value(&self) -> Option<f64> {
+        self.outputs().first().and_then(IndicatorSeries::value)
+    }
+}
+
+fn price_of(market_data: &MarketData) -> f64 {
+    match market_data {
+        MarketData::Bar(bar) => bar.close,
+        MarketData::Tick(tick) => tick.price,
+        MarketData::Quote(quote) => quote.mid(),
+        MarketData::QuoteBar(quote_bar) => quote_bar.mid_bar().close,
+    }
 }
 
 pub struct MovingAverage {
     pub period: usize,
-    pub value: f64,
     window: VecDeque<f64>,
-    history_period: usize,
-    history: VecDeque<f64>,
+    outputs: [IndicatorSeries; 1],
 }
 
 impl MovingAverage {
     pub fn new(period: usize, history: usize) -> Self {
         Self {
             period,
-            value: 0.0,
             window: VecDeque::with_capacity(period),
-            history_period: history,
-            history: VecDeque::with_capacity(history),
+            outputs: [IndicatorSeries::new("ma", history)],
         }
     }
 }
@@ -33,10 +117,7 @@ impl Indicator for MovingAverage {
     }
 
     fn update(&mut self, market_data: MarketData) {
-        let current_price = match market_data {
-            MarketData::Bar(bar) => bar.close,
-            MarketData::Tick(tick) => tick.price,
-        };
+        let current_price = price_of(&market_data);
 
         // First pop_back() if length is already at capacity
         // this ensures no new buffer is allocated
@@ -45,14 +126,16 @@ impl Indicator for MovingAverage {
         }
         self.window.push_front(current_price);
 
-        self.value = self.window.iter().sum::<f64>() / self.period as f64;
+        let value = self.window.iter().sum::<f64>() / self.period as f64;
+        self.outputs[0].push(value);
+    }
 
-        // First pop_back() if length is already at capacity
-        // this ensures no new buffer is allocated
-        if self.history.len() == self.history_period {
-            self.history.pop_back();
-        }
-        self.history.push_front(self.value);
+    fn warmup_period(&self) -> usize {
+        self.period
+    }
+
+    fn outputs(&self) -> &[IndicatorSeries] {
+        &self.outputs
     }
 }
 
@@ -60,6 +143,71 @@ impl Index<usize> for MovingAverage {
     type Output = f64;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.history[index]
+        &self.outputs[0][index]
+    }
+}
+
+/// Bollinger Bands with `upper`, `middle` and `lower` outputs
+pub struct BollingerBands {
+    pub period: usize,
+    pub deviations: f64,
+    window: VecDeque<f64>,
+    outputs: [IndicatorSeries; 3],
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, deviations: f64, history: usize) -> Self {
+        Self {
+            period,
+            deviations,
+            window: VecDeque::with_capacity(period),
+            outputs: [
+                IndicatorSeries::new("middle", history),
+                IndicatorSeries::new("upper", history),
+                IndicatorSeries::new("lower", history),
+            ],
+        }
+    }
+
+    pub fn middle(&self) -> &IndicatorSeries {
+        &self.outputs[0]
+    }
+
+    pub fn upper(&self) -> &IndicatorSeries {
+        &self.outputs[1]
+    }
+
+    pub fn lower(&self) -> &IndicatorSeries {
+        &self.outputs[2]
+    }
+}
+
+impl Indicator for BollingerBands {
+    fn is_ready(&self) -> bool {
+        self.window.len() == self.period
+    }
+
+    fn update(&mut self, market_data: MarketData) {
+        if self.window.len() == self.period {
+            self.window.pop_back();
+        }
+        self.window.push_front(price_of(&market_data));
+
+        let count = self.window.len() as f64;
+        let mean = self.window.iter().sum::<f64>() / count;
+        let variance = self.window.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / count;
+        let band = variance.sqrt() * self.deviations;
+
+        self.outputs[0].push(mean);
+        self.outputs[1].push(mean + band);
+        self.outputs[2].push(mean - band);
+    }
+
+    fn warmup_period(&self) -> usize {
+        self.period
+    }
+
+    fn outputs(&self) -> &[IndicatorSeries] {
+        &self.outputs
     }
 }
@@ -1,6 +1,10 @@
 pub mod broker;
+pub mod consolidator;
 pub mod core;
 pub mod data;
 pub mod engine;
 pub mod indicator;
+pub mod order_book;
+pub mod session;
 pub mod strategy;
+pub mod timeframe;
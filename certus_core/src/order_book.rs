@@ -0,0 +1,121 @@
+use crate::data::{BookDelta, BookLevel, BookSide, BookSnapshot, OrderBookUpdate, Quote, Timestamp};
+
+/// In-memory limit order book built from snapshots and deltas
+/// Bids are kept from best (highest) to worst, asks from best (lowest) to worst
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub timestamp: Timestamp,
+    bids: Vec<BookLevel>,
+    asks: Vec<BookLevel>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_snapshot(snapshot: &BookSnapshot) -> Self {
+        let mut book = Self::new();
+        book.apply_snapshot(snapshot);
+        book
+    }
+
+    pub fn apply(&mut self, update: &OrderBookUpdate) {
+        match update {
+            OrderBookUpdate::Snapshot(snapshot) => self.apply_snapshot(snapshot),
+            OrderBookUpdate::Delta(delta) => self.apply_delta(delta),
+        }
+    }
+
+    /// Replace the book, levels without size are dropped and the levels are sorted
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) {
+        self.timestamp = snapshot.timestamp;
+        self.bids = snapshot.bids.iter().copied().filter(|level| level.size > 0.0).collect();
+        self.asks = snapshot.asks.iter().copied().filter(|level| level.size > 0.0).collect();
+        self.bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+        self.asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+    }
+
+    pub fn apply_delta(&mut self, delta: &BookDelta) {
+        self.timestamp = delta.timestamp;
+        self.set_level(delta.side, delta.price, delta.size);
+    }
+
+    /// Set the size at a price, a size of 0 or less removes the level
+    pub fn set_level(&mut self, side: BookSide, price: f64, size: f64) {
+        let levels = self.levels_mut(side);
+        let position = levels.iter().position(|level| !is_better(side, level.price, price));
+        match position {
+            Some(index) if levels[index].price == price => {
+                if size > 0.0 {
+                    levels[index].size = size;
+                } else {
+                    levels.remove(index);
+                }
+            }
+            Some(index) if size > 0.0 => levels.insert(index, BookLevel { price, size }),
+            None if size > 0.0 => levels.push(BookLevel { price, size }),
+            _ => {}
+        }
+    }
+
+    /// Levels of a side, from best to worst
+    pub fn levels(&self, side: BookSide) -> &[BookLevel] {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: BookSide) -> &mut Vec<BookLevel> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        }
+    }
+
+    /// Size resting at a price, 0 without a level
+    pub fn size_at(&self, side: BookSide, price: f64) -> f64 {
+        self.levels(side)
+            .iter()
+            .find(|level| level.price == price)
+            .map_or(0.0, |level| level.size)
+    }
+
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.bids.first().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.asks.first().copied()
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()?.price + self.best_ask()?.price) / 2.0)
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Top of the book as a quote, None when a side is empty
+    pub fn quote(&self) -> Option<Quote> {
+        let bid = self.best_bid()?;
+        let ask = self.best_ask()?;
+        Some(Quote {
+            timestamp: self.timestamp,
+            bid: bid.price,
+            bid_size: bid.size,
+            ask: ask.price,
+            ask_size: ask.size,
+        })
+    }
+}
+
+/// Whether `price` is ahead of `other` on a side of the book
+fn is_better(side: BookSide, price: f64, other: f64) -> bool {
+    match side {
+        BookSide::Bid => price > other,
+        BookSide::Ask => price < other,
+    }
+}
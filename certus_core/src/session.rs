@@ -0,0 +1,207 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+
+use crate::data::Timestamp;
+
+/// enum defining which trading hours of a calendar to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionType {
+    Regular,
+    Extended,
+}
+
+/// Daily trading hours in exchange local time
+/// A close at or before the open means the session spans midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionHours {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl SessionHours {
+    pub fn new(open: NaiveTime, close: NaiveTime) -> Self {
+        Self { open, close }
+    }
+
+    pub fn spans_midnight(&self) -> bool {
+        self.close <= self.open
+    }
+}
+
+/// A single trading session in exchange local time
+/// The trading date is the date on which the session closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub trading_date: NaiveDate,
+    pub open: NaiveDateTime,
+    pub close: NaiveDateTime,
+}
+
+impl Session {
+    pub fn contains(&self, dt: NaiveDateTime) -> bool {
+        dt >= self.open && dt < self.close
+    }
+}
+
+/// struct defining when an exchange is open
+#[derive(Debug, Clone)]
+pub struct TradingCalendar {
+    pub time_zone: Tz,
+    pub regular_hours: SessionHours,
+    pub extended_hours: Option<SessionHours>,
+    pub trading_days: Vec<Weekday>,
+    pub holidays: HashSet<NaiveDate>,
+    pub early_closes: HashMap<NaiveDate, NaiveTime>,
+}
+
+// Number of days searched for the next session, covers long holiday weekends
+const MAX_DAYS_WITHOUT_SESSION: i64 = 14;
+
+fn time(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+impl TradingCalendar {
+    /// Calendar trading Monday through Friday without holidays
+    pub fn new(time_zone: Tz, regular_hours: SessionHours) -> Self {
+        Self {
+            time_zone,
+            regular_hours,
+            extended_hours: None,
+            trading_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            holidays: HashSet::new(),
+            early_closes: HashMap::new(),
+        }
+    }
+
+    /// CME Globex equity index futures, regular trading hours 08:30-15:00 CT
+    /// and the overnight session from 17:00 CT the previous day until 16:00 CT
+    pub fn cme_globex() -> Self {
+        Self::new(chrono_tz::America::Chicago, SessionHours::new(time(8, 30), time(15, 0)))
+            .with_extended_hours(SessionHours::new(time(17, 0), time(16, 0)))
+    }
+
+    /// US equities, regular trading hours 09:30-16:00 ET and extended hours 04:00-20:00 ET
+    pub fn us_equities() -> Self {
+        Self::new(chrono_tz::America::New_York, SessionHours::new(time(9, 30), time(16, 0)))
+            .with_extended_hours(SessionHours::new(time(4, 0), time(20, 0)))
+    }
+
+    pub fn with_extended_hours(mut self, extended_hours: SessionHours) -> Self {
+        self.extended_hours = Some(extended_hours);
+        self
+    }
+
+    pub fn with_holiday(mut self, date: NaiveDate) -> Self {
+        self.holidays.insert(date);
+        self
+    }
+
+    pub fn with_early_close(mut self, date: NaiveDate, close: NaiveTime) -> Self {
+        self.early_closes.insert(date, close);
+        self
+    }
+
+    /// Trading hours for the session type, regular hours when there are no extended hours
+    pub fn hours(&self, session_type: SessionType) -> SessionHours {
+        match session_type {
+            SessionType::Regular => self.regular_hours,
+            SessionType::Extended => self.extended_hours.unwrap_or(self.regular_hours),
+        }
+    }
+
+    pub fn is_trading_day(&self, trading_date: NaiveDate) -> bool {
+        self.trading_days.contains(&trading_date.weekday()) && !self.holidays.contains(&trading_date)
+    }
+
+    /// Session for a trading date, None on weekends and holidays
+    pub fn session(&self, trading_date: NaiveDate, session_type: SessionType) -> Option<Session> {
+        if !self.is_trading_day(trading_date) {
+            return None;
+        }
+
+        let hours = self.hours(session_type);
+        let open = if hours.spans_midnight() {
+            (trading_date - Duration::days(1)).and_time(hours.open)
+        } else {
+            trading_date.and_time(hours.open)
+        };
+        let mut close = trading_date.and_time(hours.close);
+        if let Some(early_close) = self.early_closes.get(&trading_date) {
+            close = close.min(trading_date.and_time(*early_close));
+        }
+
+        if close <= open {
+            return None;
+        }
+
+        Some(Session {
+            trading_date,
+            open,
+            close,
+        })
+    }
+
+    /// Session containing `dt` (exchange local time)
+    pub fn session_at(&self, dt: NaiveDateTime, session_type: SessionType) -> Option<Session> {
+        [dt.date(), dt.date() + Duration::days(1)]
+            .into_iter()
+            .filter_map(|date| self.session(date, session_type))
+            .find(|session| session.contains(dt))
+    }
+
+    pub fn is_session_open(&self, dt: NaiveDateTime, session_type: SessionType) -> bool {
+        self.session_at(dt, session_type).is_some()
+    }
+
+    /// Minutes until the close of the session containing `dt`, None when closed
+    pub fn minutes_to_close(&self, dt: NaiveDateTime, session_type: SessionType) -> Option<i64> {
+        self.session_at(dt, session_type)
+            .map(|session| (session.close - dt).num_minutes())
+    }
+
+    /// First session opening at or after `dt`
+    pub fn next_session(&self, dt: NaiveDateTime, session_type: SessionType) -> Option<Session> {
+        (0..=MAX_DAYS_WITHOUT_SESSION)
+            .map(|days| dt.date() + Duration::days(days))
+            .filter_map(|date| self.session(date, session_type))
+            .find(|session| session.open >= dt)
+    }
+
+    /// `dt` when the session is open, otherwise the open of the next session
+    pub fn next_trading_time(&self, dt: NaiveDateTime, session_type: SessionType) -> Option<NaiveDateTime> {
+        if self.is_session_open(dt, session_type) {
+            return Some(dt);
+        }
+        self.next_session(dt, session_type).map(|session| session.open)
+    }
+
+    /// Convert a UTC time to exchange local time
+    pub fn to_local(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        utc.with_timezone(&self.time_zone).naive_local()
+    }
+
+    pub fn is_session_open_at(&self, timestamp: Timestamp, session_type: SessionType) -> bool {
+        self.is_session_open(timestamp.to_local(&self.time_zone), session_type)
+    }
+
+    pub fn minutes_to_close_at(&self, timestamp: Timestamp, session_type: SessionType) -> Option<i64> {
+        self.minutes_to_close(timestamp.to_local(&self.time_zone), session_type)
+    }
+
+    pub fn next_trading_time_at(&self, timestamp: Timestamp, session_type: SessionType) -> Option<Timestamp> {
+        if self.is_session_open_at(timestamp, session_type) {
+            return Some(timestamp);
+        }
+        self.next_session(timestamp.to_local(&self.time_zone), session_type)
+            .map(|session| Timestamp::from_local(session.open, &self.time_zone))
+    }
+}
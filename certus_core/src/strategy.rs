@@ -1,6 +1,6 @@
-use std::collections::VecDeque;
+use std::{any::Any, collections::VecDeque, fmt, marker::PhantomData};
 
-use crate::{broker::Broker, core::{Order, OrderSide, OrderType}, data::MarketData};
+use crate::{broker::Broker, consolidator::BarConsolidator, core::{Order, OrderSide, OrderType}, data::{Bar, MarketData}, indicator::Indicator, timeframe::Timeframe};
 
 /// trait for trading strategies
 /// init and next methods should be implemented
@@ -19,12 +19,29 @@ pub trait StrategyBase : MarketDataReceiver {
 
     // get instrument id
     fn get_instrument(&self) -> u32;
+
+    /// Get the indicators registered by the strategy
+    /// The engine updates these before next() is called and only calls
+    /// next() once all of them are ready
+    fn get_indicators(&mut self) -> Option<&mut StrategyIndicators> {
+        None
+    }
+
+    /// Get the higher timeframes the strategy subscribed to
+    /// The engine feeds these and calls on_timeframe_bar() for every completed bar
+    fn get_timeframes(&mut self) -> Option<&mut StrategyTimeframes> {
+        None
+    }
 }
 
 pub trait Strategy : MarketDataReceiver + StrategyBase {
     /// Will be called for every new bar or tick, when tick data is supplied
     fn next(&mut self, market_data: MarketData, broker: &mut dyn Broker);
 
+    /// Will be called for every completed bar of a subscribed timeframe, before next()
+    /// The indicators of the timeframe are already updated with the bar
+    fn on_timeframe_bar(&mut self, _timeframe: TimeframeHandle, _bar: Bar, _broker: &mut dyn Broker) {}
+
     /// Easiest method to place an order
     fn place_order(&mut self, broker: &mut dyn Broker, side: OrderSide, order_type: OrderType, size: f64) -> usize {
         broker.place_order(Order {
@@ -32,9 +49,9 @@ pub trait Strategy : MarketDataReceiver + StrategyBase {
             related_id: None,
             instrument: self.get_instrument(),
             strategy_id: self.get_id(),
-            side: side,
-            order_type: order_type,
-            size: size,
+            side,
+            order_type,
+            size,
         }).id.unwrap()
     }
 
@@ -45,13 +62,46 @@ pub trait Strategy : MarketDataReceiver + StrategyBase {
             related_id: Some(related_id),
             instrument: self.get_instrument(),
             strategy_id: self.get_id(),
-            side: side,
-            order_type: order_type,
-            size: size,
+            side,
+            order_type,
+            size,
         }).id.unwrap()
     }
 }
 
+/// Pass market data to the strategies the same way in backtests and live trading
+/// Updates the strategies and their registered indicators, delivers the completed timeframe bars and calls
+/// next() on the strategies whose indicators, including those of their timeframes, are warmed up
+pub fn dispatch_market_data(strategies: &mut [Box<dyn Strategy>], market_data: MarketData, broker: &mut dyn Broker) {
+    for strategy in strategies.iter_mut() {
+        strategy.update(market_data, broker);
+        if let Some(indicators) = strategy.get_indicators() {
+            indicators.update(market_data);
+        }
+    }
+
+    for strategy in strategies.iter_mut() {
+        let Some(timeframes) = strategy.get_timeframes() else {
+            continue;
+        };
+        for (timeframe, bar) in timeframes.update(market_data) {
+            strategy.on_timeframe_bar(timeframe, bar, broker);
+        }
+    }
+
+    for strategy in strategies.iter_mut() {
+        let is_ready = strategy
+            .get_indicators()
+            .is_none_or(|indicators| indicators.is_ready())
+            && strategy
+                .get_timeframes()
+                .is_none_or(|timeframes| timeframes.is_ready());
+        if is_ready {
+            strategy.next(market_data, broker);
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct StrategyData {
     pub max_data_back: usize,
@@ -79,3 +129,176 @@ impl MarketDataReceiver for StrategyData {
         }
     }
 }
+
+/// Typed handle to an indicator registered with `StrategyIndicators`
+pub struct IndicatorHandle<T> {
+    index: usize,
+    _indicator: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for IndicatorHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for IndicatorHandle<T> {}
+
+impl<T> fmt::Debug for IndicatorHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IndicatorHandle({})", self.index)
+    }
+}
+
+/// Container for the indicators of a strategy
+/// Keeps the indicators updated and tracks the warmup of the strategy
+#[derive(Default)]
+pub struct StrategyIndicators {
+    indicators: Vec<Box<dyn Indicator>>,
+    updates: usize,
+}
+
+impl StrategyIndicators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an indicator, the returned handle is used to access it
+    pub fn register<T: Indicator>(&mut self, indicator: T) -> IndicatorHandle<T> {
+        self.indicators.push(Box::new(indicator));
+        IndicatorHandle {
+            index: self.indicators.len() - 1,
+            _indicator: PhantomData,
+        }
+    }
+
+    pub fn get<T: Indicator>(&self, handle: IndicatorHandle<T>) -> &T {
+        let indicator: &dyn Any = self.indicators[handle.index].as_ref();
+        indicator
+            .downcast_ref::<T>()
+            .expect("indicator handle belongs to a different container")
+    }
+
+    pub fn get_mut<T: Indicator>(&mut self, handle: IndicatorHandle<T>) -> &mut T {
+        let indicator: &mut dyn Any = self.indicators[handle.index].as_mut();
+        indicator
+            .downcast_mut::<T>()
+            .expect("indicator handle belongs to a different container")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Indicator> {
+        self.indicators.iter().map(|indicator| indicator.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.indicators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indicators.is_empty()
+    }
+
+    /// Number of updates needed before all registered indicators are ready
+    pub fn warmup_period(&self) -> usize {
+        self.indicators
+            .iter()
+            .map(|indicator| indicator.warmup_period())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of updates received so far
+    pub fn updates(&self) -> usize {
+        self.updates
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.updates >= self.warmup_period()
+            && self.indicators.iter().all(|indicator| indicator.is_ready())
+    }
+
+    pub fn update(&mut self, market_data: MarketData) {
+        self.updates += 1;
+        for indicator in self.indicators.iter_mut() {
+            indicator.update(market_data);
+        }
+    }
+}
+
+/// Handle to a timeframe subscribed with `StrategyTimeframes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeframeHandle(usize);
+
+/// A consolidated timeframe with its own indicators
+pub struct TimeframeData {
+    consolidator: BarConsolidator,
+    pub indicators: StrategyIndicators,
+    pub last_bar: Option<Bar>,
+}
+
+impl TimeframeData {
+    pub fn timeframe(&self) -> Timeframe {
+        self.consolidator.output
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.last_bar.is_some() && self.indicators.is_ready()
+    }
+}
+
+/// Container for the higher timeframes of a strategy
+#[derive(Default)]
+pub struct StrategyTimeframes {
+    timeframes: Vec<TimeframeData>,
+}
+
+impl StrategyTimeframes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to the bars completed by the consolidator
+    pub fn subscribe(&mut self, consolidator: BarConsolidator) -> TimeframeHandle {
+        self.timeframes.push(TimeframeData {
+            consolidator,
+            indicators: StrategyIndicators::new(),
+            last_bar: None,
+        });
+        TimeframeHandle(self.timeframes.len() - 1)
+    }
+
+    pub fn get(&self, handle: TimeframeHandle) -> &TimeframeData {
+        &self.timeframes[handle.0]
+    }
+
+    pub fn get_mut(&mut self, handle: TimeframeHandle) -> &mut TimeframeData {
+        &mut self.timeframes[handle.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.timeframes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timeframes.is_empty()
+    }
+
+    /// Whether the indicators of every timeframe are warmed up
+    pub fn is_ready(&self) -> bool {
+        self.timeframes.iter().all(|timeframe| timeframe.indicators.is_ready())
+    }
+
+    /// Feed market data to all timeframes, returns the completed bars
+    /// after the indicators of their timeframe have been updated
+    pub fn update(&mut self, market_data: MarketData) -> Vec<(TimeframeHandle, Bar)> {
+        let mut completed = Vec::new();
+        for (index, timeframe) in self.timeframes.iter_mut().enumerate() {
+            for bar in timeframe.consolidator.update(market_data) {
+                timeframe.indicators.update(MarketData::Bar(bar));
+                timeframe.last_bar = Some(bar);
+                completed.push((TimeframeHandle(index), bar));
+            }
+        }
+        completed
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+
+use crate::data::Timestamp;
+
+/// enum defining the timeframe of a bar
+/// Buckets are aligned to the session open, see `Timeframe::bucket_start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeframe {
+    Seconds(u32),
+    Minutes(u32),
+    Hours(u32),
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+}
+
+impl Timeframe {
+    /// Fixed length of the timeframe in seconds, None for months
+    pub fn seconds(&self) -> Option<i64> {
+        match *self {
+            Timeframe::Seconds(n) => Some(n as i64),
+            Timeframe::Minutes(n) => Some(n as i64 * 60),
+            Timeframe::Hours(n) => Some(n as i64 * 3_600),
+            Timeframe::Days(n) => Some(n as i64 * 86_400),
+            Timeframe::Weeks(n) => Some(n as i64 * 7 * 86_400),
+            Timeframe::Months(_) => None,
+        }
+    }
+
+    fn count(&self) -> u32 {
+        match *self {
+            Timeframe::Seconds(n)
+            | Timeframe::Minutes(n)
+            | Timeframe::Hours(n)
+            | Timeframe::Days(n)
+            | Timeframe::Weeks(n)
+            | Timeframe::Months(n) => n,
+        }
+    }
+
+    /// # Panics
+    /// Panics on a count of 0, such a timeframe has no buckets
+    fn assert_valid(&self) {
+        assert!(self.count() > 0, "timeframe ({}) must have a count of at least 1", self);
+    }
+
+    fn is_intraday(&self) -> bool {
+        matches!(
+            self,
+            Timeframe::Seconds(_) | Timeframe::Minutes(_) | Timeframe::Hours(_)
+        )
+    }
+
+    /// Whether buckets of this timeframe are made up of whole buckets of `input`
+    pub fn is_multiple_of(&self, input: &Timeframe) -> bool {
+        if self.count() == 0 || input.count() == 0 {
+            return false;
+        }
+        match (self.seconds(), input.seconds()) {
+            (Some(output), Some(input)) => output % input == 0,
+            // Months are made up of whole days
+            (None, Some(input)) => 86_400 % input == 0,
+            (None, None) => self.count().is_multiple_of(input.count()),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Start of the bucket containing `dt`
+    /// A trading day runs for 24 hours from `session_open`. Intraday buckets are
+    /// counted from the open of the trading day, daily and longer buckets start
+    /// at the open of their first trading day.
+    /// # Panics
+    /// Panics on timeframes with a count of 0, as do the other bucket functions
+    pub fn bucket_start(&self, dt: NaiveDateTime, session_open: NaiveTime) -> NaiveDateTime {
+        self.assert_valid();
+        let day = trading_date(dt, session_open);
+        let day_start = session_start(day, session_open);
+
+        match *self {
+            Timeframe::Seconds(_) | Timeframe::Minutes(_) | Timeframe::Hours(_) => {
+                let bucket_seconds = self.seconds().unwrap();
+                let elapsed = (dt - day_start).num_seconds();
+                day_start + Duration::seconds(elapsed - elapsed.rem_euclid(bucket_seconds))
+            }
+            Timeframe::Days(n) => {
+                let days = day.num_days_from_ce() as i64 - 1;
+                let start = day - Duration::days(days.rem_euclid(n as i64));
+                session_start(start, session_open)
+            }
+            Timeframe::Weeks(n) => {
+                // Day 1 of the common era is a Monday
+                let weeks = (day.num_days_from_ce() as i64 - 1).div_euclid(7);
+                let monday = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                let start = monday - Duration::weeks(weeks.rem_euclid(n as i64));
+                session_start(start, session_open)
+            }
+            Timeframe::Months(n) => {
+                let months = day.year() as i64 * 12 + day.month0() as i64;
+                let start_month = months - months.rem_euclid(n as i64);
+                let start = NaiveDate::from_ymd_opt(
+                    start_month.div_euclid(12) as i32,
+                    start_month.rem_euclid(12) as u32 + 1,
+                    1,
+                )
+                .unwrap();
+                session_start(start, session_open)
+            }
+        }
+    }
+
+    /// End (exclusive) of the bucket starting at `bucket_start`
+    pub fn bucket_end(&self, bucket_start: NaiveDateTime, session_open: NaiveTime) -> NaiveDateTime {
+        self.assert_valid();
+        if self.is_intraday() {
+            return bucket_start + Duration::seconds(self.seconds().unwrap());
+        }
+
+        let day = trading_date(bucket_start, session_open);
+        let end = match *self {
+            Timeframe::Months(n) => day + Months::new(n),
+            _ => day + Duration::seconds(self.seconds().unwrap()),
+        };
+        session_start(end, session_open)
+    }
+
+    /// Start of the bucket containing `timestamp`, with the session open in `time_zone`
+    /// Intraday buckets are counted in elapsed time from the session open, so
+    /// they keep their length on days with a DST transition
+    pub fn bucket_start_at(&self, timestamp: Timestamp, session_open: NaiveTime, time_zone: &Tz) -> Timestamp {
+        self.assert_valid();
+        let local = timestamp.to_local(time_zone);
+        if !self.is_intraday() {
+            return Timestamp::from_local(self.bucket_start(local, session_open), time_zone);
+        }
+
+        let day_start = session_start(trading_date(local, session_open), session_open);
+        let day_start = Timestamp::from_local(day_start, time_zone);
+        let bucket_nanos = self.seconds().unwrap() * 1_000_000_000;
+        let elapsed = (timestamp - day_start).num_nanoseconds().unwrap();
+        day_start + Duration::nanoseconds(elapsed - elapsed.rem_euclid(bucket_nanos))
+    }
+
+    /// End (exclusive) of the bucket starting at `bucket_start`, with the session open in `time_zone`
+    pub fn bucket_end_at(&self, bucket_start: Timestamp, session_open: NaiveTime, time_zone: &Tz) -> Timestamp {
+        self.assert_valid();
+        if self.is_intraday() {
+            return bucket_start + Duration::seconds(self.seconds().unwrap());
+        }
+
+        let local = bucket_start.to_local(time_zone);
+        Timestamp::from_local(self.bucket_end(local, session_open), time_zone)
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Timeframe::Seconds(n) => write!(f, "{}s", n),
+            Timeframe::Minutes(n) => write!(f, "{}m", n),
+            Timeframe::Hours(n) => write!(f, "{}h", n),
+            Timeframe::Days(n) => write!(f, "{}D", n),
+            Timeframe::Weeks(n) => write!(f, "{}W", n),
+            Timeframe::Months(n) => write!(f, "{}M", n),
+        }
+    }
+}
+
+/// Trading date of `dt`, the date of the middle of its 24 hour trading day
+/// e.g. with a 17:00 session open, Sunday 18:00 belongs to Monday
+pub fn trading_date(dt: NaiveDateTime, session_open: NaiveTime) -> NaiveDate {
+    let since_open = dt.time() - session_open;
+    let day_start = if since_open < Duration::zero() {
+        dt.date() - Duration::days(1)
+    } else {
+        dt.date()
+    };
+    (day_start.and_time(session_open) + Duration::hours(12)).date()
+}
+
+/// Start of the trading day with trading date `day`
+pub fn session_start(day: NaiveDate, session_open: NaiveTime) -> NaiveDateTime {
+    if session_open < NaiveTime::from_hms_opt(12, 0, 0).unwrap() {
+        day.and_time(session_open)
+    } else {
+        (day - Duration::days(1)).and_time(session_open)
+    }
+}
@@ -0,0 +1,210 @@
+use certus_core::consolidator::{BarConsolidator, QuoteBarAggregator, TickBarAggregator};
+use certus_core::data::{Bar, MarketData, Quote, Tick, Timestamp};
+use certus_core::indicator::{Indicator, MovingAverage};
+use certus_core::session::{SessionType, TradingCalendar};
+use certus_core::timeframe::Timeframe;
+use certus_core::strategy::StrategyTimeframes;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 1, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn create_bar(date: NaiveDateTime, open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_naive_utc(date),
+        open,
+        high,
+        low,
+        close,
+        volume: 100.0,
+    })
+}
+
+#[test]
+fn emits_bar_when_last_input_bar_of_bucket_arrives() {
+    let mut consolidator = BarConsolidator::new(1, 5);
+
+    for minute in 0..4 {
+        let completed = consolidator.update(create_bar(at(2, 9, minute), 100.0, 101.0, 99.0, 100.5));
+        assert!(completed.is_empty(), "bucket must not complete early");
+    }
+
+    let completed = consolidator.update(create_bar(at(2, 9, 4), 100.5, 103.0, 98.0, 102.0));
+    assert_eq!(completed.len(), 1);
+    let bar = completed[0];
+    assert_eq!(bar.timestamp.naive_utc(), at(2, 9, 0));
+    assert_eq!(bar.open, 100.0);
+    assert_eq!(bar.high, 103.0);
+    assert_eq!(bar.low, 98.0);
+    assert_eq!(bar.close, 102.0);
+    assert_eq!(bar.volume, 500.0);
+}
+
+#[test]
+fn emits_incomplete_bucket_when_next_bucket_starts() {
+    let mut consolidator = BarConsolidator::new(5, 10);
+
+    assert!(consolidator.update(create_bar(at(2, 9, 0), 100.0, 101.0, 99.0, 100.0)).is_empty());
+
+    // 9:05 is missing, 9:15 completes both the 9:00 and the 9:10 bucket
+    let completed = consolidator.update(create_bar(at(2, 9, 15), 105.0, 106.0, 104.0, 105.5));
+    assert_eq!(completed.len(), 2);
+    assert_eq!(completed[0].timestamp.naive_utc(), at(2, 9, 0));
+    assert_eq!(completed[0].close, 100.0);
+    assert_eq!(completed[1].timestamp.naive_utc(), at(2, 9, 10));
+    assert_eq!(completed[1].close, 105.5);
+}
+
+#[test]
+fn daily_buckets_align_to_midnight() {
+    let mut consolidator = BarConsolidator::new(60, 1440);
+
+    assert!(consolidator.update(create_bar(at(2, 22, 0), 100.0, 101.0, 99.0, 100.0)).is_empty());
+    let completed = consolidator.update(create_bar(at(2, 23, 0), 100.0, 102.0, 99.0, 101.0));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].timestamp.naive_utc(), at(2, 0, 0));
+    assert_eq!(completed[0].high, 102.0);
+    assert!(consolidator.flush().is_none());
+}
+
+#[test]
+fn timeframe_indicators_only_see_completed_bars() {
+    let mut timeframes = StrategyTimeframes::new();
+    let m5 = timeframes.subscribe(BarConsolidator::new(1, 5));
+    let ma = timeframes
+        .get_mut(m5)
+        .indicators
+        .register(MovingAverage::new(2, 5));
+
+    let mut completed = Vec::new();
+    for minute in 0..10 {
+        let close = 100.0 + minute as f64;
+        completed.extend(timeframes.update(create_bar(at(2, 9, minute), close, close, close, close)));
+        if minute < 9 {
+            assert!(!timeframes.get(m5).is_ready());
+        }
+    }
+
+    assert_eq!(completed.len(), 2);
+    assert!(completed.iter().all(|(handle, _)| *handle == m5));
+    let timeframe = timeframes.get(m5);
+    assert!(timeframe.is_ready());
+    assert_eq!(timeframe.indicators.get(ma).value(), Some((104.0 + 109.0) / 2.0));
+    assert_eq!(timeframe.last_bar.unwrap().close, 109.0);
+}
+
+fn create_tick(date: NaiveDateTime, price: f64, size: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_naive_utc(date),
+        price,
+        size,
+    })
+}
+
+#[test]
+fn tick_timestamp_is_utc_nanoseconds() {
+    let date = at(2, 9, 30);
+    let MarketData::Tick(tick) = create_tick(date, 1.0, 1.0) else {
+        unreachable!()
+    };
+    assert_eq!(tick.timestamp.nanos(), date.and_utc().timestamp_nanos_opt().unwrap());
+    assert_eq!(tick.timestamp.naive_utc(), date);
+}
+
+#[test]
+fn ticks_are_aggregated_into_time_bars() {
+    let mut aggregator = TickBarAggregator::new(1);
+
+    assert!(aggregator.update(create_tick(at(2, 9, 0), 100.0, 2.0)).is_none());
+    assert!(aggregator.update(create_tick(at(2, 9, 0), 101.0, 1.0)).is_none());
+    assert!(aggregator.update(create_tick(at(2, 9, 0), 99.5, 3.0)).is_none());
+    assert!(aggregator.update(create_tick(at(2, 9, 0), 100.5, 1.0)).is_none());
+
+    // The first tick of the next minute completes the 9:00 bar, 9:01 has no ticks
+    let bar = aggregator
+        .update(create_tick(at(2, 9, 2), 102.0, 5.0))
+        .expect("expected completed bar");
+    assert_eq!(bar.timestamp.naive_utc(), at(2, 9, 0));
+    assert_eq!(bar.open, 100.0);
+    assert_eq!(bar.high, 101.0);
+    assert_eq!(bar.low, 99.5);
+    assert_eq!(bar.close, 100.5);
+    assert_eq!(bar.volume, 7.0);
+
+    let bar = aggregator.flush().expect("expected partial bar");
+    assert_eq!(bar.timestamp.naive_utc(), at(2, 9, 2));
+    assert_eq!(bar.volume, 5.0);
+    assert!(aggregator.flush().is_none());
+}
+
+#[test]
+fn quotes_are_aggregated_into_quote_bars() {
+    let quote = |date: NaiveDateTime, bid: f64, ask: f64| {
+        MarketData::Quote(Quote {
+            timestamp: Timestamp::from_naive_utc(date),
+            bid,
+            bid_size: 1.0,
+            ask,
+            ask_size: 2.0,
+        })
+    };
+    let mut aggregator = QuoteBarAggregator::new(1);
+
+    assert!(aggregator.update(quote(at(2, 9, 0), 100.0, 100.5)).is_none());
+    assert!(aggregator.update(quote(at(2, 9, 0), 99.5, 100.25)).is_none());
+    // Ticks are not quotes
+    assert!(aggregator.update(create_tick(at(2, 9, 1), 1.0, 1.0)).is_none());
+
+    let quote_bar = aggregator
+        .update(quote(at(2, 9, 1), 101.0, 101.5))
+        .expect("expected completed quote bar");
+    assert_eq!(quote_bar.timestamp.naive_utc(), at(2, 9, 0));
+    assert_eq!((quote_bar.bid_open, quote_bar.bid_low, quote_bar.bid_close), (100.0, 99.5, 99.5));
+    assert_eq!((quote_bar.ask_high, quote_bar.ask_close), (100.5, 100.25));
+    assert_eq!(aggregator.flush().unwrap().timestamp.naive_utc(), at(2, 9, 1));
+    assert!(aggregator.flush().is_none());
+}
+
+#[test]
+fn calendar_completes_daily_bar_at_session_close() {
+    let mut consolidator = BarConsolidator::from_timeframes(Timeframe::Minutes(60), Timeframe::Days(1))
+        .with_calendar(TradingCalendar::us_equities(), SessionType::Regular);
+
+    // Session hours are New York time
+    let local = |hour: u32, minute: u32| {
+        Timestamp::from_local(at(3, hour, minute), &chrono_tz::America::New_York).naive_utc()
+    };
+
+    // Pre-market bar is dropped
+    assert!(consolidator.update(create_bar(local(8, 0), 90.0, 90.0, 90.0, 90.0)).is_empty());
+    assert!(consolidator.update(create_bar(local(9, 30), 100.0, 102.0, 99.0, 101.0)).is_empty());
+    let completed = consolidator.update(create_bar(local(15, 0), 101.0, 103.0, 100.0, 102.0));
+
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].timestamp.naive_utc(), local(9, 30));
+    assert_eq!(completed[0].open, 100.0);
+    assert_eq!(completed[0].low, 99.0);
+    assert_eq!(completed[0].close, 102.0);
+    assert!(consolidator.flush().is_none());
+}
+
+#[test]
+fn buckets_follow_the_session_open_across_dst() {
+    // 17:00 CT is 23:00 UTC in winter and 22:00 UTC in summer
+    let mut consolidator = BarConsolidator::from_timeframes(Timeframe::Hours(1), Timeframe::Days(1))
+        .with_session_open(NaiveTime::from_hms_opt(17, 0, 0).unwrap())
+        .with_time_zone(chrono_tz::America::Chicago);
+
+    let winter = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap().and_hms_opt(23, 0, 0).unwrap();
+    let summer = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap().and_hms_opt(22, 0, 0).unwrap();
+    assert!(consolidator.update(create_bar(winter, 100.0, 101.0, 99.0, 100.0)).is_empty());
+
+    let completed = consolidator.update(create_bar(summer, 100.0, 101.0, 99.0, 100.0));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].timestamp.naive_utc(), winter);
+    assert_eq!(consolidator.flush().unwrap().timestamp.naive_utc(), summer);
+}
@@ -0,0 +1,138 @@
+use certus_core::data::{Bar, DataHandlerError, MarketData, MarketDataValidator, Timestamp};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use chrono_tz::America::Chicago;
+
+fn at(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+#[test]
+fn local_time_converts_with_dst_offset() {
+    // CST is UTC-6, CDT is UTC-5
+    let winter = Timestamp::from_local(at(1, 2, 8, 30), &Chicago);
+    let summer = Timestamp::from_local(at(7, 2, 8, 30), &Chicago);
+
+    assert_eq!(winter.naive_utc(), at(1, 2, 14, 30));
+    assert_eq!(summer.naive_utc(), at(7, 2, 13, 30));
+    assert_eq!(summer.to_local(&Chicago), at(7, 2, 8, 30));
+}
+
+#[test]
+fn local_time_inside_dst_gap_moves_forward() {
+    // 02:30 doesn't exist on 2024-03-10, clocks jump from 02:00 to 03:00
+    let timestamp = Timestamp::from_local(at(3, 10, 2, 30), &Chicago);
+    assert_eq!(timestamp.to_local(&Chicago), at(3, 10, 3, 30));
+}
+
+#[test]
+fn ambiguous_local_time_resolves_to_earliest() {
+    // 01:30 occurs twice on 2024-11-03, first in CDT
+    let timestamp = Timestamp::from_local(at(11, 3, 1, 30), &Chicago);
+    assert_eq!(timestamp.naive_utc(), at(11, 3, 6, 30));
+}
+
+#[test]
+fn timestamp_conversions_and_arithmetic() {
+    let timestamp = Timestamp::from_naive_utc(at(1, 2, 14, 30));
+    assert_eq!(Timestamp::from_seconds(timestamp.nanos() / 1_000_000_000), timestamp);
+    assert_eq!(Timestamp::from_millis(timestamp.nanos() / 1_000_000), timestamp);
+    assert_eq!(Timestamp::checked_from_seconds(timestamp.nanos() / 1_000_000_000), Some(timestamp));
+    assert_eq!(Timestamp::checked_from_micros(timestamp.nanos() / 1_000), Some(timestamp));
+    // Millisecond epochs read as seconds are far beyond 2262
+    assert_eq!(Timestamp::checked_from_seconds(timestamp.nanos() / 1_000_000), None);
+    assert_eq!(Timestamp::checked_from_millis(i64::MIN / 1_000), None);
+
+    let later = timestamp + Duration::minutes(5);
+    assert!(later > timestamp);
+    assert_eq!(later - timestamp, Duration::minutes(5));
+    assert_eq!(later - Duration::minutes(5), timestamp);
+    assert_eq!(timestamp.to_string(), "2024-01-02T14:30:00Z");
+}
+
+#[test]
+fn bars_and_ticks_share_the_timestamp_type() {
+    let timestamp = Timestamp::from_naive_utc(at(1, 2, 14, 30));
+    let bar = MarketData::Bar(Bar {
+        timestamp,
+        open: 1.0,
+        high: 1.0,
+        low: 1.0,
+        close: 1.0,
+        volume: 1.0,
+    });
+
+    assert_eq!(bar.timestamp(), timestamp);
+    assert_eq!(bar.timestamp().in_time_zone(&Chicago).to_rfc3339(), "2024-01-02T08:30:00-06:00");
+}
+
+fn bar(seconds: i64, open: f64, high: f64, low: f64, close: f64) -> MarketData {
+    MarketData::Bar(Bar {
+        timestamp: Timestamp::from_seconds(seconds),
+        open,
+        high,
+        low,
+        close,
+        volume: 1.0,
+    })
+}
+
+#[test]
+fn ohlc_relationships_are_checked() {
+    let MarketData::Bar(valid) = bar(0, 100.0, 101.0, 99.0, 100.5) else {
+        unreachable!()
+    };
+    assert!(valid.ohlc_error().is_none());
+
+    for invalid in [
+        bar(0, 100.0, 99.0, 101.0, 100.0),
+        bar(0, 102.0, 101.0, 99.0, 100.0),
+        bar(0, 100.0, 101.0, 99.0, 98.0),
+        bar(0, f64::NAN, 101.0, 99.0, 100.0),
+    ] {
+        let MarketData::Bar(invalid) = invalid else {
+            unreachable!()
+        };
+        assert!(invalid.ohlc_error().is_some());
+    }
+}
+
+#[test]
+fn validator_rejects_invalid_and_out_of_order_rows() {
+    let mut validator = MarketDataValidator::new();
+    assert!(validator.validate(&bar(60, 100.0, 101.0, 99.0, 100.0), Some(2)).is_ok());
+
+    let error = validator
+        .validate(&bar(120, 100.0, 99.0, 101.0, 100.0), Some(3))
+        .unwrap_err();
+    assert!(matches!(error, DataHandlerError::InvalidOhlc { line: Some(3), .. }));
+
+    let error = validator
+        .validate(&bar(0, 100.0, 101.0, 99.0, 100.0), Some(4))
+        .unwrap_err();
+    assert_eq!(
+        error,
+        DataHandlerError::OutOfOrder {
+            line: Some(4),
+            timestamp: Timestamp::from_seconds(0),
+            previous: Timestamp::from_seconds(60),
+        }
+    );
+    assert!(error.is_row_error());
+    assert!(error.to_string().starts_with("line 4: "));
+
+    // Rejected rows do not move the previous timestamp
+    assert!(validator.validate(&bar(60, 100.0, 101.0, 99.0, 100.0), Some(5)).is_ok());
+}
+
+#[test]
+fn file_errors_are_not_row_errors() {
+    let error = DataHandlerError::FileNotFound {
+        path: String::from("bars.csv"),
+    };
+    assert!(!error.is_row_error());
+    assert_eq!(error.line(), None);
+    assert!(error.to_string().contains("bars.csv"));
+}
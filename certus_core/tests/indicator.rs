@@ -0,0 +1,66 @@
+use certus_core::data::{MarketData, Tick, Timestamp};
+use certus_core::indicator::{BollingerBands, Indicator, IndicatorSeries, MovingAverage};
+
+fn make_tick(price: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_nanos(0),
+        price,
+        size: 1.0,
+    })
+}
+
+#[test]
+fn series_keeps_bounded_history_newest_first() {
+    let mut series = IndicatorSeries::new("test", 3);
+    assert_eq!(series.value(), None);
+
+    for value in [1.0, 2.0, 3.0, 4.0] {
+        series.push(value);
+    }
+
+    assert_eq!(series.len(), 3);
+    assert_eq!(series.value(), Some(4.0));
+    assert_eq!(series[0], 4.0);
+    assert_eq!(series[2], 2.0);
+    assert_eq!(series.get(3), None);
+}
+
+#[test]
+fn moving_average_exposes_primary_output() {
+    let mut ma = MovingAverage::new(3, 10);
+    for price in [1.0, 2.0, 3.0] {
+        ma.update(make_tick(price));
+    }
+    assert!(ma.is_ready());
+    assert_eq!(ma.value(), Some(2.0));
+
+    ma.update(make_tick(7.0));
+    assert_eq!(ma.value(), Some(4.0));
+    assert_eq!(ma[0], 4.0);
+    assert_eq!(ma[1], 2.0);
+
+    let outputs = ma.outputs();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].name(), "ma");
+    assert_eq!(ma.output("ma").unwrap()[1], 2.0);
+    assert!(ma.output("missing").is_none());
+}
+
+#[test]
+fn bollinger_bands_expose_named_outputs() {
+    let mut bands = BollingerBands::new(4, 2.0, 5);
+    for price in [2.0, 4.0, 4.0, 6.0] {
+        bands.update(make_tick(price));
+    }
+    assert!(bands.is_ready());
+
+    // mean 4, population std dev sqrt(2)
+    let band = 2.0 * 2.0_f64.sqrt();
+    assert_eq!(bands.value(), Some(4.0));
+    assert_eq!(bands.output("middle").unwrap().value(), Some(4.0));
+    assert!((bands.upper()[0] - (4.0 + band)).abs() < 1e-12);
+    assert!((bands.lower()[0] - (4.0 - band)).abs() < 1e-12);
+
+    let names: Vec<&str> = bands.outputs().iter().map(|s| s.name()).collect();
+    assert_eq!(names, vec!["middle", "upper", "lower"]);
+}
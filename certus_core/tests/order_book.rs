@@ -0,0 +1,59 @@
+use certus_core::data::{BookDelta, BookLevel, BookSide, BookSnapshot, OrderBookUpdate, Timestamp};
+use certus_core::order_book::OrderBook;
+
+fn level(price: f64, size: f64) -> BookLevel {
+    BookLevel { price, size }
+}
+
+fn snapshot() -> BookSnapshot {
+    BookSnapshot {
+        timestamp: Timestamp::from_seconds(1),
+        // Given out of order, the book sorts them
+        bids: vec![level(99.0, 5.0), level(99.5, 2.0), level(98.5, 0.0)],
+        asks: vec![level(100.5, 4.0), level(100.0, 1.0)],
+    }
+}
+
+fn delta(side: BookSide, price: f64, size: f64) -> OrderBookUpdate {
+    OrderBookUpdate::Delta(BookDelta {
+        timestamp: Timestamp::from_seconds(2),
+        side,
+        price,
+        size,
+    })
+}
+
+#[test]
+fn snapshot_sorts_levels_from_best_to_worst() {
+    let book = OrderBook::from_snapshot(&snapshot());
+
+    assert_eq!(book.levels(BookSide::Bid), &[level(99.5, 2.0), level(99.0, 5.0)]);
+    assert_eq!(book.levels(BookSide::Ask), &[level(100.0, 1.0), level(100.5, 4.0)]);
+    assert_eq!(book.mid(), Some(99.75));
+    assert_eq!(book.spread(), Some(0.5));
+
+    let quote = book.quote().unwrap();
+    assert_eq!(quote.bid, 99.5);
+    assert_eq!(quote.ask_size, 1.0);
+}
+
+#[test]
+fn deltas_insert_update_and_remove_levels() {
+    let mut book = OrderBook::new();
+    assert!(book.quote().is_none());
+    book.apply(&OrderBookUpdate::Snapshot(snapshot()));
+
+    book.apply(&delta(BookSide::Bid, 99.75, 3.0));
+    book.apply(&delta(BookSide::Bid, 99.0, 6.0));
+    book.apply(&delta(BookSide::Ask, 100.0, 0.0));
+    book.apply(&delta(BookSide::Ask, 101.0, 2.0));
+
+    assert_eq!(
+        book.levels(BookSide::Bid),
+        &[level(99.75, 3.0), level(99.5, 2.0), level(99.0, 6.0)]
+    );
+    assert_eq!(book.levels(BookSide::Ask), &[level(100.5, 4.0), level(101.0, 2.0)]);
+    assert_eq!(book.size_at(BookSide::Bid, 99.0), 6.0);
+    assert_eq!(book.size_at(BookSide::Ask, 100.0), 0.0);
+    assert_eq!(book.timestamp, Timestamp::from_seconds(2));
+}
@@ -0,0 +1,107 @@
+use certus_core::core::{Instrument, InstrumentType};
+use certus_core::session::{SessionHours, SessionType, TradingCalendar};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+
+fn at(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+#[test]
+fn regular_hours_on_trading_days() {
+    let calendar = TradingCalendar::us_equities();
+
+    // Tuesday 2024-03-05
+    assert!(!calendar.is_session_open(at(3, 5, 9, 29), SessionType::Regular));
+    assert!(calendar.is_session_open(at(3, 5, 9, 30), SessionType::Regular));
+    assert!(calendar.is_session_open(at(3, 5, 15, 59), SessionType::Regular));
+    assert!(!calendar.is_session_open(at(3, 5, 16, 0), SessionType::Regular));
+    assert!(calendar.is_session_open(at(3, 5, 18, 0), SessionType::Extended));
+
+    // Saturday
+    assert!(!calendar.is_session_open(at(3, 9, 12, 0), SessionType::Regular));
+    assert_eq!(calendar.minutes_to_close(at(3, 5, 15, 0), SessionType::Regular), Some(60));
+    assert_eq!(calendar.minutes_to_close(at(3, 5, 17, 0), SessionType::Regular), None);
+}
+
+#[test]
+fn overnight_session_spans_midnight() {
+    let calendar = TradingCalendar::cme_globex();
+
+    // Sunday evening opens Monday's session
+    let session = calendar
+        .session_at(at(3, 3, 18, 0), SessionType::Extended)
+        .expect("expected Sunday evening session");
+    assert_eq!(session.trading_date, date(3, 4));
+    assert_eq!(session.open, at(3, 3, 17, 0));
+    assert_eq!(session.close, at(3, 4, 16, 0));
+
+    assert!(calendar.is_session_open(at(3, 5, 2, 0), SessionType::Extended));
+    assert!(!calendar.is_session_open(at(3, 5, 16, 30), SessionType::Extended));
+    assert!(!calendar.is_session_open(at(3, 5, 2, 0), SessionType::Regular));
+    // Friday evening has no session
+    assert!(!calendar.is_session_open(at(3, 8, 18, 0), SessionType::Extended));
+    assert_eq!(
+        calendar.minutes_to_close(at(3, 5, 23, 0), SessionType::Extended),
+        Some(17 * 60)
+    );
+}
+
+#[test]
+fn holidays_and_early_closes() {
+    let calendar = TradingCalendar::us_equities()
+        .with_holiday(date(7, 4))
+        .with_early_close(date(7, 3), NaiveTime::from_hms_opt(13, 0, 0).unwrap());
+
+    assert!(calendar.is_session_open(at(7, 3, 12, 59), SessionType::Regular));
+    assert!(!calendar.is_session_open(at(7, 3, 13, 0), SessionType::Regular));
+    assert!(!calendar.is_session_open(at(7, 3, 15, 0), SessionType::Extended));
+    assert!(!calendar.is_session_open(at(7, 4, 10, 0), SessionType::Regular));
+
+    let next = calendar
+        .next_session(at(7, 3, 13, 0), SessionType::Regular)
+        .expect("expected next session");
+    assert_eq!(next.open, at(7, 5, 9, 30));
+    assert_eq!(
+        calendar.next_trading_time(at(7, 3, 12, 0), SessionType::Regular),
+        Some(at(7, 3, 12, 0))
+    );
+}
+
+#[test]
+fn custom_calendar_and_time_zone_conversion() {
+    let calendar = TradingCalendar::new(
+        chrono_tz::Europe::London,
+        SessionHours::new(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(16, 30, 0).unwrap(),
+        ),
+    );
+    assert_eq!(calendar.hours(SessionType::Extended), calendar.regular_hours);
+
+    // British summer time, UTC+1
+    let utc = DateTime::from_timestamp(at(7, 2, 7, 30).and_utc().timestamp(), 0).unwrap();
+    assert_eq!(calendar.to_local(utc), at(7, 2, 8, 30));
+    assert!(calendar.is_session_open(calendar.to_local(utc), SessionType::Regular));
+}
+
+#[test]
+fn instrument_carries_calendar() {
+    let instrument = Instrument::new(
+        String::from("ES"),
+        Some(String::from("CME")),
+        InstrumentType::ContinuousFutures {
+            big_point_value: 50.0,
+        },
+    )
+    .with_calendar(TradingCalendar::cme_globex());
+
+    let calendar = instrument.calendar.as_ref().unwrap();
+    assert!(calendar.is_session_open(at(3, 5, 10, 0), SessionType::Regular));
+}
@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use certus_core::broker::Broker;
+use certus_core::core::{Instrument, Order, Trade};
+use certus_core::data::{MarketData, Tick, Timestamp};
+use certus_core::indicator::{BollingerBands, Indicator, MovingAverage};
+use certus_core::strategy::{
+    IndicatorHandle, MarketDataReceiver, Strategy, StrategyBase, StrategyIndicators, dispatch_market_data,
+};
+
+fn make_tick(price: f64) -> MarketData {
+    MarketData::Tick(Tick {
+        timestamp: Timestamp::from_nanos(0),
+        price,
+        size: 1.0,
+    })
+}
+
+#[test]
+fn registered_indicators_are_updated_and_accessible() {
+    let mut indicators = StrategyIndicators::new();
+    let fast = indicators.register(MovingAverage::new(2, 5));
+    let bands = indicators.register(BollingerBands::new(3, 2.0, 5));
+    assert_eq!(indicators.len(), 2);
+
+    for price in [1.0, 2.0, 3.0] {
+        indicators.update(make_tick(price));
+    }
+
+    assert_eq!(indicators.get(fast).value(), Some(2.5));
+    assert_eq!(indicators.get(bands).middle().value(), Some(2.0));
+    assert_eq!(indicators.updates(), 3);
+}
+
+#[test]
+fn warmup_period_is_longest_indicator_period() {
+    let mut indicators = StrategyIndicators::new();
+    assert_eq!(indicators.warmup_period(), 0);
+    assert!(indicators.is_ready());
+
+    indicators.register(MovingAverage::new(2, 5));
+    indicators.register(MovingAverage::new(4, 5));
+    assert_eq!(indicators.warmup_period(), 4);
+
+    for price in [1.0, 2.0, 3.0] {
+        indicators.update(make_tick(price));
+        assert!(!indicators.is_ready());
+    }
+    indicators.update(make_tick(4.0));
+    assert!(indicators.is_ready());
+}
+
+/// Broker without instruments that keeps the placed orders
+#[derive(Default)]
+struct NullBroker {
+    orders: Vec<Order>,
+    instruments: Vec<Instrument>,
+}
+
+impl Broker for NullBroker {
+    fn place_order(&mut self, order: Order) -> &Order {
+        self.orders.push(order);
+        self.orders.last().unwrap()
+    }
+
+    fn cancel_order(&mut self, _order_id: usize) -> bool {
+        false
+    }
+
+    fn add_instrument(&mut self, mut instrument: Instrument) -> &Instrument {
+        instrument.id = Some(self.instruments.len() as u32 + 1);
+        self.instruments.push(instrument);
+        self.instruments.last().unwrap()
+    }
+
+    fn get_instrument(&mut self, instrument_id: u32) -> Option<&Instrument> {
+        self.instruments.iter().find(|instrument| instrument.id == Some(instrument_id))
+    }
+
+    fn get_current_position(&mut self, _strategy_id: usize, _instrument_id: u32) -> f64 {
+        0.0
+    }
+
+    fn get_open_trades(&mut self, _strategy_id: usize, _instrument_id: u32) -> Vec<&Trade> {
+        Vec::new()
+    }
+}
+
+struct RecordingStrategy {
+    indicators: StrategyIndicators,
+    ma: IndicatorHandle<MovingAverage>,
+    next_values: Rc<RefCell<Vec<f64>>>,
+}
+
+impl MarketDataReceiver for RecordingStrategy {
+    fn update(&mut self, _market_data: MarketData, _broker: &mut dyn Broker) {}
+}
+
+impl StrategyBase for RecordingStrategy {
+    fn init(&mut self, _id: usize) {}
+
+    fn get_id(&self) -> usize {
+        1
+    }
+
+    fn get_instrument(&self) -> u32 {
+        1
+    }
+
+    fn get_indicators(&mut self) -> Option<&mut StrategyIndicators> {
+        Some(&mut self.indicators)
+    }
+}
+
+impl Strategy for RecordingStrategy {
+    fn next(&mut self, _market_data: MarketData, _broker: &mut dyn Broker) {
+        let value = self.indicators.get(self.ma).value().unwrap();
+        self.next_values.borrow_mut().push(value);
+    }
+}
+
+#[test]
+fn dispatch_updates_indicators_and_waits_for_warmup() {
+    let next_values = Rc::new(RefCell::new(Vec::new()));
+    let mut indicators = StrategyIndicators::new();
+    let ma = indicators.register(MovingAverage::new(3, 10));
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(RecordingStrategy {
+        indicators,
+        ma,
+        next_values: next_values.clone(),
+    })];
+    let mut broker = NullBroker::default();
+
+    for price in [1.0, 2.0, 3.0, 4.0, 5.0] {
+        dispatch_market_data(&mut strategies, make_tick(price), &mut broker);
+    }
+
+    let indicators = strategies[0].get_indicators().unwrap();
+    assert_eq!(indicators.updates(), 5);
+    assert_eq!(indicators.get(ma).value(), Some(4.0));
+    assert_eq!(*next_values.borrow(), vec![2.0, 3.0, 4.0]);
+}
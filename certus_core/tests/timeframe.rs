@@ -0,0 +1,111 @@
+use certus_core::data::Timestamp;
+use certus_core::timeframe::{Timeframe, trading_date};
+use chrono_tz::America::Chicago;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+fn at(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn globex_open() -> NaiveTime {
+    NaiveTime::from_hms_opt(17, 0, 0).unwrap()
+}
+
+#[test]
+fn intraday_buckets_longer_than_an_hour() {
+    let midnight = NaiveTime::MIN;
+    assert_eq!(Timeframe::Minutes(240).bucket_start(at(3, 5, 13, 45), midnight), at(3, 5, 12, 0));
+    assert_eq!(Timeframe::Hours(2).bucket_start(at(3, 5, 1, 59), midnight), at(3, 5, 0, 0));
+    assert_eq!(Timeframe::Seconds(30).bucket_start(at(3, 5, 1, 59), midnight), at(3, 5, 1, 59));
+    assert_eq!(Timeframe::Hours(2).bucket_end(at(3, 5, 0, 0), midnight), at(3, 5, 2, 0));
+}
+
+#[test]
+fn intraday_buckets_align_to_session_open() {
+    // 4 hour buckets counted from 17:00: 17, 21, 1, 5, ...
+    let timeframe = Timeframe::Hours(4);
+    assert_eq!(timeframe.bucket_start(at(3, 5, 18, 0), globex_open()), at(3, 5, 17, 0));
+    assert_eq!(timeframe.bucket_start(at(3, 6, 0, 30), globex_open()), at(3, 5, 21, 0));
+    assert_eq!(timeframe.bucket_start(at(3, 6, 2, 0), globex_open()), at(3, 6, 1, 0));
+}
+
+#[test]
+fn daily_buckets_start_at_session_open() {
+    let daily = Timeframe::Days(1);
+    // Tuesday 18:00 belongs to Wednesday's session
+    assert_eq!(trading_date(at(3, 5, 18, 0), globex_open()), NaiveDate::from_ymd_opt(2024, 3, 6).unwrap());
+    assert_eq!(daily.bucket_start(at(3, 5, 18, 0), globex_open()), at(3, 5, 17, 0));
+    assert_eq!(daily.bucket_start(at(3, 6, 16, 59), globex_open()), at(3, 5, 17, 0));
+    assert_eq!(daily.bucket_end(at(3, 5, 17, 0), globex_open()), at(3, 6, 17, 0));
+
+    let equities_open = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+    assert_eq!(daily.bucket_start(at(3, 5, 15, 0), equities_open), at(3, 5, 9, 30));
+}
+
+#[test]
+fn weekly_buckets_start_on_monday_session() {
+    let weekly = Timeframe::Weeks(1);
+    // Wednesday 2024-03-06, week starts with the Sunday evening session
+    assert_eq!(weekly.bucket_start(at(3, 6, 10, 0), globex_open()), at(3, 3, 17, 0));
+    assert_eq!(weekly.bucket_start(at(3, 3, 18, 0), globex_open()), at(3, 3, 17, 0));
+    assert_eq!(weekly.bucket_end(at(3, 3, 17, 0), globex_open()), at(3, 10, 17, 0));
+    assert_eq!(weekly.bucket_start(at(3, 6, 10, 0), NaiveTime::MIN), at(3, 4, 0, 0));
+}
+
+#[test]
+fn monthly_buckets() {
+    let midnight = NaiveTime::MIN;
+    assert_eq!(Timeframe::Months(1).bucket_start(at(2, 29, 10, 0), midnight), at(2, 1, 0, 0));
+    assert_eq!(Timeframe::Months(1).bucket_end(at(2, 1, 0, 0), midnight), at(3, 1, 0, 0));
+    assert_eq!(Timeframe::Months(3).bucket_start(at(5, 15, 10, 0), midnight), at(4, 1, 0, 0));
+    // The session of March 1st opens on February 29th at 17:00
+    assert_eq!(Timeframe::Months(1).bucket_start(at(2, 29, 18, 0), globex_open()), at(2, 29, 17, 0));
+}
+
+#[test]
+fn multiples_of_timeframes() {
+    assert!(Timeframe::Minutes(30).is_multiple_of(&Timeframe::Minutes(1)));
+    assert!(Timeframe::Hours(2).is_multiple_of(&Timeframe::Minutes(30)));
+    assert!(Timeframe::Months(1).is_multiple_of(&Timeframe::Minutes(1)));
+    assert!(Timeframe::Months(3).is_multiple_of(&Timeframe::Months(1)));
+    assert!(!Timeframe::Minutes(7).is_multiple_of(&Timeframe::Minutes(5)));
+    assert!(!Timeframe::Days(1).is_multiple_of(&Timeframe::Months(1)));
+}
+
+#[test]
+fn hourly_buckets_keep_their_length_across_dst() {
+    // Clocks in Chicago spring forward from 02:00 to 03:00 on 2024-03-10
+    let timeframe = Timeframe::Hours(1);
+    let local = |hour: u32, minute: u32| Timestamp::from_local(at(3, 10, hour, minute), &Chicago);
+
+    let start = timeframe.bucket_start_at(local(3, 30), NaiveTime::MIN, &Chicago);
+    assert_eq!(start, local(3, 0));
+    assert_eq!(timeframe.bucket_end_at(start, NaiveTime::MIN, &Chicago) - start, chrono::Duration::hours(1));
+    assert_eq!(timeframe.bucket_start_at(local(1, 59), NaiveTime::MIN, &Chicago), local(1, 0));
+}
+
+#[test]
+fn daily_buckets_start_at_the_local_session_open() {
+    let timeframe = Timeframe::Days(1);
+    let utc = |month: u32, day: u32, hour: u32| Timestamp::from_naive_utc(at(month, day, hour, 0));
+
+    // 17:00 CT is 23:00 UTC before and 22:00 UTC after the DST transition
+    assert_eq!(timeframe.bucket_start_at(utc(3, 6, 2), globex_open(), &Chicago), utc(3, 5, 23));
+    assert_eq!(timeframe.bucket_start_at(utc(3, 13, 2), globex_open(), &Chicago), utc(3, 12, 22));
+    assert_eq!(timeframe.bucket_end_at(utc(3, 5, 23), globex_open(), &Chicago), utc(3, 6, 23));
+}
+
+#[test]
+#[should_panic(expected = "timeframe (0m) must have a count of at least 1")]
+fn empty_intraday_timeframes_are_rejected() {
+    Timeframe::Minutes(0).bucket_start_at(Timestamp::from_seconds(0), globex_open(), &Chicago);
+}
+
+#[test]
+#[should_panic(expected = "must have a count of at least 1")]
+fn empty_monthly_timeframes_are_rejected() {
+    Timeframe::Months(0).bucket_start(at(3, 5, 18, 0), globex_open());
+}
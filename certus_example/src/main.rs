@@ -1,21 +1,19 @@
-pub mod data;
 pub mod strategy;
 
 use certus_bt::broker::BacktestingBroker;
 
-use certus_bt::csv_data_handler::CSVDataHandler;
+use certus_bt::csv_data_handler::{GenericCSVRowParser, StreamingCSVDataHandler};
 use certus_bt::data::HistoricBarConsolidationModel;
 use certus_bt::engine::{BacktestingEngine, BacktestingExecutionEngine};
 use certus_core::broker::Broker;
 use certus_core::core::{Instrument, InstrumentType};
+use certus_core::data::DataHandlerError;
 use certus_core::engine::Engine;
+use chrono_tz::America;
 
-use crate::data::TradeStationCSVRowParser;
 use crate::strategy::SimpleStrategy;
 
-use env_logger;
-
-fn main() {
+fn main() -> Result<(), DataHandlerError> {
     env_logger::init();
 
     let mut broker = BacktestingBroker::new(100_000.0);
@@ -28,9 +26,9 @@ fn main() {
     );
     let instrument_es_ref = broker.add_instrument(instrument_es);
 
-    let ts_row_parser = TradeStationCSVRowParser::new();
-    let bar_consolidation_model = HistoricBarConsolidationModel::new(1, 30);
-    let data_handler = CSVDataHandler::new(
+    let ts_row_parser = GenericCSVRowParser::tradestation(America::Chicago);
+    let bar_consolidation_model = HistoricBarConsolidationModel::new(1, 30).with_time_zone(America::Chicago);
+    let data_handler = StreamingCSVDataHandler::new(
         String::from("./data/ES-1M-20150101-20251219.csv"),
         Box::new(ts_row_parser),
         bar_consolidation_model,
@@ -40,14 +38,15 @@ fn main() {
 
     let mut engine = BacktestingEngine {
         data_handler: Box::new(data_handler),
-        broker: broker,
+        broker,
         execution_engine: Box::new(execution_engine),
         strategies: vec![Box::new(strategy)],
     };
 
     engine.init();
-    engine.run();
+    engine.run()?;
 
     // broker.print_stats();
     // broker.generate_equity_curve();
+    Ok(())
 }
@@ -2,7 +2,9 @@ use certus_core::broker::Broker;
 use certus_core::core::{OrderSide, OrderType};
 use certus_core::data::MarketData;
 use certus_core::indicator::{Indicator, MovingAverage};
-use certus_core::strategy::{Strategy, MarketDataReceiver, StrategyBase, StrategyData};
+use certus_core::strategy::{
+    IndicatorHandle, MarketDataReceiver, Strategy, StrategyBase, StrategyData, StrategyIndicators,
+};
 
 use log;
 
@@ -10,18 +12,24 @@ pub struct SimpleStrategy {
     id: usize,
     instrument: u32,
     data: StrategyData,
-    ma_slow: MovingAverage,
-    ma_fast: MovingAverage,
+    indicators: StrategyIndicators,
+    ma_slow: IndicatorHandle<MovingAverage>,
+    ma_fast: IndicatorHandle<MovingAverage>,
 }
 
 impl SimpleStrategy {
     pub fn new(instrument: u32) -> Self {
+        let mut indicators = StrategyIndicators::new();
+        let ma_fast = indicators.register(MovingAverage::new(7, 50));
+        let ma_slow = indicators.register(MovingAverage::new(21, 50));
+
         Self {
             id: 0,
             instrument,
             data: StrategyData::new(50),
-            ma_fast: MovingAverage::new(7, 50),
-            ma_slow: MovingAverage::new(21, 50),
+            indicators,
+            ma_fast,
+            ma_slow,
         }
     }
 }
@@ -29,9 +37,6 @@ impl SimpleStrategy {
 impl MarketDataReceiver for SimpleStrategy {
     fn update(&mut self, market_data: MarketData, broker: &mut dyn Broker) {
         self.data.update(market_data, broker);
-
-        self.ma_fast.update(market_data);
-        self.ma_slow.update(market_data);
     }
 }
 
@@ -47,31 +52,43 @@ impl StrategyBase for SimpleStrategy {
     fn get_instrument(&self) -> u32 {
         self.instrument
     }
+
+    fn get_indicators(&mut self) -> Option<&mut StrategyIndicators> {
+        Some(&mut self.indicators)
+    }
 }
 
 impl Strategy for SimpleStrategy {
-    fn next(&mut self, _market_data: MarketData, broker: &mut dyn Broker) {
-        if !self.data.is_ready() {
-            return;
-        }
-
-        let current_bar = match self.data.market_data.front() {
-            Some(MarketData::Bar(bar)) => bar,
+    fn next(&mut self, market_data: MarketData, broker: &mut dyn Broker) {
+        let current_bar = match market_data {
+            MarketData::Bar(bar) => bar,
             _ => unreachable!("Expected only MarketData::Bar"),
         };
 
+        let ma_fast = self.indicators.get(self.ma_fast);
+        let ma_slow = self.indicators.get(self.ma_slow);
+
         log::debug!(
             "Close {} - MA Fast {} - MA Slow {}",
             current_bar.close,
-            self.ma_fast.value,
-            self.ma_slow.value
+            ma_fast.value().unwrap_or_default(),
+            ma_slow.value().unwrap_or_default()
         );
 
+        // A crossover needs the previous values, the first bar after the warmup has none yet
+        let fast_previous = ma_fast.outputs()[0].get(1);
+        let slow_previous = ma_slow.outputs()[0].get(1);
+        let (Some(fast_previous), Some(slow_previous)) = (fast_previous, slow_previous) else {
+            return;
+        };
+        let crossover_up = fast_previous < slow_previous && ma_fast[0] > ma_slow[0];
+        let crossover_down = fast_previous > slow_previous && ma_fast[0] < ma_slow[0];
+
         let cur_pos = broker.get_current_position(self.id, self.instrument);
         let mut cur_trade_id: Option<usize> = None;
         if cur_pos != 0.0 {
             let trades = broker.get_open_trades(self.id, self.instrument);
-            if trades.len() > 0 {
+            if !trades.is_empty() {
                 cur_trade_id = Some(trades.first().map(|t| t.id).unwrap());
             }
         }
@@ -79,13 +96,13 @@ impl Strategy for SimpleStrategy {
         // Check for entry
         if cur_pos == 0.0 {
             // Crossover upside check
-            if self.ma_fast[1] < self.ma_slow[1] && self.ma_fast[0] > self.ma_slow[0] {
+            if crossover_up {
                 log::info!("[ENTRY] Crossover UP");
                 let _order_id = self.place_order(broker, OrderSide::Buy, OrderType::Market, 1.0);
             }
 
             // Crossover downside check
-            if self.ma_fast[1] > self.ma_slow[1] && self.ma_fast[0] < self.ma_slow[0] {
+            if crossover_down {
                 log::info!("[ENTRY] Crossover DOWN");
                 let _order_id = self.place_order(broker, OrderSide::Sell, OrderType::Market, 1.0);
             }
@@ -94,7 +111,7 @@ impl Strategy for SimpleStrategy {
         // Check for exiting long
         else if cur_pos > 0.0 {
             // Crossover downside check
-            if self.ma_fast[1] > self.ma_slow[1] && self.ma_fast[0] < self.ma_slow[0] {
+            if crossover_down {
                 log::info!("[EXIT] Crossover DOWN");
                 let _order_id = self.place_related_order(broker, OrderSide::Buy, OrderType::Market, 1.0, cur_trade_id.unwrap());
             }
@@ -103,7 +120,7 @@ impl Strategy for SimpleStrategy {
         // Check for exiting short
         else if cur_pos < 0.0 {
             // Crossover upside check
-            if self.ma_fast[1] < self.ma_slow[1] && self.ma_fast[0] > self.ma_slow[0] {
+            if crossover_up {
                 log::info!("[EXIT] Crossover UP");
                 let _order_id = self.place_related_order(broker, OrderSide::Sell, OrderType::Market, 1.0, cur_trade_id.unwrap());
             }
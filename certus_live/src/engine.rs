@@ -1,20 +1,30 @@
 use std::hint;
 
-use certus_core::data::DataHandler;
+use certus_core::broker::Broker;
+use certus_core::data::{DataHandler, DataHandlerError};
 use certus_core::engine::Engine;
+use certus_core::strategy::{Strategy, dispatch_market_data};
 
 pub struct LiveEngine {
     pub data_handler: Box<dyn DataHandler>,
+    pub broker: Box<dyn Broker>,
+    pub strategies: Vec<Box<dyn Strategy>>,
 }
 
 impl Engine for LiveEngine {
-    fn init(&mut self) {}
+    fn init(&mut self) {
+        for (index, strategy) in self.strategies.iter_mut().enumerate() {
+            strategy.init(index + 1);
+        }
+    }
 
-    fn run(&mut self) {
+    /// Runs until the data feed fails
+    fn run(&mut self) -> Result<(), DataHandlerError> {
         let mut data_feed = self.data_handler.get_data_feed();
         loop {
-            if let Some(market_data) = data_feed.poll() {
+            if let Some(market_data) = data_feed.poll()? {
                 println!("{}", market_data);
+                dispatch_market_data(&mut self.strategies, market_data, self.broker.as_mut());
             } else {
                 hint::spin_loop();
             }